@@ -26,7 +26,24 @@ CREATE TABLE IF NOT EXISTS files (
     owner BIGINT REFERENCES users (id),
     is_deleted bool DEFAULT FALSE,
     is_public bool DEFAULT FALSE,
-    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    processing_status VARCHAR(20) NOT NULL DEFAULT 'unprocessed',
+    duration_ms INT,
+    codec VARCHAR(32),
+    sample_rate INT,
+    channels SMALLINT,
+    bitrate INT,
+    deleted_at timestamp,
+    checksum VARCHAR(64),
+    content_hash VARCHAR(64),
+    storage_key VARCHAR(255) NOT NULL,
+    size BIGINT,
+    description VARCHAR(500),
+    start_ms INT,
+    end_ms INT,
+    play_count BIGINT NOT NULL DEFAULT 0,
+    forked_from BIGINT,
+    forked_from_owner BIGINT
 );
 
 CREATE TABLE IF NOT EXISTS guild (
@@ -41,5 +58,137 @@ CREATE TABLE IF NOT EXISTS guild_file(
     file_id BIGINT REFERENCES files(id),
     PRIMARY KEY (guild_id, file_id),
     is_deleted bool DEFAULT FALSE,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    deleted_with_file bool NOT NULL DEFAULT false
+);
+
+CREATE TABLE IF NOT EXISTS file_waveform(
+    file_id BIGINT PRIMARY KEY REFERENCES files(id) ON DELETE CASCADE,
+    peaks REAL[] NOT NULL,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS upload_session(
+    id BIGINT PRIMARY KEY,
+    owner BIGINT NOT NULL REFERENCES users(id),
+    display_name VARCHAR(50) NOT NULL,
+    is_public bool NOT NULL DEFAULT FALSE,
+    size BIGINT NOT NULL,
+    uploaded_size BIGINT NOT NULL DEFAULT 0,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at timestamp NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS tag(
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(32) NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS file_tag(
+    file_id BIGINT REFERENCES files(id) ON DELETE CASCADE,
+    tag_id INT REFERENCES tag(id) ON DELETE CASCADE,
+    PRIMARY KEY (file_id, tag_id)
+);
+
+CREATE TABLE IF NOT EXISTS file_version(
+    file_id BIGINT PRIMARY KEY REFERENCES files(id) ON DELETE CASCADE,
+    storage_key VARCHAR(255) NOT NULL,
+    content_hash VARCHAR(64),
+    checksum VARCHAR(64),
+    processing_status VARCHAR(20) NOT NULL,
+    duration_ms INT,
+    codec VARCHAR(32),
+    sample_rate INT,
+    channels SMALLINT,
+    bitrate INT,
+    size BIGINT,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    start_ms INT,
+    end_ms INT
+);
+
+CREATE TABLE IF NOT EXISTS favorite(
+    user_id BIGINT REFERENCES users(id) ON DELETE CASCADE,
+    file_id BIGINT REFERENCES files(id) ON DELETE CASCADE,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, file_id)
+);
+
+CREATE TABLE IF NOT EXISTS pack(
+    id BIGINT PRIMARY KEY,
+    owner BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(50) NOT NULL,
+    description VARCHAR(500),
+    is_public BOOLEAN NOT NULL DEFAULT false,
     time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP
 );
+
+CREATE TABLE IF NOT EXISTS pack_file(
+    pack_id BIGINT REFERENCES pack(id) ON DELETE CASCADE,
+    file_id BIGINT REFERENCES files(id) ON DELETE CASCADE,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (pack_id, file_id)
+);
+
+CREATE TABLE IF NOT EXISTS guild_pack(
+    guild_id BIGINT REFERENCES guild(id) ON DELETE CASCADE,
+    pack_id BIGINT REFERENCES pack(id) ON DELETE CASCADE,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (guild_id, pack_id)
+);
+
+CREATE TABLE IF NOT EXISTS transfer_offer(
+    id BIGINT PRIMARY KEY,
+    from_user BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    to_user BIGINT NOT NULL,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at timestamp NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS transfer_offer_file(
+    offer_id BIGINT REFERENCES transfer_offer(id) ON DELETE CASCADE,
+    file_id BIGINT REFERENCES files(id) ON DELETE CASCADE,
+    PRIMARY KEY (offer_id, file_id)
+);
+
+CREATE TABLE IF NOT EXISTS ownership_transfer(
+    id BIGSERIAL PRIMARY KEY,
+    file_id BIGINT NOT NULL,
+    from_user BIGINT NOT NULL,
+    to_user BIGINT NOT NULL,
+    offer_id BIGINT NOT NULL,
+    time_transferred timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS guild_settings(
+    guild_id BIGINT PRIMARY KEY REFERENCES guild(id) ON DELETE CASCADE,
+    volume SMALLINT NOT NULL DEFAULT 100,
+    max_queue_length SMALLINT NOT NULL DEFAULT 10,
+    members_can_add_public_files BOOLEAN NOT NULL DEFAULT true,
+    default_voice_channel_id BIGINT
+);
+
+CREATE TABLE IF NOT EXISTS guild_role_permission(
+    guild_id BIGINT REFERENCES guild(id) ON DELETE CASCADE,
+    role_id BIGINT NOT NULL,
+    can_play BOOLEAN NOT NULL DEFAULT false,
+    can_add BOOLEAN NOT NULL DEFAULT false,
+    can_remove BOOLEAN NOT NULL DEFAULT false,
+    can_manage BOOLEAN NOT NULL DEFAULT false,
+    PRIMARY KEY (guild_id, role_id)
+);
+
+CREATE TABLE IF NOT EXISTS guild_member_roles(
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    role_ids BIGINT[] NOT NULL DEFAULT '{}',
+    PRIMARY KEY (guild_id, user_id)
+);
+
+CREATE TABLE IF NOT EXISTS guild_manager(
+    guild_id BIGINT REFERENCES guild(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    added_by BIGINT NOT NULL,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (guild_id, user_id)
+);
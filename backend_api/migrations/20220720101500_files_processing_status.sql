@@ -0,0 +1,2 @@
+ALTER TABLE files
+ADD processing_status VARCHAR(20) NOT NULL DEFAULT 'unprocessed';
@@ -0,0 +1,6 @@
+ALTER TABLE files
+ADD duration_ms INT,
+ADD codec VARCHAR(32),
+ADD sample_rate INT,
+ADD channels SMALLINT,
+ADD bitrate INT;
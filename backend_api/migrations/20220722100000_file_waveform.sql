@@ -0,0 +1,5 @@
+CREATE TABLE file_waveform(
+    file_id BIGINT PRIMARY KEY REFERENCES files(id) ON DELETE CASCADE,
+    peaks REAL[] NOT NULL,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,6 @@
+ALTER TABLE files
+ADD deleted_at timestamp;
+
+UPDATE files
+SET deleted_at = CURRENT_TIMESTAMP
+WHERE is_deleted = true;
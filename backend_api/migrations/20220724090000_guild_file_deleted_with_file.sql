@@ -0,0 +1,9 @@
+ALTER TABLE guild_file
+ADD deleted_with_file bool NOT NULL DEFAULT false;
+
+UPDATE guild_file
+SET is_deleted = true, deleted_with_file = true
+FROM files
+WHERE files.id = guild_file.file_id
+AND files.is_deleted = true
+AND guild_file.is_deleted = false;
@@ -0,0 +1,2 @@
+ALTER TABLE files
+ADD checksum VARCHAR(64);
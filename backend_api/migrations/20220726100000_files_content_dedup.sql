@@ -0,0 +1,13 @@
+ALTER TABLE files
+ADD content_hash VARCHAR(64),
+ADD storage_key VARCHAR(255);
+
+-- Files uploaded before deduplication are stored under their id
+UPDATE files
+SET storage_key = id::text;
+
+ALTER TABLE files
+ALTER COLUMN storage_key SET NOT NULL;
+
+CREATE INDEX files_content_hash_idx ON files (content_hash);
+CREATE INDEX files_storage_key_idx ON files (storage_key);
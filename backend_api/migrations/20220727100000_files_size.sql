@@ -0,0 +1,2 @@
+ALTER TABLE files
+ADD size BIGINT;
@@ -0,0 +1,12 @@
+CREATE TABLE upload_session(
+    id BIGINT PRIMARY KEY,
+    owner BIGINT NOT NULL REFERENCES users(id),
+    display_name VARCHAR(50) NOT NULL,
+    is_public bool NOT NULL DEFAULT FALSE,
+    size BIGINT NOT NULL,
+    uploaded_size BIGINT NOT NULL DEFAULT 0,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at timestamp NOT NULL
+);
+
+CREATE INDEX upload_session_expires_at_idx ON upload_session (expires_at);
@@ -0,0 +1,12 @@
+CREATE TABLE tag(
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(32) NOT NULL UNIQUE
+);
+
+CREATE TABLE file_tag(
+    file_id BIGINT REFERENCES files(id) ON DELETE CASCADE,
+    tag_id INT REFERENCES tag(id) ON DELETE CASCADE,
+    PRIMARY KEY (file_id, tag_id)
+);
+
+CREATE INDEX file_tag_tag_id_idx ON file_tag (tag_id);
@@ -0,0 +1,2 @@
+ALTER TABLE files
+ADD description VARCHAR(500);
@@ -0,0 +1,16 @@
+CREATE TABLE file_version(
+    file_id BIGINT PRIMARY KEY REFERENCES files(id) ON DELETE CASCADE,
+    storage_key VARCHAR(255) NOT NULL,
+    content_hash VARCHAR(64),
+    checksum VARCHAR(64),
+    processing_status VARCHAR(20) NOT NULL,
+    duration_ms INT,
+    codec VARCHAR(32),
+    sample_rate INT,
+    channels SMALLINT,
+    bitrate INT,
+    size BIGINT,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX file_version_storage_key_idx ON file_version (storage_key);
@@ -0,0 +1,7 @@
+ALTER TABLE files
+ADD start_ms INT,
+ADD end_ms INT;
+
+ALTER TABLE file_version
+ADD start_ms INT,
+ADD end_ms INT;
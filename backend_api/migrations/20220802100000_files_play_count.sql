@@ -0,0 +1,2 @@
+ALTER TABLE files
+ADD play_count BIGINT NOT NULL DEFAULT 0;
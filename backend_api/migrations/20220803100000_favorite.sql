@@ -0,0 +1,8 @@
+CREATE TABLE favorite(
+    user_id BIGINT REFERENCES users(id) ON DELETE CASCADE,
+    file_id BIGINT REFERENCES files(id) ON DELETE CASCADE,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (user_id, file_id)
+);
+
+CREATE INDEX favorite_file_id_idx ON favorite (file_id);
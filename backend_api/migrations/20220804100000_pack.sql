@@ -0,0 +1,24 @@
+CREATE TABLE pack(
+    id BIGINT PRIMARY KEY,
+    owner BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(50) NOT NULL,
+    description VARCHAR(500),
+    is_public BOOLEAN NOT NULL DEFAULT false,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX pack_owner_idx ON pack (owner);
+
+CREATE TABLE pack_file(
+    pack_id BIGINT REFERENCES pack(id) ON DELETE CASCADE,
+    file_id BIGINT REFERENCES files(id) ON DELETE CASCADE,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (pack_id, file_id)
+);
+
+CREATE TABLE guild_pack(
+    guild_id BIGINT REFERENCES guild(id) ON DELETE CASCADE,
+    pack_id BIGINT REFERENCES pack(id) ON DELETE CASCADE,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (guild_id, pack_id)
+);
@@ -0,0 +1,4 @@
+-- Provenance of forked files, kept without references so it outlives the original file and owner
+ALTER TABLE files
+ADD forked_from BIGINT,
+ADD forked_from_owner BIGINT;
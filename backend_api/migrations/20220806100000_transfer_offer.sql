@@ -0,0 +1,28 @@
+CREATE TABLE transfer_offer(
+    id BIGINT PRIMARY KEY,
+    from_user BIGINT NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Recipient doesn't have to be registered until the offer is accepted
+    to_user BIGINT NOT NULL,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at timestamp NOT NULL
+);
+
+CREATE INDEX transfer_offer_to_user_idx ON transfer_offer (to_user);
+
+CREATE TABLE transfer_offer_file(
+    offer_id BIGINT REFERENCES transfer_offer(id) ON DELETE CASCADE,
+    file_id BIGINT REFERENCES files(id) ON DELETE CASCADE,
+    PRIMARY KEY (offer_id, file_id)
+);
+
+-- Audit of owner changes, kept without references so it outlives purged files and users
+CREATE TABLE ownership_transfer(
+    id BIGSERIAL PRIMARY KEY,
+    file_id BIGINT NOT NULL,
+    from_user BIGINT NOT NULL,
+    to_user BIGINT NOT NULL,
+    offer_id BIGINT NOT NULL,
+    time_transferred timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX ownership_transfer_file_id_idx ON ownership_transfer (file_id);
@@ -0,0 +1,7 @@
+CREATE TABLE guild_settings(
+    guild_id BIGINT PRIMARY KEY REFERENCES guild(id) ON DELETE CASCADE,
+    volume SMALLINT NOT NULL DEFAULT 100,
+    max_queue_length SMALLINT NOT NULL DEFAULT 10,
+    members_can_add_public_files BOOLEAN NOT NULL DEFAULT true,
+    default_voice_channel_id BIGINT
+);
@@ -0,0 +1,16 @@
+CREATE TABLE guild_role_permission(
+    guild_id BIGINT REFERENCES guild(id) ON DELETE CASCADE,
+    role_id BIGINT NOT NULL,
+    can_play BOOLEAN NOT NULL DEFAULT false,
+    can_add BOOLEAN NOT NULL DEFAULT false,
+    can_remove BOOLEAN NOT NULL DEFAULT false,
+    can_manage BOOLEAN NOT NULL DEFAULT false,
+    PRIMARY KEY (guild_id, role_id)
+);
+
+CREATE TABLE guild_member_roles(
+    guild_id BIGINT NOT NULL,
+    user_id BIGINT NOT NULL,
+    role_ids BIGINT[] NOT NULL DEFAULT '{}',
+    PRIMARY KEY (guild_id, user_id)
+);
@@ -0,0 +1,7 @@
+CREATE TABLE guild_manager(
+    guild_id BIGINT REFERENCES guild(id) ON DELETE CASCADE,
+    user_id BIGINT NOT NULL,
+    added_by BIGINT NOT NULL,
+    time_added timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (guild_id, user_id)
+);
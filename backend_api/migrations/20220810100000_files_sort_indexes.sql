@@ -0,0 +1,4 @@
+-- Keyset pagination of file listings, the name sort is case insensitive
+CREATE INDEX files_time_added_id_idx ON files (time_added DESC, id DESC);
+CREATE INDEX files_name_id_idx ON files (LOWER(COALESCE(display_name, '')), id);
+CREATE INDEX files_play_count_id_idx ON files (play_count DESC, id DESC);
@@ -0,0 +1,152 @@
+use std::process::Stdio;
+
+use lazy_static::lazy_static;
+use serde::Deserialize;
+use tokio::{
+    fs::{remove_file, write},
+    process::Command,
+};
+
+use crate::{error::errors::KekServerError, models::sound_file::AudioMetadata};
+
+use super::temp_file_path;
+
+lazy_static! {
+    static ref FFPROBE_PATH: String =
+        dotenv::var("FFPROBE_PATH").unwrap_or_else(|_| "ffprobe".to_string());
+}
+
+#[derive(Debug, Deserialize)]
+struct FfprobeOutput {
+    #[serde(default)]
+    streams: Vec<FfprobeStream>,
+    format: Option<FfprobeFormat>,
+}
+
+// ffprobe outputs most numeric values as strings
+#[derive(Debug, Deserialize)]
+struct FfprobeStream {
+    codec_name: Option<String>,
+    sample_rate: Option<String>,
+    channels: Option<i16>,
+    bit_rate: Option<String>,
+    duration: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FfprobeFormat {
+    duration: Option<String>,
+    bit_rate: Option<String>,
+}
+
+/// Probes the first audio stream of the file using ffprobe.
+pub async fn probe_metadata(name: &str, data: &[u8]) -> Result<AudioMetadata, KekServerError> {
+    let input_path = temp_file_path(&format!("{}_probe", name));
+    write(&input_path, data).await?;
+
+    let output = Command::new(&*FFPROBE_PATH)
+        .args([
+            "-v",
+            "error",
+            "-select_streams",
+            "a:0",
+            "-show_entries",
+            "stream=codec_name,sample_rate,channels,bit_rate,duration:format=duration,bit_rate",
+            "-of",
+            "json",
+        ])
+        .arg(&input_path)
+        .stdin(Stdio::null())
+        .output()
+        .await;
+
+    let _ = remove_file(&input_path).await;
+
+    let output = output?;
+    if !output.status.success() {
+        return Err(KekServerError::AudioProcessingError(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+
+    return parse_ffprobe_output(&output.stdout);
+}
+
+fn parse_ffprobe_output(output: &[u8]) -> Result<AudioMetadata, KekServerError> {
+    let output: FfprobeOutput = serde_json::from_slice(output)?;
+    let stream = match output.streams.into_iter().next() {
+        Some(stream) => stream,
+        None => {
+            return Err(KekServerError::AudioProcessingError(
+                "No audio stream found".to_string(),
+            ))
+        }
+    };
+    let (format_duration, format_bit_rate) = match output.format {
+        Some(format) => (format.duration, format.bit_rate),
+        None => (None, None),
+    };
+
+    let duration_ms = format_duration
+        .or(stream.duration)
+        .and_then(|d| d.parse::<f64>().ok())
+        .map(|d| (d * 1000.0).round() as i32);
+
+    return Ok(AudioMetadata {
+        duration_ms,
+        codec: stream.codec_name,
+        sample_rate: stream.sample_rate.and_then(|s| s.parse().ok()),
+        channels: stream.channels,
+        bitrate: stream
+            .bit_rate
+            .or(format_bit_rate)
+            .and_then(|b| b.parse().ok()),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::sound_file::AudioMetadata;
+
+    use super::parse_ffprobe_output;
+
+    #[test]
+    fn test_parse_ffprobe_output() {
+        let output = br#"{
+            "programs": [],
+            "streams": [
+                {
+                    "codec_name": "mp3",
+                    "sample_rate": "44100",
+                    "channels": 2,
+                    "bit_rate": "128000",
+                    "duration": "3.500000"
+                }
+            ],
+            "format": {
+                "duration": "3.526531",
+                "bit_rate": "128341"
+            }
+        }"#;
+
+        let metadata = parse_ffprobe_output(output).unwrap();
+
+        assert_eq!(
+            metadata,
+            AudioMetadata {
+                duration_ms: Some(3527),
+                codec: Some("mp3".to_string()),
+                sample_rate: Some(44100),
+                channels: Some(2),
+                bitrate: Some(128000),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_ffprobe_output_without_audio_stream() {
+        let output = br#"{ "programs": [], "streams": [], "format": { "duration": "1.0" } }"#;
+
+        assert!(parse_ffprobe_output(output).is_err());
+    }
+}
@@ -0,0 +1,11 @@
+use std::path::PathBuf;
+
+pub mod metadata;
+pub mod processing;
+pub mod trim;
+pub mod waveform;
+
+/// Path in the temp directory for handing sound file data to ffmpeg tools.
+fn temp_file_path(name: &str) -> PathBuf {
+    return std::env::temp_dir().join(format!("ksv2_{}", name));
+}
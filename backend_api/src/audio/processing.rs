@@ -0,0 +1,185 @@
+use std::process::Stdio;
+
+use actix_web::web::Data;
+use lazy_static::lazy_static;
+use log::{error, info};
+use sqlx::PgPool;
+use tokio::{
+    fs::{read, remove_file, write},
+    process::Command,
+    sync::Semaphore,
+};
+
+use crate::{
+    error::errors::KekServerError,
+    models::sound_file::{AudioMetadata, ProcessingStatus, SoundFile},
+    storage::{checksum, original_storage_key, processing_key, SoundStorage},
+};
+
+use super::{metadata::probe_metadata, temp_file_path, waveform::generate_waveform};
+
+lazy_static! {
+    static ref FFMPEG_PATH: String =
+        dotenv::var("FFMPEG_PATH").unwrap_or_else(|_| "ffmpeg".to_string());
+    static ref TARGET_LOUDNESS: f64 = dotenv::var("TARGET_LOUDNESS")
+        .unwrap_or_else(|_| (-16.0).to_string())
+        .parse()
+        .unwrap_or(-16.0);
+    // Limits the number of concurrently running ffmpeg processes
+    static ref PROCESSING_PERMITS: Semaphore = Semaphore::new(2);
+}
+
+/// Converts uploaded files to loudness normalized Opus/Ogg in the background.
+///
+/// Processing results are shared by all files stored under the same key.
+pub fn spawn_processing(
+    files: Vec<SoundFile>,
+    db_pool: Data<PgPool>,
+    storage: Data<dyn SoundStorage>,
+) {
+    if files.is_empty() {
+        return;
+    }
+
+    actix_web::rt::spawn(async move {
+        for file in files {
+            let id = &file.id;
+            let key = &file.storage_key;
+            let (status, processed) = match process_sound_file(key, &storage).await {
+                Ok(processed) => {
+                    info!("Finished processing file with id: [{}]", id.0);
+                    (ProcessingStatus::Ready, Some(processed))
+                }
+                Err(e) => {
+                    error!("Failed to process file with id: [{}], error: {}", id.0, e);
+                    (ProcessingStatus::Failed, None)
+                }
+            };
+
+            if let Err(e) = update_processed_file(key, &status, processed, &db_pool).await {
+                error!(
+                    "Failed to set processing status for file with id: [{}], error: {}",
+                    id.0, e
+                );
+            }
+
+            if status == ProcessingStatus::Ready {
+                if let Err(e) = generate_waveform(id, key, &storage, &db_pool).await {
+                    error!(
+                        "Failed to generate waveform for file with id: [{}], error: {}",
+                        id.0, e
+                    );
+                }
+            }
+        }
+    });
+}
+
+/// Processing only lives in memory, files left pending by a restart are processed again.
+pub async fn requeue_pending_files(
+    db_pool: Data<PgPool>,
+    storage: Data<dyn SoundStorage>,
+) -> Result<usize, KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+    let files = SoundFile::get_pending_files(&mut transaction).await?;
+    transaction.commit().await?;
+
+    let count = files.len();
+    spawn_processing(files, db_pool, storage);
+    return Ok(count);
+}
+
+async fn update_processed_file(
+    storage_key: &str,
+    status: &ProcessingStatus,
+    processed: Option<ProcessedFile>,
+    db_pool: &Data<PgPool>,
+) -> Result<(), KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+    SoundFile::set_processing_status(storage_key, status, &mut transaction).await?;
+    if let Some(processed) = processed {
+        SoundFile::set_metadata(storage_key, &processed.metadata, &mut transaction).await?;
+        SoundFile::set_checksum(storage_key, &processed.checksum, &mut transaction).await?;
+    }
+    transaction.commit().await?;
+    return Ok(());
+}
+
+struct ProcessedFile {
+    metadata: AudioMetadata,
+    checksum: String,
+}
+
+/// Keeps the uploaded file as the original and replaces the playable file with the converted one.
+async fn process_sound_file(
+    key: &str,
+    storage: &Data<dyn SoundStorage>,
+) -> Result<ProcessedFile, KekServerError> {
+    let _permit = PROCESSING_PERMITS
+        .acquire()
+        .await
+        .map_err(|e| KekServerError::AudioProcessingError(e.to_string()))?;
+
+    let original_key = original_storage_key(key);
+    // Files processed again after a restart can already be converted, the kept original is used instead
+    let data = match storage.read(&original_key).await {
+        Ok(data) => data,
+        Err(_) => {
+            let data = storage.read(key).await?;
+            storage.save(&original_key, &data).await?;
+            data
+        }
+    };
+
+    let converted = transcode(key, &data).await?;
+    let metadata = probe_metadata(key, &converted).await?;
+    // Stored file stays intact if writing the converted file is interrupted
+    let temp_key = processing_key(key);
+    storage.save(&temp_key, &converted).await?;
+    storage.rename(&temp_key, key).await?;
+
+    return Ok(ProcessedFile {
+        metadata,
+        checksum: checksum(&converted),
+    });
+}
+
+async fn transcode(key: &str, data: &[u8]) -> Result<Vec<u8>, KekServerError> {
+    let input_path = temp_file_path(key);
+    let output_path = temp_file_path(&format!("{}_processed.ogg", key));
+    write(&input_path, data).await?;
+
+    let output = Command::new(&*FFMPEG_PATH)
+        .args(["-hide_banner", "-loglevel", "error", "-y", "-i"])
+        .arg(&input_path)
+        .args([
+            "-vn",
+            "-af",
+            &format!("loudnorm=I={}:TP=-1.5:LRA=11", *TARGET_LOUDNESS),
+            "-ar",
+            "48000",
+            "-c:a",
+            "libopus",
+            "-b:a",
+            "96k",
+            "-f",
+            "ogg",
+        ])
+        .arg(&output_path)
+        .stdin(Stdio::null())
+        .output()
+        .await;
+
+    let converted = match output {
+        Ok(output) if output.status.success() => read(&output_path).await.map_err(|e| e.into()),
+        Ok(output) => Err(KekServerError::AudioProcessingError(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        )),
+        Err(e) => Err(e.into()),
+    };
+
+    let _ = remove_file(&input_path).await;
+    let _ = remove_file(&output_path).await;
+
+    return converted;
+}
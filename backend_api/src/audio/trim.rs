@@ -0,0 +1,84 @@
+use std::process::Stdio;
+
+use lazy_static::lazy_static;
+use tokio::{
+    fs::{remove_file, write},
+    process::Command,
+    sync::Semaphore,
+};
+use uuid::Uuid;
+
+use crate::error::errors::KekServerError;
+
+use super::temp_file_path;
+
+lazy_static! {
+    static ref FFMPEG_PATH: String =
+        dotenv::var("FFMPEG_PATH").unwrap_or_else(|_| "ffmpeg".to_string());
+    // Previews are requested without auth, so trimming can't start an ffmpeg process per request
+    static ref TRIM_PERMITS: Semaphore = Semaphore::new(2);
+}
+
+/// Cuts the segment between the trim points and encodes it as Opus/Ogg, the stored audio is not modified.
+pub async fn trim_audio(
+    key: &str,
+    data: &[u8],
+    start_ms: Option<i32>,
+    end_ms: Option<i32>,
+) -> Result<Vec<u8>, KekServerError> {
+    let _permit = TRIM_PERMITS
+        .acquire()
+        .await
+        .map_err(|e| KekServerError::AudioProcessingError(e.to_string()))?;
+    // Same file can be trimmed by concurrent requests
+    let input_path = temp_file_path(&format!("{}_trim_{}", key, Uuid::new_v4()));
+    write(&input_path, data).await?;
+
+    let output = Command::new(&*FFMPEG_PATH)
+        .args(["-hide_banner", "-loglevel", "error", "-i"])
+        .arg(&input_path)
+        .args(segment_args(start_ms, end_ms))
+        .args(["-vn", "-c:a", "libopus", "-b:a", "96k", "-f", "ogg", "-"])
+        .stdin(Stdio::null())
+        .output()
+        .await;
+
+    let _ = remove_file(&input_path).await;
+
+    let output = output?;
+    if !output.status.success() {
+        return Err(KekServerError::AudioProcessingError(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+    return Ok(output.stdout);
+}
+
+/// Seek arguments of the segment, placed after the input so seeking is exact.
+fn segment_args(start_ms: Option<i32>, end_ms: Option<i32>) -> Vec<String> {
+    let mut args = Vec::new();
+    if let Some(start_ms) = start_ms {
+        args.push("-ss".to_string());
+        args.push(format!("{}ms", start_ms));
+    }
+    if let Some(end_ms) = end_ms {
+        args.push("-to".to_string());
+        args.push(format!("{}ms", end_ms));
+    }
+    return args;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::segment_args;
+
+    #[test]
+    fn test_segment_args() {
+        assert_eq!(
+            segment_args(Some(1500), Some(4000)),
+            vec!["-ss", "1500ms", "-to", "4000ms"]
+        );
+        assert_eq!(segment_args(None, Some(4000)), vec!["-to", "4000ms"]);
+        assert!(segment_args(None, None).is_empty());
+    }
+}
@@ -0,0 +1,122 @@
+use std::process::Stdio;
+
+use actix_web::web::Data;
+use lazy_static::lazy_static;
+use sqlx::PgPool;
+use tokio::{
+    fs::{remove_file, write},
+    process::Command,
+};
+
+use crate::{
+    error::errors::KekServerError,
+    models::{file_waveform::FileWaveform, ids::SoundFileId},
+    storage::SoundStorage,
+};
+
+use super::temp_file_path;
+
+lazy_static! {
+    static ref FFMPEG_PATH: String =
+        dotenv::var("FFMPEG_PATH").unwrap_or_else(|_| "ffmpeg".to_string());
+}
+
+/// Number of peaks a waveform is downsampled to.
+const WAVEFORM_PEAKS: usize = 200;
+const WAVEFORM_SAMPLE_RATE: &str = "8000";
+
+/// Computes the waveform of the stored file and caches it in the database.
+pub async fn generate_waveform(
+    id: &SoundFileId,
+    storage_key: &str,
+    storage: &Data<dyn SoundStorage>,
+    db_pool: &Data<PgPool>,
+) -> Result<FileWaveform, KekServerError> {
+    let data = storage.read(storage_key).await?;
+    let samples = decode_samples(storage_key, &data).await?;
+
+    let waveform = FileWaveform {
+        file_id: id.clone(),
+        peaks: peaks_from_samples(&samples, WAVEFORM_PEAKS),
+    };
+
+    let mut transaction = db_pool.begin().await?;
+    waveform.upsert(&mut transaction).await?;
+    transaction.commit().await?;
+
+    return Ok(waveform);
+}
+
+/// Decodes the file to mono signed 16-bit samples.
+async fn decode_samples(key: &str, data: &[u8]) -> Result<Vec<i16>, KekServerError> {
+    let input_path = temp_file_path(&format!("{}_waveform", key));
+    write(&input_path, data).await?;
+
+    let output = Command::new(&*FFMPEG_PATH)
+        .args(["-hide_banner", "-loglevel", "error", "-i"])
+        .arg(&input_path)
+        .args([
+            "-vn",
+            "-ac",
+            "1",
+            "-ar",
+            WAVEFORM_SAMPLE_RATE,
+            "-f",
+            "s16le",
+            "-",
+        ])
+        .stdin(Stdio::null())
+        .output()
+        .await;
+
+    let _ = remove_file(&input_path).await;
+
+    let output = output?;
+    if !output.status.success() {
+        return Err(KekServerError::AudioProcessingError(
+            String::from_utf8_lossy(&output.stderr).to_string(),
+        ));
+    }
+
+    let samples = output
+        .stdout
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect();
+    return Ok(samples);
+}
+
+/// Splits samples into `count` buckets and takes the absolute maximum of each, normalized to `0.0..=1.0`.
+fn peaks_from_samples(samples: &[i16], count: usize) -> Vec<f32> {
+    if samples.is_empty() || count == 0 {
+        return Vec::new();
+    }
+
+    let bucket_size = samples.len().div_ceil(count);
+    return samples
+        .chunks(bucket_size)
+        .map(|bucket| {
+            let peak = bucket.iter().map(|s| s.unsigned_abs()).max().unwrap_or(0);
+            return (f32::from(peak) / f32::from(i16::MAX as u16)).min(1.0);
+        })
+        .collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::peaks_from_samples;
+
+    #[test]
+    fn test_peaks_from_samples() {
+        let samples = [0, 100, -16384, 200, i16::MIN, 0, 32767, -1];
+
+        let peaks = peaks_from_samples(&samples, 4);
+
+        assert_eq!(peaks.len(), 4);
+        assert!(peaks[0] < 0.01);
+        assert!((peaks[1] - 0.5).abs() < 0.01);
+        assert_eq!(peaks[2], 1.0);
+        assert_eq!(peaks[3], 1.0);
+        assert!(peaks_from_samples(&[], 4).is_empty());
+    }
+}
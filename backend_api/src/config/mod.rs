@@ -6,6 +6,8 @@ use std::{
 use log::{error, info};
 use serde::{Deserialize, Serialize};
 
+use crate::models::sound_file::StorageUsage;
+
 #[derive(Clone, Serialize, Deserialize, PartialEq)]
 pub struct DiscordUserId(u64);
 
@@ -15,9 +17,42 @@ impl From<u64> for DiscordUserId {
     }
 }
 
+/// Storage limits of a single user, missing limits are unlimited.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Quota {
+    pub max_bytes: Option<i64>,
+    pub max_files: Option<i64>,
+}
+
+impl Quota {
+    pub fn is_exceeded_by(&self, usage: &StorageUsage) -> bool {
+        return matches!(self.max_bytes, Some(max) if usage.bytes > max)
+            || matches!(self.max_files, Some(max) if usage.files > max);
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct UserQuota {
+    user_id: DiscordUserId,
+    #[serde(flatten)]
+    quota: Quota,
+}
+
+/// Where sound files are stored, s3 credentials are read from env variables.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StorageBackend {
+    #[default]
+    Local,
+    S3,
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Config {
     allow_upload_from: Option<Vec<DiscordUserId>>,
+    default_quota: Option<Quota>,
+    user_quotas: Option<Vec<UserQuota>>,
+    storage_backend: Option<StorageBackend>,
 }
 
 impl Config {
@@ -48,4 +83,21 @@ impl Config {
             None => &[],
         }
     }
+
+    /// Users with an override get their quota instead of the default one.
+    pub fn get_user_quota(&self, user_id: &DiscordUserId) -> Quota {
+        let user_quota = self
+            .user_quotas
+            .iter()
+            .flatten()
+            .find(|q| q.user_id == *user_id);
+        match user_quota {
+            Some(user_quota) => user_quota.quota.clone(),
+            None => self.default_quota.clone().unwrap_or_default(),
+        }
+    }
+
+    pub fn get_storage_backend(&self) -> StorageBackend {
+        return self.storage_backend.unwrap_or_default();
+    }
 }
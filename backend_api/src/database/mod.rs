@@ -4,7 +4,10 @@ pub use postgres_db::create_pool;
 
 #[cfg(test)]
 pub mod tests_db_helper {
-    use sqlx::{Connection, PgConnection, Postgres, migrate::MigrateDatabase, PgPool, postgres::PgPoolOptions};
+    use sqlx::{
+        migrate::MigrateDatabase, postgres::PgPoolOptions, Connection, PgConnection, PgPool,
+        Postgres,
+    };
 
     pub async fn db_connection() -> PgConnection {
         let database_url =
@@ -26,6 +29,10 @@ pub mod tests_db_helper {
         if !Postgres::database_exists(&database_url).await.unwrap() {
             Postgres::create_database(&database_url).await.unwrap();
         }
-        return PgPoolOptions::new().max_connections(1).connect(&database_url).await.unwrap();
+        return PgPoolOptions::new()
+            .max_connections(1)
+            .connect(&database_url)
+            .await
+            .unwrap();
     }
 }
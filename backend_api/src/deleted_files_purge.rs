@@ -0,0 +1,103 @@
+use actix_web::web::Data;
+use lazy_static::lazy_static;
+use log::warn;
+use sqlx::PgPool;
+
+use crate::{
+    error::errors::KekServerError,
+    models::{
+        file_version::FileVersion,
+        ids::SoundFileId,
+        sound_file::{ProcessingStatus, SoundFile},
+    },
+    storage::{original_storage_key, SoundStorage},
+};
+
+lazy_static! {
+    pub static ref DELETED_FILES_RETENTION_DAYS: i32 = dotenv::var("DELETED_FILES_RETENTION_DAYS")
+        .unwrap_or_else(|_| 30.to_string())
+        .parse()
+        .unwrap_or(30);
+}
+
+#[derive(Debug, Default)]
+pub struct PurgeSummary {
+    pub purged_files: u64,
+    pub failed_storage_deletes: usize,
+}
+
+/// Permanently removes files soft deleted longer than `DELETED_FILES_RETENTION_DAYS`.
+///
+/// Database rows are removed first, bytes which fail to be removed from storage are only logged.
+/// Bytes shared with deduplicated files are kept until no file or previous version is stored under them.
+pub async fn purge_deleted_files(
+    db_pool: &Data<PgPool>,
+    storage: &Data<dyn SoundStorage>,
+) -> Result<PurgeSummary, KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+    let files =
+        SoundFile::get_expired_deleted_files(*DELETED_FILES_RETENTION_DAYS, &mut transaction)
+            .await?;
+    if files.is_empty() {
+        transaction.rollback().await?;
+        return Ok(PurgeSummary::default());
+    }
+
+    let ids = files
+        .iter()
+        .map(|f| f.id.clone())
+        .collect::<Vec<SoundFileId>>();
+    // Previous versions are removed together with their files
+    let versions = FileVersion::get_for_files(&ids, &mut transaction).await?;
+    let purged_files = SoundFile::purge(&ids, &mut transaction).await?;
+    let stored_blobs = files
+        .iter()
+        .map(|f| (f.storage_key.clone(), f.processing_status.clone()))
+        .chain(
+            versions
+                .into_iter()
+                .map(|v| (v.storage_key, v.processing_status)),
+        )
+        .collect::<Vec<(String, ProcessingStatus)>>();
+    let storage_keys = stored_blobs
+        .iter()
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<String>>();
+    let unreferenced_keys =
+        SoundFile::get_unreferenced_storage_keys(&storage_keys, &mut transaction).await?;
+    transaction.commit().await?;
+
+    let failed_storage_deletes = delete_blobs(&unreferenced_keys, &stored_blobs, storage).await;
+
+    return Ok(PurgeSummary {
+        purged_files,
+        failed_storage_deletes,
+    });
+}
+
+/// Removes bytes from storage together with their original.
+/// Bytes which fail to be removed are only logged, returns the number of failed deletes.
+pub async fn delete_blobs(
+    storage_keys: &[String],
+    stored_blobs: &[(String, ProcessingStatus)],
+    storage: &Data<dyn SoundStorage>,
+) -> usize {
+    let mut failed_storage_deletes = 0;
+    for storage_key in storage_keys {
+        let mut keys = vec![storage_key.clone()];
+        // Unprocessed files were never converted, so they don't have an original
+        let unprocessed = stored_blobs.iter().any(|(key, processing_status)| {
+            key == storage_key && *processing_status == ProcessingStatus::Unprocessed
+        });
+        if !unprocessed {
+            keys.push(original_storage_key(storage_key));
+        }
+        for key in keys {
+            if let Err(e) = storage.delete(&key).await {
+                warn!("Failed to delete [{}] from storage: {}", key, e);
+                failed_storage_deletes += 1;
+            }
+        }
+    }
+    return failed_storage_deletes;
+}
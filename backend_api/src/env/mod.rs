@@ -1,8 +1,20 @@
+use crate::config::StorageBackend;
+
 pub fn check_required_env_variables() {
     dotenv::var("DISCORD_CLIENT_ID").expect("DISCORD_CLIENT_ID env variable missing!");
     dotenv::var("DISCORD_CLIENT_SECRET").expect("DISCORD_CLIENT_SECRET env variable missing!");
     dotenv::var("DATABASE_URL").expect("DATABASE_URL env variable missing!");
-    dotenv::var("SOUNDFILE_DIR").expect("SOUNDFILE_DIR env variable missing!");
     dotenv::var("WS_TOKEN").expect("WS_TOKEN env variable missing!");
     dotenv::var("DISCORD_BOT_TOKEN").expect("DISCORD_BOT_TOKEN env variable missing!");
 }
+
+pub fn check_storage_env_variables(backend: StorageBackend) {
+    match backend {
+        StorageBackend::S3 => {
+            dotenv::var("S3_BUCKET").expect("S3_BUCKET env variable missing!");
+        }
+        StorageBackend::Local => {
+            dotenv::var("SOUNDFILE_DIR").expect("SOUNDFILE_DIR env variable missing!");
+        }
+    }
+}
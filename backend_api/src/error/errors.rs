@@ -66,6 +66,8 @@ pub enum KekServerError {
     InvalidHeaderValue(#[from] reqwest::header::InvalidHeaderValue),
     #[error(transparent)]
     SerdeUrlencodedError(#[from] serde_urlencoded::ser::Error),
+    #[error(transparent)]
+    S3Error(#[from] s3::error::S3Error),
     #[error("Provided files faild to upload")]
     NoFilesUploadedError,
     #[error("Invalid Authorization Credentials")]
@@ -80,6 +82,50 @@ pub enum KekServerError {
     WrongMimeTypeError,
     #[error("File too large")]
     FileTooLargeError,
+    #[error("File longer than maximum duration of {0} ms")]
+    FileTooLongError(i64),
+    #[error("Storage quota exceeded")]
+    QuotaExceededError,
+    #[error("Upload session not found")]
+    UploadSessionNotFoundError,
+    #[error("Upload offset mismatch, expected offset {0}")]
+    UploadOffsetMismatchError(i64),
+    #[error("Upload is not complete")]
+    UploadIncompleteError,
+    #[error("Invalid tag: {0}")]
+    InvalidTagError(String),
+    #[error("Invalid upload manifest: {0}")]
+    InvalidManifestError(String),
+    #[error("Display name must have 1 to {0} characters")]
+    InvalidDisplayNameError(usize),
+    #[error("Description can have at most {0} characters")]
+    InvalidDescriptionError(usize),
+    #[error("Invalid trim: {0}")]
+    InvalidTrimError(String),
+    #[error("File is still being processed")]
+    FileProcessingPendingError,
+    #[error("File doesn't have a previous version")]
+    NoPreviousVersionError,
+    #[error("Invalid cursor")]
+    InvalidCursorError,
+    #[error("Invalid pack id error")]
+    InvalidPackIdError,
+    #[error("Pack name must have 1 to {0} characters")]
+    InvalidPackNameError(usize),
+    #[error("Invalid transfer offer id error")]
+    InvalidTransferOfferIdError,
+    #[error("Files can't be offered to their owner")]
+    InvalidTransferRecipientError,
+    #[error("Invalid guild settings: {0}")]
+    InvalidGuildSettingsError(String),
+    #[error("Invalid role id")]
+    InvalidRoleIdError,
+    #[error("User isn't a manager of the guild")]
+    InvalidGuildManagerError,
+    #[error("Missing guild permission: {0}")]
+    MissingGuildPermissionError(String),
+    #[error("Invalid or expired audio signature")]
+    InvalidAudioSignatureError,
     #[error("Enviroment Error")]
     EnvError(#[from] dotenv::Error),
     #[error("Request extensions error")]
@@ -100,10 +146,84 @@ pub enum KekServerError {
     UnauthorizedFileAccessError(String),
     #[error("Preview file not found")]
     PreviewFileNotFound,
+    #[error("Audio processing error: {0}")]
+    AudioProcessingError(String),
     #[error("{0}")]
     Other(String),
 }
 
+impl KekServerError {
+    /// Machine readable code of the error, sent as `error` in error responses.
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            KekServerError::PayloadError(..) => "payload_error",
+            KekServerError::RequestTokenError(..) => "request_token_error",
+            KekServerError::RevocationRequestTokenError(..) => "revocation_request_token_error",
+            KekServerError::CookieParseError(..) => "cookie_parse_error",
+            KekServerError::UuidError(..) => "uuid_error",
+            KekServerError::SqlxError(..) => "sqlx_error",
+            KekServerError::SerdeJsonParseError(..) => "serde_json_parse_error",
+            KekServerError::OAuthConfigurationError(..) => "oauth_config_error",
+            KekServerError::MultipartError(..) => "multipart_error",
+            KekServerError::BlockingError(..) => "blocking_error",
+            KekServerError::IOError(..) => "io_error",
+            KekServerError::ActixWebError(..) => "actix_web_error",
+            KekServerError::ActixMailboxError(..) => "actix_mailbox_error",
+            KekServerError::TokioOneshotRecvError(..) => "tokio_oneshot_recv_error",
+            KekServerError::ElapsedError(..) => "elapsed_error",
+            KekServerError::WsBotClientError(..) => "ws_bot_client_error",
+            KekServerError::ToStrError(..) => "to_str_error",
+            KekServerError::SerdeUrlencodedError(..) => "serde_urlencoded_error",
+            KekServerError::ParseFloatError(..) => "parse_float_error",
+            KekServerError::ParseIntError(..) => "parse_int_error",
+            KekServerError::ReqwestError(..) => "reqwest_error",
+            KekServerError::InvalidHeaderValue(..) => "invalid_header_value",
+            KekServerError::S3Error(..) => "s3_error",
+            KekServerError::NoFilesUploadedError => "no_files_uploaded_error",
+            KekServerError::InvalidCredentialsError => "invalid_credentials_error",
+            KekServerError::DiscordRequestError => "discord_request_error",
+            KekServerError::NotInGuildError => "not_in_guild_error",
+            KekServerError::UnableToGetMimeError => "unable_to_get_mime_error",
+            KekServerError::WrongMimeTypeError => "wrong_mime_type_error",
+            KekServerError::FileTooLargeError => "file_too_large_error",
+            KekServerError::FileTooLongError(..) => "file_too_long_error",
+            KekServerError::QuotaExceededError => "quota_exceeded_error",
+            KekServerError::UploadSessionNotFoundError => "upload_session_not_found_error",
+            KekServerError::UploadOffsetMismatchError(..) => "upload_offset_mismatch_error",
+            KekServerError::UploadIncompleteError => "upload_incomplete_error",
+            KekServerError::InvalidTagError(..) => "invalid_tag_error",
+            KekServerError::InvalidManifestError(..) => "invalid_manifest_error",
+            KekServerError::InvalidDisplayNameError(..) => "invalid_display_name_error",
+            KekServerError::InvalidDescriptionError(..) => "invalid_description_error",
+            KekServerError::InvalidTrimError(..) => "invalid_trim_error",
+            KekServerError::FileProcessingPendingError => "file_processing_pending_error",
+            KekServerError::NoPreviousVersionError => "no_previous_version_error",
+            KekServerError::InvalidCursorError => "invalid_cursor_error",
+            KekServerError::InvalidPackIdError => "invalid_pack_id_error",
+            KekServerError::InvalidPackNameError(..) => "invalid_pack_name_error",
+            KekServerError::InvalidTransferOfferIdError => "invalid_transfer_offer_id_error",
+            KekServerError::InvalidTransferRecipientError => "invalid_transfer_recipient_error",
+            KekServerError::InvalidGuildSettingsError(..) => "invalid_guild_settings_error",
+            KekServerError::InvalidRoleIdError => "invalid_role_id_error",
+            KekServerError::InvalidGuildManagerError => "invalid_guild_manager_error",
+            KekServerError::MissingGuildPermissionError(..) => "missing_guild_permission_error",
+            KekServerError::InvalidAudioSignatureError => "invalid_audio_signature_error",
+            KekServerError::EnvError(..) => "enviroment_error",
+            KekServerError::RequestExtensionsError => "request_extension_error",
+            KekServerError::AuthorizedUserNotFoundError => "user_not_found_error",
+            KekServerError::UserNotInCacheError => "user_not_in_cache_error",
+            KekServerError::AuthorizationTimeExpiredError => "authorization_time_expired_error",
+            KekServerError::InvalidGuildIdError => "invalid_guild_id_error",
+            KekServerError::GuildFileDoesNotExistError => "guild_file_does_not_exist_error",
+            KekServerError::InvalidFileIdError => "invalid_file_id_error",
+            KekServerError::UnauthorizedFileAccessError(..) => "unauthorized_file_access_error",
+            KekServerError::PreviewFileNotFound => "preview_file_not_found",
+            KekServerError::AudioProcessingError(..) => "audio_processing_error",
+            KekServerError::Other(..) => "other",
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct ApiError<'a> {
     error: &'a str,
@@ -135,6 +255,7 @@ impl ResponseError for KekServerError {
             KekServerError::ReqwestError(..) => StatusCode::INTERNAL_SERVER_ERROR,
             KekServerError::InvalidHeaderValue(..) => StatusCode::INTERNAL_SERVER_ERROR,
             KekServerError::SerdeUrlencodedError(..) => StatusCode::INTERNAL_SERVER_ERROR,
+            KekServerError::S3Error(..) => StatusCode::INTERNAL_SERVER_ERROR,
             KekServerError::NoFilesUploadedError => StatusCode::BAD_REQUEST,
             KekServerError::InvalidCredentialsError => StatusCode::UNAUTHORIZED,
             KekServerError::DiscordRequestError => StatusCode::INTERNAL_SERVER_ERROR,
@@ -142,6 +263,28 @@ impl ResponseError for KekServerError {
             KekServerError::UnableToGetMimeError => StatusCode::INTERNAL_SERVER_ERROR,
             KekServerError::WrongMimeTypeError => StatusCode::INTERNAL_SERVER_ERROR,
             KekServerError::FileTooLargeError => StatusCode::BAD_REQUEST,
+            KekServerError::FileTooLongError(..) => StatusCode::BAD_REQUEST,
+            KekServerError::QuotaExceededError => StatusCode::BAD_REQUEST,
+            KekServerError::UploadSessionNotFoundError => StatusCode::NOT_FOUND,
+            KekServerError::UploadOffsetMismatchError(..) => StatusCode::CONFLICT,
+            KekServerError::UploadIncompleteError => StatusCode::BAD_REQUEST,
+            KekServerError::InvalidTagError(..) => StatusCode::BAD_REQUEST,
+            KekServerError::InvalidManifestError(..) => StatusCode::BAD_REQUEST,
+            KekServerError::InvalidDisplayNameError(..) => StatusCode::BAD_REQUEST,
+            KekServerError::InvalidDescriptionError(..) => StatusCode::BAD_REQUEST,
+            KekServerError::InvalidTrimError(..) => StatusCode::BAD_REQUEST,
+            KekServerError::FileProcessingPendingError => StatusCode::CONFLICT,
+            KekServerError::NoPreviousVersionError => StatusCode::NOT_FOUND,
+            KekServerError::InvalidCursorError => StatusCode::BAD_REQUEST,
+            KekServerError::InvalidPackIdError => StatusCode::NOT_FOUND,
+            KekServerError::InvalidPackNameError(..) => StatusCode::BAD_REQUEST,
+            KekServerError::InvalidTransferOfferIdError => StatusCode::NOT_FOUND,
+            KekServerError::InvalidTransferRecipientError => StatusCode::BAD_REQUEST,
+            KekServerError::InvalidGuildSettingsError(..) => StatusCode::BAD_REQUEST,
+            KekServerError::InvalidRoleIdError => StatusCode::NOT_FOUND,
+            KekServerError::InvalidGuildManagerError => StatusCode::NOT_FOUND,
+            KekServerError::MissingGuildPermissionError(..) => StatusCode::FORBIDDEN,
+            KekServerError::InvalidAudioSignatureError => StatusCode::FORBIDDEN,
             KekServerError::EnvError(..) => StatusCode::INTERNAL_SERVER_ERROR,
             KekServerError::RequestExtensionsError => StatusCode::INTERNAL_SERVER_ERROR,
             KekServerError::AuthorizedUserNotFoundError => StatusCode::INTERNAL_SERVER_ERROR,
@@ -152,54 +295,14 @@ impl ResponseError for KekServerError {
             KekServerError::InvalidFileIdError => StatusCode::NOT_FOUND,
             KekServerError::UnauthorizedFileAccessError(..) => StatusCode::UNAUTHORIZED,
             KekServerError::PreviewFileNotFound => StatusCode::NOT_FOUND,
+            KekServerError::AudioProcessingError(..) => StatusCode::INTERNAL_SERVER_ERROR,
             KekServerError::Other(..) => StatusCode::BAD_REQUEST,
         }
     }
 
     fn error_response(&self) -> HttpResponse {
         return HttpResponse::build(self.status_code()).json(ApiError {
-            error: match self {
-                KekServerError::PayloadError(..) => "payload_error",
-                KekServerError::RequestTokenError(..) => "request_token_error",
-                KekServerError::RevocationRequestTokenError(..) => "revocation_request_token_error",
-                KekServerError::CookieParseError(..) => "cookie_parse_error",
-                KekServerError::UuidError(..) => "uuid_error",
-                KekServerError::SqlxError(..) => "sqlx_error",
-                KekServerError::SerdeJsonParseError(..) => "serde_json_parse_error",
-                KekServerError::OAuthConfigurationError(..) => "oauth_config_error",
-                KekServerError::MultipartError(..) => "multipart_error",
-                KekServerError::BlockingError(..) => "blocking_error",
-                KekServerError::IOError(..) => "io_error",
-                KekServerError::ActixWebError(..) => "actix_web_error",
-                KekServerError::ActixMailboxError(..) => "actix_mailbox_error",
-                KekServerError::TokioOneshotRecvError(..) => "tokio_oneshot_recv_error",
-                KekServerError::ElapsedError(..) => "elapsed_error",
-                KekServerError::WsBotClientError(..) => "ws_bot_client_error",
-                KekServerError::ToStrError(..) => "to_str_error",
-                KekServerError::SerdeUrlencodedError(..) => "serde_urlencoded_error",
-                KekServerError::ParseFloatError(..) => "parse_float_error",
-                KekServerError::ParseIntError(..) => "parse_int_error",
-                KekServerError::ReqwestError(..) => "reqwest_error",
-                KekServerError::InvalidHeaderValue(..) => "invalid_header_value",
-                KekServerError::NoFilesUploadedError => "no_files_uploaded_error",
-                KekServerError::InvalidCredentialsError => "invalid_credentials_error",
-                KekServerError::DiscordRequestError => "discord_request_error",
-                KekServerError::NotInGuildError => "not_in_guild_error",
-                KekServerError::UnableToGetMimeError => "unable_to_get_mime_error",
-                KekServerError::WrongMimeTypeError => "wrong_mime_type_error",
-                KekServerError::FileTooLargeError => "file_too_large_error",
-                KekServerError::EnvError(..) => "enviroment_error",
-                KekServerError::RequestExtensionsError => "request_extension_error",
-                KekServerError::AuthorizedUserNotFoundError => "user_not_found_error",
-                KekServerError::UserNotInCacheError => "user_not_in_cache_error",
-                KekServerError::AuthorizationTimeExpiredError => "authorization_time_expired_error",
-                KekServerError::InvalidGuildIdError => "invalid_guild_id_error",
-                KekServerError::GuildFileDoesNotExistError => "guild_file_does_not_exist_error",
-                KekServerError::InvalidFileIdError => "invalid_file_id_error",
-                KekServerError::UnauthorizedFileAccessError(..) => "unauthorized_file_access_error",
-                KekServerError::PreviewFileNotFound => "preview_file_not_found",
-                KekServerError::Other(..) => "other",
-            },
+            error: self.error_code(),
             description: &self.to_string(),
         });
     }
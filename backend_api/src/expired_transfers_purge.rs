@@ -0,0 +1,22 @@
+use actix_web::web::Data;
+use lazy_static::lazy_static;
+use sqlx::PgPool;
+
+use crate::{error::errors::KekServerError, models::transfer_offer::TransferOffer};
+
+lazy_static! {
+    pub static ref TRANSFER_OFFER_EXPIRY_HOURS: i32 = dotenv::var("TRANSFER_OFFER_EXPIRY_HOURS")
+        .unwrap_or_else(|_| 72.to_string())
+        .parse()
+        .unwrap_or(72);
+}
+
+/// Removes ownership transfer offers which weren't accepted in `TRANSFER_OFFER_EXPIRY_HOURS`.
+///
+/// Returns the number of removed offers.
+pub async fn purge_expired_transfers(db_pool: &Data<PgPool>) -> Result<u64, KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+    let purged = TransferOffer::delete_expired(&mut transaction).await?;
+    transaction.commit().await?;
+    return Ok(purged);
+}
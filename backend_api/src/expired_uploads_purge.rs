@@ -0,0 +1,39 @@
+use actix_web::web::Data;
+use lazy_static::lazy_static;
+use log::warn;
+use sqlx::PgPool;
+
+use crate::{
+    error::errors::KekServerError,
+    models::upload_session::UploadSession,
+    storage::{upload_key, SoundStorage},
+};
+
+lazy_static! {
+    pub static ref UPLOAD_SESSION_EXPIRY_HOURS: i32 = dotenv::var("UPLOAD_SESSION_EXPIRY_HOURS")
+        .unwrap_or_else(|_| 24.to_string())
+        .parse()
+        .unwrap_or(24);
+}
+
+/// Removes resumable uploads which weren't appended to for `UPLOAD_SESSION_EXPIRY_HOURS`.
+///
+/// Returns the number of removed upload sessions.
+pub async fn purge_expired_uploads(
+    db_pool: &Data<PgPool>,
+    storage: &Data<dyn SoundStorage>,
+) -> Result<usize, KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+    let sessions = UploadSession::delete_expired(&mut transaction).await?;
+    transaction.commit().await?;
+
+    // Sessions without uploaded bytes never saved anything
+    for session in sessions.iter().filter(|s| s.uploaded_size > 0) {
+        let key = upload_key(&session.id);
+        if let Err(e) = storage.delete(&key).await {
+            warn!("Failed to delete [{}] from storage: {}", key, e);
+        }
+    }
+
+    return Ok(sessions.len());
+}
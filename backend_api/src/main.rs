@@ -7,16 +7,22 @@ use std::{
 use active_guilds_check::ActiveGuildsCheck;
 use actix_cors::Cors;
 use actix_web::{web::Data, App, HttpServer};
-use env::check_required_env_variables;
+use audio::processing::requeue_pending_files;
+use env::{check_required_env_variables, check_storage_env_variables};
 use log::{error, info, warn};
 use routes::{not_found::not_found, routes_config, status::Status};
 
 use dotenv::dotenv;
 use snowflake::SnowflakeIdGenerator;
+use storage::{integrity_scan, SoundStorage};
 use tokio::sync::{Mutex as AsyncMutex, RwLock};
-use utils::cache::{
-    create_auth_middlware_queue_cache, create_authorized_user_cache, create_user_guilds_cache,
-    create_user_guilds_middlware_queue_cache,
+use utils::{
+    cache::{
+        create_auth_middlware_queue_cache, create_authorized_user_cache,
+        create_trimmed_audio_cache, create_user_guilds_cache,
+        create_user_guilds_middlware_queue_cache,
+    },
+    SNOWFLAKE_EPOCH_MS,
 };
 use ws::{
     channels_server::{self, ChannelsServer},
@@ -24,19 +30,24 @@ use ws::{
     ws_session::WsSessionCommChannels,
 };
 
-use crate::config::Config;
+use crate::config::{Config, StorageBackend};
 
 mod active_guilds_check;
+mod audio;
 mod config;
 mod database;
+mod deleted_files_purge;
 mod discord_client_config;
 mod env;
 mod error;
+mod expired_transfers_purge;
+mod expired_uploads_purge;
 mod middleware;
 mod models;
 mod oauth_client;
 mod routes;
 mod scheduler;
+mod storage;
 mod utils;
 mod ws;
 
@@ -65,6 +76,32 @@ async fn main() -> std::io::Result<()> {
             .expect("Failed to create database connection pool!"),
     );
 
+    let config = Data::new(Config::load_config());
+    let storage_backend = match config.as_ref() {
+        Some(cfg) => cfg.get_storage_backend(),
+        None => StorageBackend::default(),
+    };
+    check_storage_env_variables(storage_backend);
+    let storage: Data<dyn SoundStorage> = Data::from(
+        storage::create_storage(storage_backend).expect("Failed to create sound storage!"),
+    );
+
+    // `backend_api scan-storage [--quarantine]` runs the storage integrity scan once and exits
+    let args = std::env::args().collect::<Vec<String>>();
+    if args.get(1).map(|a| a.as_str()) == Some("scan-storage") {
+        let quarantine = args.iter().any(|a| a == "--quarantine");
+        match integrity_scan::scan_storage(&pool, &storage, quarantine).await {
+            Ok(report) => report.log(),
+            Err(e) => error!("Storage scan failed: {}", e),
+        }
+        return Ok(());
+    }
+
+    match requeue_pending_files(pool.clone(), storage.clone()).await {
+        Ok(count) => info!("Requeued {} pending files for processing", count),
+        Err(e) => error!("Failed to requeue pending files: {}", e),
+    }
+
     let snowflake_thread_id = Arc::new(Mutex::new(0));
 
     let controls_server = Data::new(ControlsServer::new());
@@ -75,6 +112,7 @@ async fn main() -> std::io::Result<()> {
     let user_guilds_middleware_queue =
         Data::new(AsyncMutex::new(create_user_guilds_middlware_queue_cache()));
     let auth_middleware_queue = Data::new(AsyncMutex::new(create_auth_middlware_queue_cache()));
+    let trimmed_audio_cache = Data::new(create_trimmed_audio_cache());
 
     let channels_server = Data::new(ChannelsServer::new(
         authorized_users_cache.clone(),
@@ -193,7 +231,71 @@ async fn main() -> std::io::Result<()> {
         }
     });
 
-    let config = Data::new(Config::load_config());
+    let pool_ref = pool.clone();
+    let storage_ref = storage.clone();
+    scheduler.run(std::time::Duration::from_secs(3600), move || {
+        let pool_ref = pool_ref.clone();
+        let storage_ref = storage_ref.clone();
+        info!("Purging deleted files");
+        async move {
+            match deleted_files_purge::purge_deleted_files(&pool_ref, &storage_ref).await {
+                Ok(summary) => info!(
+                    "Finished purging deleted files, purged: {}, failed storage deletes: {}",
+                    summary.purged_files, summary.failed_storage_deletes
+                ),
+                Err(e) => error!("Failed to purge deleted files: {}", e),
+            }
+        }
+    });
+
+    let pool_ref = pool.clone();
+    let storage_ref = storage.clone();
+    scheduler.run(std::time::Duration::from_secs(3600), move || {
+        let pool_ref = pool_ref.clone();
+        let storage_ref = storage_ref.clone();
+        info!("Purging expired uploads");
+        async move {
+            match expired_uploads_purge::purge_expired_uploads(&pool_ref, &storage_ref).await {
+                Ok(purged) => info!("Finished purging expired uploads, purged: {}", purged),
+                Err(e) => error!("Failed to purge expired uploads: {}", e),
+            }
+        }
+    });
+
+    let pool_ref = pool.clone();
+    scheduler.run(std::time::Duration::from_secs(3600), move || {
+        let pool_ref = pool_ref.clone();
+        info!("Purging expired transfer offers");
+        async move {
+            match expired_transfers_purge::purge_expired_transfers(&pool_ref).await {
+                Ok(purged) => info!(
+                    "Finished purging expired transfer offers, purged: {}",
+                    purged
+                ),
+                Err(e) => error!("Failed to purge expired transfer offers: {}", e),
+            }
+        }
+    });
+
+    let pool_ref = pool.clone();
+    let storage_ref = storage.clone();
+    scheduler.run(std::time::Duration::from_secs(24 * 3600), move || {
+        let pool_ref = pool_ref.clone();
+        let storage_ref = storage_ref.clone();
+        info!("Scanning storage integrity");
+        async move {
+            match integrity_scan::scan_storage(
+                &pool_ref,
+                &storage_ref,
+                *integrity_scan::QUARANTINE_ORPHANED_FILES,
+            )
+            .await
+            {
+                Ok(report) => report.log(),
+                Err(e) => error!("Storage scan failed: {}", e),
+            }
+        }
+    });
 
     warn!("Starting server on address: {}", bind_address);
     return HttpServer::new(move || {
@@ -202,7 +304,7 @@ async fn main() -> std::io::Result<()> {
         {
             let id_arc = snowflake_thread_id.clone();
             let mut lock = id_arc.lock().unwrap();
-            let epoch = UNIX_EPOCH + std::time::Duration::from_millis(1640991600000); // epoch start time 01.01.2022. 00:00
+            let epoch = UNIX_EPOCH + std::time::Duration::from_millis(SNOWFLAKE_EPOCH_MS);
             snowflakes = Data::new(Mutex::new(SnowflakeIdGenerator::with_epoch(
                 *lock, 1, epoch,
             )));
@@ -228,9 +330,11 @@ async fn main() -> std::io::Result<()> {
             .app_data(status.clone())
             .app_data(user_guilds_middleware_queue.clone())
             .app_data(auth_middleware_queue.clone())
+            .app_data(trimmed_audio_cache.clone())
             .app_data(snowflakes)
             .app_data(config.clone())
             .app_data(channels_server.clone())
+            .app_data(storage.clone())
             .configure(routes_config)
             .default_service(actix_web::web::to(not_found))
     })
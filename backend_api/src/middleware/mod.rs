@@ -2,7 +2,7 @@ use std::sync::Arc;
 
 use actix_web::web::Data;
 use log::debug;
-use tokio::sync::{Notify, Mutex};
+use tokio::sync::{Mutex, Notify};
 
 use crate::{
     error::errors::KekServerError,
@@ -0,0 +1,248 @@
+use std::collections::HashSet;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+
+use crate::error::errors::KekServerError;
+
+use super::{
+    ids::{GuildId, SoundFileId, UserId},
+    sound_file::{SoundFile, SoundFileRow},
+};
+
+/// Sound file bookmarked by a user, users can favorite public files,
+/// their own files and files enabled in their guilds.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Favorite {
+    pub user_id: UserId,
+    pub file_id: SoundFileId,
+    pub time_added: NaiveDateTime,
+}
+
+impl Favorite {
+    /// Returns `None` if the file doesn't exist or the user can't see it, favoriting a file twice keeps the first favorite.
+    pub async fn insert(
+        user_id: &UserId,
+        file_id: &SoundFileId,
+        user_guilds: &[GuildId],
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Self>, KekServerError> {
+        let user_guilds = user_guilds.iter().map(|g| g.0 as i64).collect::<Vec<i64>>();
+        sqlx::query!(
+            "
+            INSERT INTO favorite (user_id, file_id)
+            SELECT $1, id FROM files
+            WHERE id = $2 AND is_deleted = false
+            AND (
+                is_public = true
+                OR owner = $1
+                OR EXISTS (
+                    SELECT 1 FROM guild_file
+                    WHERE guild_file.file_id = files.id AND guild_file.guild_id = ANY($3)
+                    AND guild_file.is_deleted = false
+                )
+            )
+            ON CONFLICT DO NOTHING
+            ",
+            user_id.0 as i64,
+            file_id.0 as i64,
+            &user_guilds
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Favorite::get(user_id, file_id, transaction).await;
+    }
+
+    pub async fn get(
+        user_id: &UserId,
+        file_id: &SoundFileId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Self>, KekServerError> {
+        match sqlx::query!(
+            "
+            SELECT * FROM favorite
+            WHERE user_id = $1 AND file_id = $2
+            ",
+            user_id.0 as i64,
+            file_id.0 as i64
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        {
+            Some(r) => {
+                return Ok(Some(Self {
+                    user_id: r.user_id.into(),
+                    file_id: r.file_id.into(),
+                    time_added: r.time_added,
+                }));
+            }
+            None => return Ok(None),
+        }
+    }
+
+    pub async fn delete(
+        user_id: &UserId,
+        file_id: &SoundFileId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Self>, KekServerError> {
+        match sqlx::query!(
+            "
+            DELETE FROM favorite
+            WHERE user_id = $1 AND file_id = $2
+            RETURNING *
+            ",
+            user_id.0 as i64,
+            file_id.0 as i64
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        {
+            Some(r) => {
+                return Ok(Some(Self {
+                    user_id: r.user_id.into(),
+                    file_id: r.file_id.into(),
+                    time_added: r.time_added,
+                }));
+            }
+            None => return Ok(None),
+        }
+    }
+
+    /// Favorited files of the user, most recently favorited first. Deleted files are left out until they are restored
+    /// and files the user can no longer see are left out like in `insert`.
+    pub async fn get_user_favorite_files(
+        user_id: &UserId,
+        user_guilds: &[GuildId],
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<SoundFile>, KekServerError> {
+        let user_guilds = user_guilds.iter().map(|g| g.0 as i64).collect::<Vec<i64>>();
+        let records = sqlx::query_as!(
+            SoundFileRow,
+            "
+            SELECT files.* FROM favorite
+            INNER JOIN files ON files.id = favorite.file_id
+            WHERE favorite.user_id = $1 AND files.is_deleted = false
+            AND (
+                files.is_public = true
+                OR files.owner = $1
+                OR EXISTS (
+                    SELECT 1 FROM guild_file
+                    WHERE guild_file.file_id = files.id AND guild_file.guild_id = ANY($2)
+                    AND guild_file.is_deleted = false
+                )
+            )
+            ORDER BY favorite.time_added DESC, files.id
+            ",
+            user_id.0 as i64,
+            &user_guilds
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records
+            .into_iter()
+            .map(|r| SoundFile {
+                is_favorite: true,
+                ..r.into()
+            })
+            .collect());
+    }
+
+    /// Fills in `is_favorite` of the files for the user.
+    pub async fn attach_to_files(
+        user_id: &UserId,
+        files: Vec<&mut SoundFile>,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        let file_ids = files.iter().map(|f| f.id.0 as i64).collect::<Vec<i64>>();
+        let favorites = sqlx::query!(
+            "
+            SELECT file_id FROM favorite
+            WHERE user_id = $1 AND file_id = ANY($2)
+            ",
+            user_id.0 as i64,
+            &file_ids
+        )
+        .fetch_all(&mut *transaction)
+        .await?
+        .into_iter()
+        .map(|r| SoundFileId::from(r.file_id))
+        .collect::<HashSet<SoundFileId>>();
+        for file in files {
+            file.is_favorite = favorites.contains(&file.id);
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection;
+
+    use crate::{
+        database::tests_db_helper::db_connection,
+        utils::test_utils::{
+            insert_guild_file_test_util, insert_guild_test_util, insert_random_file_test_util,
+            insert_user_test_util,
+        },
+    };
+
+    use super::Favorite;
+
+    #[actix_web::test]
+    async fn test_favorite() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let user = insert_user_test_util(&mut transaction).await;
+        let guild = insert_guild_test_util(&mut transaction).await;
+        let private_file = insert_random_file_test_util(&mut transaction).await;
+        let guild_file = insert_random_file_test_util(&mut transaction).await;
+        insert_guild_file_test_util(&guild.id, guild_file.clone(), &mut transaction).await;
+
+        let private_favorite = Favorite::insert(
+            &user.id,
+            &private_file.id,
+            std::slice::from_ref(&guild.id),
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let guild_favorite = Favorite::insert(
+            &user.id,
+            &guild_file.id,
+            std::slice::from_ref(&guild.id),
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let favorite_files = Favorite::get_user_favorite_files(
+            &user.id,
+            std::slice::from_ref(&guild.id),
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let hidden_favorite_files =
+            Favorite::get_user_favorite_files(&user.id, &[], &mut transaction)
+                .await
+                .unwrap();
+        let mut files = [private_file.clone(), guild_file.clone()];
+        Favorite::attach_to_files(&user.id, files.iter_mut().collect(), &mut transaction)
+            .await
+            .unwrap();
+        let deleted_favorite = Favorite::delete(&user.id, &guild_file.id, &mut transaction)
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(private_favorite, None);
+        assert!(guild_favorite.is_some());
+        assert_eq!(favorite_files.len(), 1);
+        assert_eq!(favorite_files[0].id, guild_file.id);
+        assert!(hidden_favorite_files.is_empty());
+        assert!(!files[0].is_favorite);
+        assert!(files[1].is_favorite);
+        assert_eq!(deleted_favorite, guild_favorite);
+    }
+}
@@ -0,0 +1,297 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+
+use crate::error::errors::KekServerError;
+
+use super::{
+    ids::SoundFileId,
+    sound_file::{AudioMetadata, ProcessingStatus, SoundFile},
+};
+
+/// Audio a file had before it was replaced, only the latest replaced audio is kept.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FileVersion {
+    pub file_id: SoundFileId,
+    #[serde(skip)]
+    pub storage_key: String,
+    #[serde(skip)]
+    pub content_hash: Option<String>,
+    #[serde(skip)]
+    pub checksum: Option<String>,
+    pub processing_status: ProcessingStatus,
+    #[serde(flatten)]
+    pub metadata: AudioMetadata,
+    pub size: Option<i64>,
+    pub start_ms: Option<i32>,
+    pub end_ms: Option<i32>,
+    /// Time the audio was replaced
+    pub time_added: NaiveDateTime,
+}
+
+impl FileVersion {
+    /// Keeps the current audio of the file as its previous version.
+    pub async fn insert_from_file(
+        file_id: &SoundFileId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Self, KekServerError> {
+        let r = sqlx::query!(
+            "
+            INSERT INTO file_version (
+                file_id,
+                storage_key,
+                content_hash,
+                checksum,
+                processing_status,
+                duration_ms,
+                codec,
+                sample_rate,
+                channels,
+                bitrate,
+                size,
+                start_ms,
+                end_ms
+            )
+            SELECT
+                id,
+                storage_key,
+                content_hash,
+                checksum,
+                processing_status,
+                duration_ms,
+                codec,
+                sample_rate,
+                channels,
+                bitrate,
+                size,
+                start_ms,
+                end_ms
+            FROM files
+            WHERE id = $1
+            RETURNING *
+            ",
+            file_id.0 as i64
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        return Ok(Self {
+            file_id: r.file_id.into(),
+            storage_key: r.storage_key,
+            content_hash: r.content_hash,
+            checksum: r.checksum,
+            processing_status: r.processing_status.into(),
+            metadata: AudioMetadata {
+                duration_ms: r.duration_ms,
+                codec: r.codec,
+                sample_rate: r.sample_rate,
+                channels: r.channels,
+                bitrate: r.bitrate,
+            },
+            size: r.size,
+            start_ms: r.start_ms,
+            end_ms: r.end_ms,
+            time_added: r.time_added,
+        });
+    }
+
+    pub async fn get(
+        file_id: &SoundFileId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Self>, KekServerError> {
+        match sqlx::query!(
+            "
+            SELECT * FROM file_version
+            WHERE file_id = $1
+            ",
+            file_id.0 as i64
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        {
+            Some(r) => {
+                return Ok(Some(Self {
+                    file_id: r.file_id.into(),
+                    storage_key: r.storage_key,
+                    content_hash: r.content_hash,
+                    checksum: r.checksum,
+                    processing_status: r.processing_status.into(),
+                    metadata: AudioMetadata {
+                        duration_ms: r.duration_ms,
+                        codec: r.codec,
+                        sample_rate: r.sample_rate,
+                        channels: r.channels,
+                        bitrate: r.bitrate,
+                    },
+                    size: r.size,
+                    start_ms: r.start_ms,
+                    end_ms: r.end_ms,
+                    time_added: r.time_added,
+                }));
+            }
+            None => return Ok(None),
+        }
+    }
+
+    /// Versions of the files, versions are removed with their file when it is purged.
+    pub async fn get_for_files(
+        file_ids: &[SoundFileId],
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<Self>, KekServerError> {
+        let file_ids = file_ids.iter().map(|id| id.0 as i64).collect::<Vec<i64>>();
+        let records = sqlx::query!(
+            "
+            SELECT * FROM file_version
+            WHERE file_id = ANY($1)
+            ",
+            &file_ids
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records
+            .into_iter()
+            .map(|r| Self {
+                file_id: r.file_id.into(),
+                storage_key: r.storage_key,
+                content_hash: r.content_hash,
+                checksum: r.checksum,
+                processing_status: r.processing_status.into(),
+                metadata: AudioMetadata {
+                    duration_ms: r.duration_ms,
+                    codec: r.codec,
+                    sample_rate: r.sample_rate,
+                    channels: r.channels,
+                    bitrate: r.bitrate,
+                },
+                size: r.size,
+                start_ms: r.start_ms,
+                end_ms: r.end_ms,
+                time_added: r.time_added,
+            })
+            .collect());
+    }
+
+    /// Keys of all stored versions.
+    pub async fn get_storage_keys(
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<String>, KekServerError> {
+        let records = sqlx::query!(
+            "
+            SELECT storage_key FROM file_version
+            "
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records.into_iter().map(|r| r.storage_key).collect());
+    }
+
+    pub async fn delete(
+        file_id: &SoundFileId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Self>, KekServerError> {
+        match sqlx::query!(
+            "
+            DELETE FROM file_version
+            WHERE file_id = $1
+            RETURNING *
+            ",
+            file_id.0 as i64
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        {
+            Some(r) => {
+                return Ok(Some(Self {
+                    file_id: r.file_id.into(),
+                    storage_key: r.storage_key,
+                    content_hash: r.content_hash,
+                    checksum: r.checksum,
+                    processing_status: r.processing_status.into(),
+                    metadata: AudioMetadata {
+                        duration_ms: r.duration_ms,
+                        codec: r.codec,
+                        sample_rate: r.sample_rate,
+                        channels: r.channels,
+                        bitrate: r.bitrate,
+                    },
+                    size: r.size,
+                    start_ms: r.start_ms,
+                    end_ms: r.end_ms,
+                    time_added: r.time_added,
+                }));
+            }
+            None => return Ok(None),
+        }
+    }
+
+    /// The file with its audio replaced by this version.
+    pub fn restore(&self, file: SoundFile) -> SoundFile {
+        return SoundFile {
+            storage_key: self.storage_key.clone(),
+            content_hash: self.content_hash.clone(),
+            processing_status: self.processing_status.clone(),
+            metadata: self.metadata.clone(),
+            size: self.size,
+            start_ms: self.start_ms,
+            end_ms: self.end_ms,
+            ..file
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection;
+
+    use crate::{
+        database::tests_db_helper::db_connection, models::sound_file::SoundFile,
+        utils::test_utils::insert_random_file_test_util,
+    };
+
+    use super::FileVersion;
+
+    #[actix_web::test]
+    async fn test_file_version() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let sound_file = insert_random_file_test_util(&mut transaction).await;
+        let version = FileVersion::insert_from_file(&sound_file.id, &mut transaction)
+            .await
+            .unwrap();
+
+        let mut replaced_file = sound_file.clone();
+        replaced_file.storage_key = format!("{}_replaced", sound_file.storage_key);
+        replaced_file.size = Some(100);
+        let replaced_file = replaced_file
+            .set_audio(None, &mut transaction)
+            .await
+            .unwrap();
+
+        let gotten_version = FileVersion::get(&sound_file.id, &mut transaction)
+            .await
+            .unwrap();
+        let storage_keys = SoundFile::get_unreferenced_storage_keys(
+            std::slice::from_ref(&sound_file.storage_key),
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let deleted_version = FileVersion::delete(&sound_file.id, &mut transaction)
+            .await
+            .unwrap()
+            .unwrap();
+        let restored_file = deleted_version
+            .restore(replaced_file.clone())
+            .set_audio(deleted_version.checksum.as_deref(), &mut transaction)
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(version.storage_key, sound_file.storage_key);
+        assert_eq!(replaced_file.size, Some(100));
+        assert_eq!(gotten_version, Some(version));
+        assert!(storage_keys.is_empty());
+        assert_eq!(restored_file.storage_key, sound_file.storage_key);
+        assert_eq!(restored_file.size, sound_file.size);
+    }
+}
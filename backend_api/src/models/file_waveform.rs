@@ -0,0 +1,114 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+
+use crate::error::errors::KekServerError;
+
+use super::ids::SoundFileId;
+
+/// Downsampled peaks of a sound file, each peak is in range `0.0..=1.0`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct FileWaveform {
+    pub file_id: SoundFileId,
+    pub peaks: Vec<f32>,
+}
+
+impl FileWaveform {
+    pub async fn upsert(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        sqlx::query!(
+            "
+            INSERT INTO file_waveform (file_id, peaks)
+            VALUES ($1, $2)
+            ON CONFLICT (file_id)
+            DO UPDATE
+            SET peaks = $2, time_added = CURRENT_TIMESTAMP
+            ",
+            self.file_id.0 as i64,
+            &self.peaks
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+
+    pub async fn get(
+        file_id: &SoundFileId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Self>, KekServerError> {
+        match sqlx::query!(
+            "
+            SELECT file_id, peaks FROM file_waveform
+            WHERE file_id = $1
+            ",
+            file_id.0 as i64
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        {
+            Some(r) => {
+                return Ok(Some(Self {
+                    file_id: r.file_id.into(),
+                    peaks: r.peaks,
+                }));
+            }
+            None => return Ok(None),
+        }
+    }
+
+    /// Removes the cached waveform, it is generated again on the next request.
+    pub async fn delete(
+        file_id: &SoundFileId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        sqlx::query!(
+            "
+            DELETE FROM file_waveform
+            WHERE file_id = $1
+            ",
+            file_id.0 as i64
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection;
+
+    use crate::{
+        database::tests_db_helper::db_connection, utils::test_utils::insert_random_file_test_util,
+    };
+
+    use super::FileWaveform;
+
+    #[actix_web::test]
+    async fn test_upsert_waveform() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let sound_file = insert_random_file_test_util(&mut transaction).await;
+        let missing_waveform = FileWaveform::get(&sound_file.id, &mut transaction)
+            .await
+            .unwrap();
+
+        let mut waveform = FileWaveform {
+            file_id: sound_file.id.clone(),
+            peaks: vec![0.0, 0.5, 1.0],
+        };
+        waveform.upsert(&mut transaction).await.unwrap();
+        waveform.peaks = vec![0.25, 0.75];
+        waveform.upsert(&mut transaction).await.unwrap();
+
+        let gotten_waveform = FileWaveform::get(&sound_file.id, &mut transaction)
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert!(missing_waveform.is_none());
+        assert_eq!(gotten_waveform, Some(waveform));
+    }
+}
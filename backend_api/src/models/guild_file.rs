@@ -1,4 +1,4 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
@@ -9,7 +9,8 @@ use crate::error::errors::KekServerError;
 use super::{
     guild::Guild,
     ids::{GuildId, SoundFileId, UserId},
-    sound_file::SoundFile,
+    sound_file::{SoundFile, SoundFileRow},
+    tag::TagFilter,
 };
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -22,6 +23,15 @@ pub struct GuildFile {
     pub sound_file: Option<SoundFile>,
 }
 
+/// Page of a guild's file listing, cursors are missing on the first and last page
+/// and when the listing isn't paginated.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GuildFilesPage {
+    pub files: Vec<GuildFile>,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
 impl GuildFile {
     pub async fn insert_guild_file(
         guild_id: &GuildId,
@@ -34,7 +44,7 @@ impl GuildFile {
             VALUES ($1, $2)
             ON CONFLICT (guild_id, file_id)
             DO UPDATE
-            SET is_deleted = false;
+            SET is_deleted = false, deleted_with_file = false;
             ",
             guild_id.0 as i64,
             file_id.0 as i64
@@ -53,7 +63,7 @@ impl GuildFile {
         sqlx::query!(
             "
             UPDATE guild_file
-            SET is_deleted = true
+            SET is_deleted = true, deleted_with_file = false
             WHERE guild_id = $1 AND file_id = $2
             ",
             guild_id.0 as i64,
@@ -64,47 +74,73 @@ impl GuildFile {
         return Ok(guild_file);
     }
 
+    /// Disables the files in all guilds, marking the associations so they can be restored with the files.
+    pub async fn delete_with_files(
+        file_ids: &[SoundFileId],
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        let file_ids = file_ids.iter().map(|f| f.0 as i64).collect::<Vec<i64>>();
+        sqlx::query!(
+            "
+            UPDATE guild_file
+            SET is_deleted = true, deleted_with_file = true
+            WHERE file_id = ANY($1) AND is_deleted = false
+            ",
+            &file_ids
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+
+    /// Re-enables guild associations that were disabled by deleting the files.
+    pub async fn restore_with_files(
+        file_ids: &[SoundFileId],
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        let file_ids = file_ids.iter().map(|f| f.0 as i64).collect::<Vec<i64>>();
+        sqlx::query!(
+            "
+            UPDATE guild_file
+            SET is_deleted = false, deleted_with_file = false
+            WHERE file_id = ANY($1) AND deleted_with_file = true
+            ",
+            &file_ids
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+
     pub async fn get_guild_files(
         guild_id: &GuildId,
+        tag_filter: &TagFilter,
         transaction: &mut Transaction<'_, Postgres>,
     ) -> Result<Vec<Self>, KekServerError> {
-        let records = sqlx::query!(
+        let files = sqlx::query_as!(
+            SoundFileRow,
             "
-            SELECT 
-                files.*,
-                guild_file.guild_id,
-                guild_file.file_id,
-                guild_file.time_added as gf_time_added,
-                guild_file.is_deleted as gf_is_deleted
-            FROM files
+            SELECT files.* FROM files
             INNER JOIN guild_file ON guild_file.guild_id = $1
             AND files.id = guild_file.file_id
             AND guild_file.is_deleted = false
+            WHERE (
+                SELECT COUNT(*) FROM file_tag
+                INNER JOIN tag ON tag.id = file_tag.tag_id
+                WHERE file_tag.file_id = files.id AND tag.name = ANY($2)
+            ) >= $3
             ",
             guild_id.0 as i64,
+            &tag_filter.tags,
+            tag_filter.required_matches()
         )
         .fetch_all(&mut *transaction)
-        .await?;
-
-        let guild_files = records
-            .into_iter()
-            .map(|r| GuildFile {
-                guild_id: r.guild_id.into(),
-                file_id: r.file_id.into(),
-                time_added: r.gf_time_added,
-                is_deleted: r.gf_is_deleted.unwrap_or(false),
-                sound_file: Some(SoundFile {
-                    id: r.id.into(),
-                    owner: r.owner.map(|o| o.into()),
-                    display_name: r.display_name,
-                    time_added: r.time_added,
-                    is_public: r.is_public.unwrap_or(false),
-                    is_deleted: r.is_deleted.unwrap_or(false),
-                }),
-            })
-            .collect::<Vec<Self>>();
+        .await?
+        .into_iter()
+        .map(SoundFile::from)
+        .collect();
 
-        return Ok(guild_files);
+        return Self::from_files(guild_id, files, transaction).await;
     }
 
     pub async fn get_guild_file(
@@ -112,16 +148,11 @@ impl GuildFile {
         file_id: &SoundFileId,
         transaction: &mut Transaction<'_, Postgres>,
     ) -> Result<Option<Self>, KekServerError> {
-        match sqlx::query!(
+        let sound_file = match sqlx::query_as!(
+            SoundFileRow,
             "
-            SELECT
-                files.*,
-                guild_file.guild_id,
-                guild_file.file_id,
-                guild_file.time_added as gf_time_added,
-                guild_file.is_deleted as gf_is_deleted
-            FROM guild_file
-            INNER JOIN files ON files.id = $2
+            SELECT files.* FROM guild_file
+            INNER JOIN files ON files.id = guild_file.file_id
             WHERE guild_file.guild_id = $1 AND guild_file.file_id = $2
             AND guild_file.is_deleted = false
             ",
@@ -131,24 +162,48 @@ impl GuildFile {
         .fetch_optional(&mut *transaction)
         .await?
         {
-            Some(r) => {
-                return Ok(Some(Self {
-                    guild_id: r.guild_id.into(),
-                    file_id: r.file_id.into(),
-                    time_added: r.gf_time_added,
-                    is_deleted: r.gf_is_deleted.unwrap_or(false),
-                    sound_file: Some(SoundFile {
-                        id: r.file_id.into(),
-                        display_name: r.display_name,
-                        owner: r.owner.map(|o| o.into()),
-                        time_added: r.time_added,
-                        is_public: r.is_public.unwrap_or(false),
-                        is_deleted: r.is_deleted.unwrap_or(false),
-                    }),
-                }));
-            }
+            Some(r) => r.into(),
             None => return Ok(None),
-        }
+        };
+        return Ok(Self::from_files(guild_id, vec![sound_file], transaction)
+            .await?
+            .pop());
+    }
+
+    /// Guild associations of the files in the order of the files, files not in the guild are left out.
+    pub async fn from_files(
+        guild_id: &GuildId,
+        files: Vec<SoundFile>,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<Self>, KekServerError> {
+        let file_ids = files.iter().map(|f| f.id.0 as i64).collect::<Vec<i64>>();
+        let mut associations = sqlx::query!(
+            "
+            SELECT file_id, time_added, is_deleted FROM guild_file
+            WHERE guild_id = $1 AND file_id = ANY($2)
+            ",
+            guild_id.0 as i64,
+            &file_ids
+        )
+        .fetch_all(&mut *transaction)
+        .await?
+        .into_iter()
+        .map(|r| (r.file_id, (r.time_added, r.is_deleted.unwrap_or(false))))
+        .collect::<HashMap<i64, (NaiveDateTime, bool)>>();
+
+        return Ok(files
+            .into_iter()
+            .filter_map(|file| {
+                let (time_added, is_deleted) = associations.remove(&(file.id.0 as i64))?;
+                return Some(Self {
+                    guild_id: guild_id.clone(),
+                    file_id: file.id.clone(),
+                    time_added,
+                    is_deleted,
+                    sound_file: Some(file),
+                });
+            })
+            .collect());
     }
 
     pub async fn get_matching_guilds_for_file(
@@ -200,18 +255,12 @@ impl GuildFile {
         guild_id: &GuildId,
         transaction: &mut Transaction<'_, Postgres>,
     ) -> Result<HashSet<SoundFile>, KekServerError> {
-        let records = sqlx::query!(
+        let records = sqlx::query_as!(
+            SoundFileRow,
             "
-            SELECT 
-                id,
-                display_name,
-                owner,
-                files.time_added as file_time_added,
-                files.is_public as file_is_public,
-                files.is_deleted as file_is_deleted
-            FROM guild_file
-            INNER JOIN files ON files.id = guild_file.file_id 
-            AND owner = $1 
+            SELECT files.* FROM guild_file
+            INNER JOIN files ON files.id = guild_file.file_id
+            AND owner = $1
             AND guild_id = $2
             AND guild_file.is_deleted = false
             ",
@@ -221,19 +270,7 @@ impl GuildFile {
         .fetch_all(&mut *transaction)
         .await?;
 
-        let enabled_sounds = records
-            .into_iter()
-            .map(|r| SoundFile {
-                id: r.id.into(),
-                display_name: r.display_name,
-                owner: r.owner.map(|o| o.into()),
-                time_added: r.file_time_added,
-                is_public: r.file_is_public.unwrap_or(false),
-                is_deleted: r.file_is_deleted.unwrap_or(false),
-            })
-            .collect();
-
-        return Ok(enabled_sounds);
+        return Ok(records.into_iter().map(SoundFile::from).collect());
     }
 
     pub async fn bulk_insert(
@@ -264,8 +301,9 @@ mod tests {
     use crate::{
         database::tests_db_helper::db_connection,
         models::{
-            ids::{GuildId, SoundFileId, UserId},
-            sound_file::SoundFile,
+            ids::{GuildId, SoundFileId},
+            sound_file::SoundFileRow,
+            tag::TagFilter,
         },
         utils::test_utils::{
             insert_file_test_util, insert_guild_file_test_util, insert_guild_test_util,
@@ -281,39 +319,22 @@ mod tests {
         file_id: &SoundFileId,
         transaction: &mut Transaction<'_, Postgres>,
     ) -> GuildFile {
-        let guild_file = sqlx::query!(
+        let sound_file = sqlx::query_as!(
+            SoundFileRow,
             "
-            SELECT
-                files.*,
-                guild_file.guild_id,
-                guild_file.file_id,
-                guild_file.time_added as gf_time_added,
-                guild_file.is_deleted as gf_is_deleted
-            FROM guild_file
-            INNER JOIN files ON files.id = $2
-            WHERE guild_file.guild_id = $1 AND guild_file.file_id = $2
+            SELECT * FROM files
+            WHERE id = $1
             ",
-            guild_id.0 as i64,
             file_id.0 as i64
         )
-        .fetch_optional(&mut *transaction)
+        .fetch_one(&mut *transaction)
         .await
-        .unwrap()
-        .map(|r| GuildFile {
-            guild_id: GuildId(r.guild_id as u64),
-            file_id: SoundFileId(r.file_id as u64),
-            time_added: r.gf_time_added,
-            is_deleted: r.gf_is_deleted.unwrap_or(false),
-            sound_file: Some(SoundFile {
-                id: SoundFileId(r.id as u64),
-                owner: r.owner.map(|o| UserId(o as u64)),
-                display_name: r.display_name,
-                time_added: r.time_added,
-                is_public: r.is_public.unwrap_or(false),
-                is_deleted: r.is_deleted.unwrap_or(false),
-            }),
-        })
         .unwrap();
+        let guild_file = GuildFile::from_files(guild_id, vec![sound_file.into()], transaction)
+            .await
+            .unwrap()
+            .pop()
+            .unwrap();
 
         return guild_file;
     }
@@ -444,9 +465,10 @@ mod tests {
             }
         }
 
-        let guild_files = GuildFile::get_guild_files(&guild.id, &mut transaction)
-            .await
-            .unwrap();
+        let guild_files =
+            GuildFile::get_guild_files(&guild.id, &TagFilter::default(), &mut transaction)
+                .await
+                .unwrap();
 
         transaction.commit().await.unwrap();
 
@@ -0,0 +1,166 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+
+use crate::error::errors::KekServerError;
+
+use super::ids::{GuildId, UserId};
+
+/// Member trusted with curating the guild's sounds without Discord permissions.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GuildManager {
+    pub guild_id: GuildId,
+    pub user_id: UserId,
+    pub added_by: UserId,
+    pub time_added: NaiveDateTime,
+}
+
+impl GuildManager {
+    /// Adding an existing manager returns the stored manager.
+    pub async fn insert(
+        guild_id: &GuildId,
+        user_id: &UserId,
+        added_by: &UserId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Self, KekServerError> {
+        let r = sqlx::query!(
+            "
+            INSERT INTO guild_manager (guild_id, user_id, added_by)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (guild_id, user_id)
+            DO UPDATE
+            SET guild_id = guild_manager.guild_id
+            RETURNING *
+            ",
+            guild_id.0 as i64,
+            user_id.0 as i64,
+            added_by.0 as i64
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        return Ok(Self {
+            guild_id: r.guild_id.into(),
+            user_id: r.user_id.into(),
+            added_by: r.added_by.into(),
+            time_added: r.time_added,
+        });
+    }
+
+    pub async fn get_guild_managers(
+        guild_id: &GuildId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<Self>, KekServerError> {
+        let records = sqlx::query!(
+            "
+            SELECT * FROM guild_manager
+            WHERE guild_id = $1
+            ORDER BY time_added
+            ",
+            guild_id.0 as i64
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records
+            .into_iter()
+            .map(|r| Self {
+                guild_id: r.guild_id.into(),
+                user_id: r.user_id.into(),
+                added_by: r.added_by.into(),
+                time_added: r.time_added,
+            })
+            .collect());
+    }
+
+    pub async fn is_manager(
+        guild_id: &GuildId,
+        user_id: &UserId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<bool, KekServerError> {
+        let r = sqlx::query!(
+            "
+            SELECT EXISTS (
+                SELECT 1 FROM guild_manager
+                WHERE guild_id = $1 AND user_id = $2
+            ) as \"is_manager!\"
+            ",
+            guild_id.0 as i64,
+            user_id.0 as i64
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        return Ok(r.is_manager);
+    }
+
+    /// Returns `false` if the user wasn't a manager of the guild.
+    pub async fn delete(
+        guild_id: &GuildId,
+        user_id: &UserId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<bool, KekServerError> {
+        let result = sqlx::query!(
+            "
+            DELETE FROM guild_manager
+            WHERE guild_id = $1 AND user_id = $2
+            ",
+            guild_id.0 as i64,
+            user_id.0 as i64
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(result.rows_affected() > 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection;
+
+    use crate::{
+        database::tests_db_helper::db_connection,
+        utils::test_utils::{insert_guild_test_util, insert_user_test_util},
+    };
+
+    use super::GuildManager;
+
+    #[actix_web::test]
+    async fn test_guild_managers() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let guild = insert_guild_test_util(&mut transaction).await;
+        let admin = insert_user_test_util(&mut transaction).await;
+        let user = insert_user_test_util(&mut transaction).await;
+
+        let manager = GuildManager::insert(&guild.id, &user.id, &admin.id, &mut transaction)
+            .await
+            .unwrap();
+        let existing_manager =
+            GuildManager::insert(&guild.id, &user.id, &user.id, &mut transaction)
+                .await
+                .unwrap();
+        let managers = GuildManager::get_guild_managers(&guild.id, &mut transaction)
+            .await
+            .unwrap();
+        let is_manager = GuildManager::is_manager(&guild.id, &user.id, &mut transaction)
+            .await
+            .unwrap();
+        let is_admin_manager = GuildManager::is_manager(&guild.id, &admin.id, &mut transaction)
+            .await
+            .unwrap();
+        let deleted = GuildManager::delete(&guild.id, &user.id, &mut transaction)
+            .await
+            .unwrap();
+        let is_deleted_manager = GuildManager::is_manager(&guild.id, &user.id, &mut transaction)
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(manager.added_by, admin.id);
+        assert_eq!(existing_manager, manager);
+        assert_eq!(managers, vec![manager]);
+        assert!(is_manager);
+        assert!(!is_admin_manager);
+        assert!(deleted);
+        assert!(!is_deleted_manager);
+    }
+}
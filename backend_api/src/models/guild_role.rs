@@ -0,0 +1,356 @@
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+
+use crate::error::errors::KekServerError;
+
+use super::ids::{GuildId, RoleId, UserId};
+
+/// Guild actions which can be restricted to Discord roles.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum GuildAction {
+    /// Playing, stopping and skipping sounds
+    Play,
+    /// Enabling files and packs in the guild
+    Add,
+    /// Removing files from the guild
+    Remove,
+    /// Changing guild settings
+    Manage,
+}
+
+impl Display for GuildAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let action = match self {
+            GuildAction::Play => "play",
+            GuildAction::Add => "add",
+            GuildAction::Remove => "remove",
+            GuildAction::Manage => "manage",
+        };
+        return write!(f, "{}", action);
+    }
+}
+
+/// Actions members with the role are allowed to do in the guild.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GuildRolePermission {
+    pub guild_id: GuildId,
+    pub role_id: RoleId,
+    pub can_play: bool,
+    pub can_add: bool,
+    pub can_remove: bool,
+    pub can_manage: bool,
+}
+
+impl GuildRolePermission {
+    pub fn allows(&self, action: GuildAction) -> bool {
+        return match action {
+            GuildAction::Play => self.can_play,
+            GuildAction::Add => self.can_add,
+            GuildAction::Remove => self.can_remove,
+            GuildAction::Manage => self.can_manage,
+        };
+    }
+
+    /// Guilds without configured roles let every member play and add files,
+    /// otherwise one of the member's roles has to allow the action.
+    pub fn is_allowed(permissions: &[Self], member_roles: &[RoleId], action: GuildAction) -> bool {
+        if permissions.is_empty() {
+            return matches!(action, GuildAction::Play | GuildAction::Add);
+        }
+        return permissions
+            .iter()
+            .any(|p| member_roles.contains(&p.role_id) && p.allows(action));
+    }
+
+    pub async fn get_guild_permissions(
+        guild_id: &GuildId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<Self>, KekServerError> {
+        let records = sqlx::query!(
+            "
+            SELECT * FROM guild_role_permission
+            WHERE guild_id = $1
+            ORDER BY role_id
+            ",
+            guild_id.0 as i64
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records
+            .into_iter()
+            .map(|r| Self {
+                guild_id: r.guild_id.into(),
+                role_id: r.role_id.into(),
+                can_play: r.can_play,
+                can_add: r.can_add,
+                can_remove: r.can_remove,
+                can_manage: r.can_manage,
+            })
+            .collect());
+    }
+
+    pub async fn upsert(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        sqlx::query!(
+            "
+            INSERT INTO guild_role_permission (
+                guild_id,
+                role_id,
+                can_play,
+                can_add,
+                can_remove,
+                can_manage
+            )
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (guild_id, role_id)
+            DO UPDATE
+            SET can_play = $3, can_add = $4, can_remove = $5, can_manage = $6
+            ",
+            self.guild_id.0 as i64,
+            self.role_id.0 as i64,
+            self.can_play,
+            self.can_add,
+            self.can_remove,
+            self.can_manage
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+
+    /// Returns `false` if the role wasn't configured in the guild.
+    pub async fn delete(
+        guild_id: &GuildId,
+        role_id: &RoleId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<bool, KekServerError> {
+        let result = sqlx::query!(
+            "
+            DELETE FROM guild_role_permission
+            WHERE guild_id = $1 AND role_id = $2
+            ",
+            guild_id.0 as i64,
+            role_id.0 as i64
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(result.rows_affected() > 0);
+    }
+}
+
+/// Discord roles of guild members, pushed by the bot over the sync websocket.
+pub struct GuildMemberRoles;
+
+/// Roles of one member, the bot sends the roles of all members of a guild in one message.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MemberRoles {
+    pub user_id: UserId,
+    pub role_ids: Vec<RoleId>,
+}
+
+impl GuildMemberRoles {
+    /// Roles of the member including the `@everyone` role, which shares its id with the guild.
+    pub async fn get(
+        guild_id: &GuildId,
+        user_id: &UserId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<RoleId>, KekServerError> {
+        let mut roles = sqlx::query!(
+            "
+            SELECT role_ids FROM guild_member_roles
+            WHERE guild_id = $1 AND user_id = $2
+            ",
+            guild_id.0 as i64,
+            user_id.0 as i64
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        .map(|r| {
+            r.role_ids
+                .into_iter()
+                .map(RoleId::from)
+                .collect::<Vec<RoleId>>()
+        })
+        .unwrap_or_default();
+        roles.push(RoleId(guild_id.0));
+        return Ok(roles);
+    }
+
+    pub async fn update(
+        guild_id: &GuildId,
+        user_id: &UserId,
+        role_ids: &[RoleId],
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        let role_ids = role_ids.iter().map(|r| r.0 as i64).collect::<Vec<i64>>();
+        sqlx::query!(
+            "
+            INSERT INTO guild_member_roles (guild_id, user_id, role_ids)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (guild_id, user_id)
+            DO UPDATE
+            SET role_ids = $3
+            ",
+            guild_id.0 as i64,
+            user_id.0 as i64,
+            &role_ids
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+
+    /// Stores the roles of many members of the guild, members missing from `members` are left unchanged.
+    pub async fn update_members(
+        guild_id: &GuildId,
+        members: &[MemberRoles],
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        for member in members {
+            GuildMemberRoles::update(
+                guild_id,
+                &member.user_id,
+                &member.role_ids,
+                &mut *transaction,
+            )
+            .await?;
+        }
+        return Ok(());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection;
+
+    use crate::{
+        database::tests_db_helper::db_connection,
+        models::ids::{GuildId, RoleId},
+        utils::test_utils::{insert_guild_test_util, insert_user_test_util},
+    };
+
+    use super::{GuildAction, GuildMemberRoles, GuildRolePermission, MemberRoles};
+
+    fn role_permission(role_id: u64, can_play: bool, can_manage: bool) -> GuildRolePermission {
+        return GuildRolePermission {
+            guild_id: GuildId(1),
+            role_id: RoleId(role_id),
+            can_play,
+            can_add: false,
+            can_remove: false,
+            can_manage,
+        };
+    }
+
+    #[test]
+    fn test_is_allowed() {
+        let member_roles = vec![RoleId(1), RoleId(2)];
+        assert!(GuildRolePermission::is_allowed(
+            &[],
+            &member_roles,
+            GuildAction::Play
+        ));
+        assert!(!GuildRolePermission::is_allowed(
+            &[],
+            &member_roles,
+            GuildAction::Remove
+        ));
+
+        let permissions = vec![
+            role_permission(2, true, false),
+            role_permission(3, true, true),
+        ];
+        assert!(GuildRolePermission::is_allowed(
+            &permissions,
+            &member_roles,
+            GuildAction::Play
+        ));
+        assert!(!GuildRolePermission::is_allowed(
+            &permissions,
+            &member_roles,
+            GuildAction::Manage
+        ));
+        assert!(!GuildRolePermission::is_allowed(
+            &permissions,
+            &member_roles,
+            GuildAction::Add
+        ));
+    }
+
+    #[actix_web::test]
+    async fn test_guild_roles() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let guild = insert_guild_test_util(&mut transaction).await;
+        let user = insert_user_test_util(&mut transaction).await;
+        let mut permission = GuildRolePermission {
+            guild_id: guild.id.clone(),
+            role_id: RoleId(1),
+            can_play: true,
+            can_add: false,
+            can_remove: false,
+            can_manage: false,
+        };
+        permission.upsert(&mut transaction).await.unwrap();
+        permission.can_add = true;
+        permission.upsert(&mut transaction).await.unwrap();
+        let permissions = GuildRolePermission::get_guild_permissions(&guild.id, &mut transaction)
+            .await
+            .unwrap();
+
+        let default_roles = GuildMemberRoles::get(&guild.id, &user.id, &mut transaction)
+            .await
+            .unwrap();
+        GuildMemberRoles::update(&guild.id, &user.id, &[RoleId(1)], &mut transaction)
+            .await
+            .unwrap();
+        let member_roles = GuildMemberRoles::get(&guild.id, &user.id, &mut transaction)
+            .await
+            .unwrap();
+        let other_user = insert_user_test_util(&mut transaction).await;
+        GuildMemberRoles::update_members(
+            &guild.id,
+            &[
+                MemberRoles {
+                    user_id: user.id.clone(),
+                    role_ids: vec![RoleId(2)],
+                },
+                MemberRoles {
+                    user_id: other_user.id.clone(),
+                    role_ids: vec![],
+                },
+            ],
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let updated_roles = GuildMemberRoles::get(&guild.id, &user.id, &mut transaction)
+            .await
+            .unwrap();
+        let other_roles = GuildMemberRoles::get(&guild.id, &other_user.id, &mut transaction)
+            .await
+            .unwrap();
+
+        let deleted = GuildRolePermission::delete(&guild.id, &RoleId(1), &mut transaction)
+            .await
+            .unwrap();
+        let deleted_again = GuildRolePermission::delete(&guild.id, &RoleId(1), &mut transaction)
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(permissions, vec![permission]);
+        assert_eq!(default_roles, vec![RoleId(guild.id.0)]);
+        assert_eq!(member_roles, vec![RoleId(1), RoleId(guild.id.0)]);
+        assert_eq!(updated_roles, vec![RoleId(2), RoleId(guild.id.0)]);
+        assert_eq!(other_roles, vec![RoleId(guild.id.0)]);
+        assert!(deleted);
+        assert!(!deleted_again);
+    }
+}
@@ -0,0 +1,208 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+
+use crate::error::errors::KekServerError;
+
+use super::ids::{ChannelId, GuildId};
+
+pub const DEFAULT_VOLUME: i16 = 100;
+pub const MAX_VOLUME: i16 = 100;
+pub const DEFAULT_MAX_QUEUE_LENGTH: i16 = 10;
+pub const MAX_QUEUE_LENGTH: i16 = 50;
+
+/// Per guild bot settings, guilds without stored settings use the defaults.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GuildSettings {
+    pub guild_id: GuildId,
+    /// Playback volume in percent
+    pub volume: i16,
+    /// Number of sounds which can wait in the queue
+    pub max_queue_length: i16,
+    /// Members without permissions can only enable their own files if this is false
+    pub members_can_add_public_files: bool,
+    /// Channel the bot joins if a play request doesn't pick one
+    pub default_voice_channel_id: Option<ChannelId>,
+}
+
+impl GuildSettings {
+    pub fn new(guild_id: GuildId) -> Self {
+        return Self {
+            guild_id,
+            volume: DEFAULT_VOLUME,
+            max_queue_length: DEFAULT_MAX_QUEUE_LENGTH,
+            members_can_add_public_files: true,
+            default_voice_channel_id: None,
+        };
+    }
+
+    pub fn validate_volume(volume: i16) -> Result<i16, KekServerError> {
+        if !(0..=MAX_VOLUME).contains(&volume) {
+            return Err(KekServerError::InvalidGuildSettingsError(format!(
+                "volume has to be between 0 and {}",
+                MAX_VOLUME
+            )));
+        }
+        return Ok(volume);
+    }
+
+    pub fn validate_max_queue_length(max_queue_length: i16) -> Result<i16, KekServerError> {
+        if !(1..=MAX_QUEUE_LENGTH).contains(&max_queue_length) {
+            return Err(KekServerError::InvalidGuildSettingsError(format!(
+                "max_queue_length has to be between 1 and {}",
+                MAX_QUEUE_LENGTH
+            )));
+        }
+        return Ok(max_queue_length);
+    }
+
+    pub async fn get(
+        guild_id: &GuildId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Self, KekServerError> {
+        match sqlx::query!(
+            "
+            SELECT * FROM guild_settings
+            WHERE guild_id = $1
+            ",
+            guild_id.0 as i64
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        {
+            Some(r) => {
+                return Ok(Self {
+                    guild_id: r.guild_id.into(),
+                    volume: r.volume,
+                    max_queue_length: r.max_queue_length,
+                    members_can_add_public_files: r.members_can_add_public_files,
+                    default_voice_channel_id: r.default_voice_channel_id.map(|c| c.into()),
+                });
+            }
+            None => return Ok(Self::new(guild_id.clone())),
+        }
+    }
+
+    /// Stores the settings of the guild, fields which are `None` are left unchanged.
+    /// `Some(None)` removes the default voice channel.
+    pub async fn update(
+        guild_id: &GuildId,
+        volume: Option<i16>,
+        max_queue_length: Option<i16>,
+        members_can_add_public_files: Option<bool>,
+        default_voice_channel_id: Option<Option<ChannelId>>,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Self, KekServerError> {
+        let r = sqlx::query!(
+            "
+            INSERT INTO guild_settings (
+                guild_id,
+                volume,
+                max_queue_length,
+                members_can_add_public_files,
+                default_voice_channel_id
+            )
+            VALUES ($1, COALESCE($2::SMALLINT, $7), COALESCE($3::SMALLINT, $8), COALESCE($4::BOOLEAN, true), $6)
+            ON CONFLICT (guild_id)
+            DO UPDATE
+            SET volume = COALESCE($2, guild_settings.volume),
+            max_queue_length = COALESCE($3, guild_settings.max_queue_length),
+            members_can_add_public_files = COALESCE($4, guild_settings.members_can_add_public_files),
+            default_voice_channel_id = CASE WHEN $5::BOOLEAN THEN $6::BIGINT ELSE guild_settings.default_voice_channel_id END
+            RETURNING *
+            ",
+            guild_id.0 as i64,
+            volume,
+            max_queue_length,
+            members_can_add_public_files,
+            default_voice_channel_id.is_some(),
+            default_voice_channel_id.flatten().map(|c| c.0 as i64),
+            DEFAULT_VOLUME,
+            DEFAULT_MAX_QUEUE_LENGTH
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        return Ok(Self {
+            guild_id: r.guild_id.into(),
+            volume: r.volume,
+            max_queue_length: r.max_queue_length,
+            members_can_add_public_files: r.members_can_add_public_files,
+            default_voice_channel_id: r.default_voice_channel_id.map(|c| c.into()),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection;
+
+    use crate::{
+        database::tests_db_helper::db_connection, models::ids::ChannelId,
+        utils::test_utils::insert_guild_test_util,
+    };
+
+    use super::{GuildSettings, MAX_QUEUE_LENGTH, MAX_VOLUME};
+
+    #[test]
+    fn test_validate_guild_settings() {
+        assert!(GuildSettings::validate_volume(0).is_ok());
+        assert!(GuildSettings::validate_volume(MAX_VOLUME).is_ok());
+        assert!(GuildSettings::validate_volume(-1).is_err());
+        assert!(GuildSettings::validate_volume(MAX_VOLUME + 1).is_err());
+        assert!(GuildSettings::validate_max_queue_length(MAX_QUEUE_LENGTH).is_ok());
+        assert!(GuildSettings::validate_max_queue_length(0).is_err());
+        assert!(GuildSettings::validate_max_queue_length(MAX_QUEUE_LENGTH + 1).is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_guild_settings() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let guild = insert_guild_test_util(&mut transaction).await;
+        let default_settings = GuildSettings::get(&guild.id, &mut transaction)
+            .await
+            .unwrap();
+        let inserted_settings = GuildSettings::update(
+            &guild.id,
+            Some(50),
+            None,
+            Some(false),
+            Some(Some(ChannelId(1))),
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let updated_settings =
+            GuildSettings::update(&guild.id, None, Some(20), None, None, &mut transaction)
+                .await
+                .unwrap();
+        let removed_channel_settings =
+            GuildSettings::update(&guild.id, None, None, None, Some(None), &mut transaction)
+                .await
+                .unwrap();
+        let gotten_settings = GuildSettings::get(&guild.id, &mut transaction)
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(default_settings, GuildSettings::new(guild.id.clone()));
+        assert_eq!(inserted_settings.volume, 50);
+        assert_eq!(
+            inserted_settings.max_queue_length,
+            default_settings.max_queue_length
+        );
+        assert!(!inserted_settings.members_can_add_public_files);
+        assert_eq!(
+            inserted_settings.default_voice_channel_id,
+            Some(ChannelId(1))
+        );
+        assert_eq!(updated_settings.volume, 50);
+        assert_eq!(updated_settings.max_queue_length, 20);
+        assert_eq!(
+            updated_settings.default_voice_channel_id,
+            Some(ChannelId(1))
+        );
+        assert_eq!(removed_channel_settings.default_voice_channel_id, None);
+        assert_eq!(gotten_settings, removed_channel_settings);
+    }
+}
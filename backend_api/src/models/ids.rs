@@ -170,9 +170,132 @@ impl Serialize for ChannelId {
     }
 }
 
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Hash)]
+#[serde(try_from = "String")]
+pub struct PackId(pub u64);
+
+impl Id for PackId {
+    fn get_id(&self) -> u64 {
+        return self.0;
+    }
+}
+
+impl From<i64> for PackId {
+    fn from(i: i64) -> Self {
+        return Self(i as u64);
+    }
+}
+
+impl FromStr for PackId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return Ok(Self(s.parse()?));
+    }
+}
+
+impl TryFrom<String> for PackId {
+    type Error = ParseIntError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        return Self::from_str(&value);
+    }
+}
+
+impl Serialize for PackId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        return serializer.serialize_str(&self.0.to_string());
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Hash)]
+#[serde(try_from = "String")]
+pub struct TransferOfferId(pub u64);
+
+impl Id for TransferOfferId {
+    fn get_id(&self) -> u64 {
+        return self.0;
+    }
+}
+
+impl From<i64> for TransferOfferId {
+    fn from(i: i64) -> Self {
+        return Self(i as u64);
+    }
+}
+
+impl FromStr for TransferOfferId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return Ok(Self(s.parse()?));
+    }
+}
+
+impl TryFrom<String> for TransferOfferId {
+    type Error = ParseIntError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        return Self::from_str(&value);
+    }
+}
+
+impl Serialize for TransferOfferId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        return serializer.serialize_str(&self.0.to_string());
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Hash)]
+#[serde(try_from = "String")]
+pub struct RoleId(pub u64);
+
+impl Id for RoleId {
+    fn get_id(&self) -> u64 {
+        return self.0;
+    }
+}
+
+impl From<i64> for RoleId {
+    fn from(i: i64) -> Self {
+        return Self(i as u64);
+    }
+}
+
+impl FromStr for RoleId {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        return Ok(Self(s.parse()?));
+    }
+}
+
+impl TryFrom<String> for RoleId {
+    type Error = ParseIntError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        return Self::from_str(&value);
+    }
+}
+
+impl Serialize for RoleId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        return serializer.serialize_str(&self.0.to_string());
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{ChannelId, GuildId, SoundFileId, UserId};
+    use super::{ChannelId, GuildId, PackId, RoleId, SoundFileId, TransferOfferId, UserId};
     const TESTNUM: u64 = 123;
 
     #[test]
@@ -206,4 +329,28 @@ mod tests {
         let id: Option<ChannelId> = serde_json::from_str(r#"123"#).ok();
         assert_eq!(id, None);
     }
+
+    #[test]
+    fn test_deserialize_pack_id() {
+        let id: Option<PackId> = serde_json::from_str(r#""123""#).ok();
+        assert_eq!(id.unwrap().0, TESTNUM);
+        let id: Option<PackId> = serde_json::from_str(r#"123"#).ok();
+        assert_eq!(id, None);
+    }
+
+    #[test]
+    fn test_deserialize_transfer_offer_id() {
+        let id: Option<TransferOfferId> = serde_json::from_str(r#""123""#).ok();
+        assert_eq!(id.unwrap().0, TESTNUM);
+        let id: Option<TransferOfferId> = serde_json::from_str(r#"123"#).ok();
+        assert_eq!(id, None);
+    }
+
+    #[test]
+    fn test_deserialize_role_id() {
+        let id: Option<RoleId> = serde_json::from_str(r#""123""#).ok();
+        assert_eq!(id.unwrap().0, TESTNUM);
+        let id: Option<RoleId> = serde_json::from_str(r#"123"#).ok();
+        assert_eq!(id, None);
+    }
 }
@@ -1,8 +1,18 @@
+pub mod favorite;
+pub mod file_version;
+pub mod file_waveform;
 pub mod guild;
 pub mod guild_file;
+pub mod guild_manager;
+pub mod guild_role;
+pub mod guild_settings;
 pub mod ids;
+pub mod pack;
 pub mod sound_file;
 pub mod state;
+pub mod tag;
+pub mod transfer_offer;
+pub mod upload_session;
 pub mod user;
 
 pub fn postgres_like_escape(input: String) -> String {
@@ -24,7 +34,7 @@ pub fn postgres_like_escape(input: String) -> String {
     return input;
 }
 
-fn characters_to_escape(ch: char) ->  Option<&'static str> {
+fn characters_to_escape(ch: char) -> Option<&'static str> {
     match ch {
         '%' => Some(r#"\%"#),
         '\\' => Some(r#"\\"#),
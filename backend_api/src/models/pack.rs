@@ -0,0 +1,591 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+
+use crate::error::errors::KekServerError;
+
+use super::{
+    ids::{GuildId, PackId, SoundFileId, UserId},
+    sound_file::{SoundFile, SoundFileRow, MAX_LIMIT},
+};
+
+pub const MAX_PACK_NAME_LENGTH: usize = 50;
+
+/// Named collection of sound files owned by a user, it can hold the owner's files and public files.
+/// Public packs can be viewed and enabled in guilds by every user.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Pack {
+    pub id: PackId,
+    pub owner: UserId,
+    pub name: String,
+    pub description: Option<String>,
+    pub is_public: bool,
+    pub time_added: NaiveDateTime,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PackFiles {
+    #[serde(flatten)]
+    pub pack: Pack,
+    pub files: Vec<SoundFile>,
+}
+
+impl Pack {
+    /// Trimmed pack name, it has to fit the `name` column.
+    pub fn validate_name(name: &str) -> Result<String, KekServerError> {
+        let name = name.trim();
+        if name.is_empty() || name.chars().count() > MAX_PACK_NAME_LENGTH {
+            return Err(KekServerError::InvalidPackNameError(MAX_PACK_NAME_LENGTH));
+        }
+        return Ok(name.to_string());
+    }
+
+    pub async fn insert(
+        id: &PackId,
+        owner: &UserId,
+        name: &str,
+        description: Option<&str>,
+        is_public: bool,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Self, KekServerError> {
+        let r = sqlx::query!(
+            "
+            INSERT INTO pack (id, owner, name, description, is_public)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING *
+            ",
+            id.0 as i64,
+            owner.0 as i64,
+            name,
+            description,
+            is_public
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        return Ok(Self {
+            id: r.id.into(),
+            owner: r.owner.into(),
+            name: r.name,
+            description: r.description,
+            is_public: r.is_public,
+            time_added: r.time_added,
+        });
+    }
+
+    /// Returns the pack if it's public or owned by the user.
+    pub async fn get(
+        id: &PackId,
+        user_id: &UserId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Self>, KekServerError> {
+        match sqlx::query!(
+            "
+            SELECT * FROM pack
+            WHERE id = $1 AND (is_public = true OR owner = $2)
+            ",
+            id.0 as i64,
+            user_id.0 as i64
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        {
+            Some(r) => {
+                return Ok(Some(Self {
+                    id: r.id.into(),
+                    owner: r.owner.into(),
+                    name: r.name,
+                    description: r.description,
+                    is_public: r.is_public,
+                    time_added: r.time_added,
+                }));
+            }
+            None => return Ok(None),
+        }
+    }
+
+    pub async fn get_user_packs(
+        user_id: &UserId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<Self>, KekServerError> {
+        let records = sqlx::query!(
+            "
+            SELECT * FROM pack
+            WHERE owner = $1
+            ORDER BY time_added DESC, id DESC
+            ",
+            user_id.0 as i64
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records
+            .into_iter()
+            .map(|r| Self {
+                id: r.id.into(),
+                owner: r.owner.into(),
+                name: r.name,
+                description: r.description,
+                is_public: r.is_public,
+                time_added: r.time_added,
+            })
+            .collect());
+    }
+
+    pub async fn get_public_packs(
+        limit: i64,
+        page: i64,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<Self>, KekServerError> {
+        let limit = if (1..=MAX_LIMIT).contains(&limit) {
+            limit
+        } else {
+            MAX_LIMIT
+        };
+        let offset = if page < 1 { 0 } else { page - 1 };
+        let records = sqlx::query!(
+            "
+            SELECT * FROM pack
+            WHERE is_public = true
+            ORDER BY time_added DESC, id DESC
+            LIMIT $1 OFFSET $2
+            ",
+            limit,
+            limit * offset
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records
+            .into_iter()
+            .map(|r| Self {
+                id: r.id.into(),
+                owner: r.owner.into(),
+                name: r.name,
+                description: r.description,
+                is_public: r.is_public,
+                time_added: r.time_added,
+            })
+            .collect());
+    }
+
+    /// Returns `None` if the pack doesn't exist or isn't owned by the user.
+    pub async fn update(
+        id: &PackId,
+        owner: &UserId,
+        name: Option<&str>,
+        description: Option<Option<&str>>,
+        is_public: Option<bool>,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Self>, KekServerError> {
+        match sqlx::query!(
+            "
+            UPDATE pack
+            SET name = COALESCE($3, name),
+            description = CASE WHEN $4 THEN $5 ELSE description END,
+            is_public = COALESCE($6, is_public)
+            WHERE id = $1 AND owner = $2
+            RETURNING *
+            ",
+            id.0 as i64,
+            owner.0 as i64,
+            name,
+            description.is_some(),
+            description.flatten(),
+            is_public
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        {
+            Some(r) => {
+                return Ok(Some(Self {
+                    id: r.id.into(),
+                    owner: r.owner.into(),
+                    name: r.name,
+                    description: r.description,
+                    is_public: r.is_public,
+                    time_added: r.time_added,
+                }));
+            }
+            None => return Ok(None),
+        }
+    }
+
+    /// Files enabled through the pack stay enabled in guilds.
+    pub async fn delete(
+        id: &PackId,
+        owner: &UserId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Self>, KekServerError> {
+        match sqlx::query!(
+            "
+            DELETE FROM pack
+            WHERE id = $1 AND owner = $2
+            RETURNING *
+            ",
+            id.0 as i64,
+            owner.0 as i64
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        {
+            Some(r) => {
+                return Ok(Some(Self {
+                    id: r.id.into(),
+                    owner: r.owner.into(),
+                    name: r.name,
+                    description: r.description,
+                    is_public: r.is_public,
+                    time_added: r.time_added,
+                }));
+            }
+            None => return Ok(None),
+        }
+    }
+
+    /// Files of the pack in the order they were added. Deleted files and files
+    /// of other users which aren't public anymore are left out.
+    pub async fn get_files(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<SoundFile>, KekServerError> {
+        let records = sqlx::query_as!(
+            SoundFileRow,
+            "
+            SELECT files.* FROM pack_file
+            INNER JOIN files ON files.id = pack_file.file_id
+            WHERE pack_file.pack_id = $1 AND files.is_deleted = false
+            AND (files.is_public = true OR files.owner = $2)
+            ORDER BY pack_file.time_added, files.id
+            ",
+            self.id.0 as i64,
+            self.owner.0 as i64
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records.into_iter().map(SoundFile::from).collect());
+    }
+
+    /// Adds the owner's files and public files to the pack, other files are ignored.
+    /// Returns ids of the files which were added.
+    pub async fn add_files(
+        &self,
+        file_ids: &[SoundFileId],
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<SoundFileId>, KekServerError> {
+        let file_ids = file_ids.iter().map(|f| f.0 as i64).collect::<Vec<i64>>();
+        let added_files = sqlx::query!(
+            "
+            INSERT INTO pack_file (pack_id, file_id)
+            SELECT $1, id FROM files
+            WHERE id = ANY($2) AND is_deleted = false
+            AND (is_public = true OR owner = $3)
+            ON CONFLICT DO NOTHING
+            RETURNING file_id
+            ",
+            self.id.0 as i64,
+            &file_ids,
+            self.owner.0 as i64
+        )
+        .fetch_all(&mut *transaction)
+        .await?
+        .into_iter()
+        .map(|r| r.file_id.into())
+        .collect();
+        return Ok(added_files);
+    }
+
+    /// Guilds the pack is enabled in.
+    pub async fn get_guild_ids(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<GuildId>, KekServerError> {
+        let records = sqlx::query!(
+            "
+            SELECT guild_id FROM guild_pack
+            WHERE pack_id = $1
+            ",
+            self.id.0 as i64
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records.into_iter().map(|r| r.guild_id.into()).collect());
+    }
+
+    /// Enables files added to the pack in a guild the pack is enabled in.
+    pub async fn enable_files_in_guild(
+        &self,
+        guild_id: &GuildId,
+        file_ids: &[SoundFileId],
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        let file_ids = file_ids.iter().map(|f| f.0 as i64).collect::<Vec<i64>>();
+        sqlx::query!(
+            "
+            INSERT INTO guild_file (guild_id, file_id)
+            SELECT $1, file_id FROM pack_file
+            WHERE pack_id = $2 AND file_id = ANY($3)
+            ON CONFLICT (guild_id, file_id)
+            DO UPDATE
+            SET is_deleted = false, deleted_with_file = false
+            ",
+            guild_id.0 as i64,
+            self.id.0 as i64,
+            &file_ids
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+
+    /// Files stay enabled in guilds the pack is enabled in.
+    pub async fn remove_files(
+        &self,
+        file_ids: &[SoundFileId],
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        let file_ids = file_ids.iter().map(|f| f.0 as i64).collect::<Vec<i64>>();
+        sqlx::query!(
+            "
+            DELETE FROM pack_file
+            WHERE pack_id = $1 AND file_id = ANY($2)
+            ",
+            self.id.0 as i64,
+            &file_ids
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+
+    /// Enables all files of the pack in the guild, files added to the pack later are enabled too.
+    pub async fn enable_in_guild(
+        &self,
+        guild_id: &GuildId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        sqlx::query!(
+            "
+            INSERT INTO guild_pack (guild_id, pack_id)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            ",
+            guild_id.0 as i64,
+            self.id.0 as i64
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            "
+            INSERT INTO guild_file (guild_id, file_id)
+            SELECT $1, files.id FROM pack_file
+            INNER JOIN files ON files.id = pack_file.file_id
+            WHERE pack_file.pack_id = $2 AND files.is_deleted = false
+            AND (files.is_public = true OR files.owner = $3)
+            ON CONFLICT (guild_id, file_id)
+            DO UPDATE
+            SET is_deleted = false, deleted_with_file = false
+            ",
+            guild_id.0 as i64,
+            self.id.0 as i64,
+            self.owner.0 as i64
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+
+    /// Stops enabling new files of the pack in the guild, already enabled files stay enabled.
+    /// Returns `false` if the pack wasn't enabled in the guild.
+    pub async fn disable_in_guild(
+        &self,
+        guild_id: &GuildId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<bool, KekServerError> {
+        let result = sqlx::query!(
+            "
+            DELETE FROM guild_pack
+            WHERE guild_id = $1 AND pack_id = $2
+            ",
+            guild_id.0 as i64,
+            self.id.0 as i64
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(result.rows_affected() > 0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::{Connection, Postgres, Transaction};
+    use uuid::Uuid;
+
+    use crate::{
+        database::tests_db_helper::db_connection,
+        models::{
+            guild_file::GuildFile,
+            ids::{GuildId, PackId, SoundFileId, UserId},
+            sound_file::SoundFile,
+        },
+        utils::test_utils::{
+            insert_guild_test_util, insert_random_file_test_util, insert_user_test_util,
+        },
+    };
+
+    use super::{Pack, MAX_PACK_NAME_LENGTH};
+
+    async fn insert_pack(
+        owner: &UserId,
+        is_public: bool,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Pack {
+        let id = PackId(Uuid::new_v4().as_u128() as u64);
+        return Pack::insert(&id, owner, "Test pack", None, is_public, transaction)
+            .await
+            .unwrap();
+    }
+
+    async fn is_enabled(
+        guild_id: &GuildId,
+        file_id: &SoundFileId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> bool {
+        return GuildFile::get_guild_file(guild_id, file_id, transaction)
+            .await
+            .unwrap()
+            .is_some();
+    }
+
+    #[test]
+    fn test_validate_name() {
+        let max_length_name = "a".repeat(MAX_PACK_NAME_LENGTH);
+        assert_eq!(Pack::validate_name(" Pack ").unwrap(), "Pack");
+        assert_eq!(
+            Pack::validate_name(&max_length_name).unwrap(),
+            max_length_name
+        );
+        assert!(Pack::validate_name("  ").is_err());
+        assert!(Pack::validate_name(&format!("{}a", max_length_name)).is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_pack_visibility() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let owner = insert_user_test_util(&mut transaction).await;
+        let user = insert_user_test_util(&mut transaction).await;
+        let pack = insert_pack(&owner.id, false, &mut transaction).await;
+
+        let private_pack = Pack::get(&pack.id, &user.id, &mut transaction)
+            .await
+            .unwrap();
+        let not_updated_pack = Pack::update(
+            &pack.id,
+            &user.id,
+            Some("Other name"),
+            None,
+            Some(true),
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let updated_pack = Pack::update(
+            &pack.id,
+            &owner.id,
+            None,
+            Some(Some("Description")),
+            Some(true),
+            &mut transaction,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        let public_pack = Pack::get(&pack.id, &user.id, &mut transaction)
+            .await
+            .unwrap();
+        let public_packs = Pack::get_public_packs(1000, 1, &mut transaction)
+            .await
+            .unwrap();
+        let user_packs = Pack::get_user_packs(&owner.id, &mut transaction)
+            .await
+            .unwrap();
+        let deleted_pack = Pack::delete(&pack.id, &owner.id, &mut transaction)
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(private_pack, None);
+        assert_eq!(not_updated_pack, None);
+        assert_eq!(updated_pack.name, "Test pack");
+        assert_eq!(updated_pack.description.as_deref(), Some("Description"));
+        assert_eq!(public_pack, Some(updated_pack.clone()));
+        assert!(public_packs.iter().any(|p| p.id == pack.id));
+        assert_eq!(user_packs, vec![updated_pack.clone()]);
+        assert_eq!(deleted_pack, Some(updated_pack));
+    }
+
+    #[actix_web::test]
+    async fn test_pack_files_in_guilds() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let guild = insert_guild_test_util(&mut transaction).await;
+        let own_file = insert_random_file_test_util(&mut transaction).await;
+        let owner = own_file.owner.clone().unwrap();
+        let private_file = insert_random_file_test_util(&mut transaction).await;
+        let public_file = insert_random_file_test_util(&mut transaction).await;
+        SoundFile::update(
+            &public_file.id,
+            public_file.owner.as_ref().unwrap(),
+            None,
+            None,
+            Some(true),
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let pack = insert_pack(&owner, false, &mut transaction).await;
+
+        let added_files = pack
+            .add_files(
+                &[own_file.id.clone(), private_file.id.clone()],
+                &mut transaction,
+            )
+            .await
+            .unwrap();
+        pack.enable_in_guild(&guild.id, &mut transaction)
+            .await
+            .unwrap();
+        let own_file_enabled = is_enabled(&guild.id, &own_file.id, &mut transaction).await;
+        let added_public_files = pack
+            .add_files(std::slice::from_ref(&public_file.id), &mut transaction)
+            .await
+            .unwrap();
+        let guild_ids = pack.get_guild_ids(&mut transaction).await.unwrap();
+        pack.enable_files_in_guild(&guild.id, &added_public_files, &mut transaction)
+            .await
+            .unwrap();
+        let public_file_enabled = is_enabled(&guild.id, &public_file.id, &mut transaction).await;
+        let private_file_enabled = is_enabled(&guild.id, &private_file.id, &mut transaction).await;
+
+        pack.remove_files(std::slice::from_ref(&own_file.id), &mut transaction)
+            .await
+            .unwrap();
+        let pack_files = pack.get_files(&mut transaction).await.unwrap();
+        let disabled = pack
+            .disable_in_guild(&guild.id, &mut transaction)
+            .await
+            .unwrap();
+        let own_file_still_enabled = is_enabled(&guild.id, &own_file.id, &mut transaction).await;
+        transaction.commit().await.unwrap();
+
+        assert_eq!(added_files, vec![own_file.id.clone()]);
+        assert!(own_file_enabled);
+        assert_eq!(guild_ids, vec![guild.id.clone()]);
+        assert!(public_file_enabled);
+        assert!(!private_file_enabled);
+        assert_eq!(pack_files.len(), 1);
+        assert_eq!(pack_files[0].id, public_file.id);
+        assert!(disabled);
+        assert!(own_file_still_enabled);
+    }
+}
@@ -1,12 +1,30 @@
-use chrono::{NaiveDateTime, Utc};
+use chrono::{Duration, NaiveDateTime, Utc};
+use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use sqlx::{Postgres, Transaction};
 
-use crate::error::errors::KekServerError;
+use crate::{error::errors::KekServerError, storage::sound_file_key};
 
-use super::{ids::{SoundFileId, UserId}, postgres_like_escape};
+use super::{
+    guild_file::GuildFile,
+    ids::{GuildId, SoundFileId, UserId},
+    postgres_like_escape,
+    tag::{TagCount, TagFilter, TagMatch},
+};
 
 pub const MAX_LIMIT: i64 = 200;
+pub const MAX_DISPLAY_NAME_LENGTH: usize = 50;
+pub const MAX_DESCRIPTION_LENGTH: usize = 500;
+/// Maximum number of "did you mean" suggestions of a search
+pub const MAX_SUGGESTIONS: i64 = 5;
+
+lazy_static! {
+    /// Minimum word similarity of a display name to the search for the file to be found
+    pub static ref SEARCH_SIMILARITY_THRESHOLD: f32 = dotenv::var("SEARCH_SIMILARITY_THRESHOLD")
+        .unwrap_or_else(|_| 0.3.to_string())
+        .parse()
+        .unwrap_or(0.3);
+}
 
 #[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, Eq)]
 pub struct SoundFile {
@@ -18,6 +36,176 @@ pub struct SoundFile {
     pub is_deleted: bool,
     #[serde(default)]
     pub is_public: bool,
+    #[serde(default)]
+    pub processing_status: ProcessingStatus,
+    #[serde(flatten)]
+    pub metadata: AudioMetadata,
+    /// Key of the stored bytes, files with identical content share the same key.
+    #[serde(skip)]
+    pub storage_key: String,
+    /// SHA-256 of the uploaded content.
+    #[serde(skip)]
+    pub content_hash: Option<String>,
+    /// Uploaded size in bytes, unknown for files uploaded before sizes were stored.
+    pub size: Option<i64>,
+    pub description: Option<String>,
+    /// Start of the played segment, the stored audio is never trimmed.
+    pub start_ms: Option<i32>,
+    /// End of the played segment, plays until the end of the audio if not set.
+    pub end_ms: Option<i32>,
+    /// Number of times the file was played from the website.
+    #[serde(default)]
+    pub play_count: i64,
+    /// File this file was forked from, kept after the original file is deleted.
+    pub forked_from: Option<SoundFileId>,
+    /// Owner of the file this file was forked from.
+    pub forked_from_owner: Option<UserId>,
+    /// Only filled in by file listings, see `Tag::attach_to_files`.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Only filled in by file listings, see `Favorite::attach_to_files`.
+    #[serde(default)]
+    pub is_favorite: bool,
+}
+
+/// Row of the `files` table, queries returning whole files map it with `query_as!`.
+#[derive(Clone, Debug)]
+pub struct SoundFileRow {
+    pub id: i64,
+    pub display_name: Option<String>,
+    pub owner: Option<i64>,
+    pub is_deleted: Option<bool>,
+    pub is_public: Option<bool>,
+    pub time_added: NaiveDateTime,
+    pub processing_status: String,
+    pub duration_ms: Option<i32>,
+    pub codec: Option<String>,
+    pub sample_rate: Option<i32>,
+    pub channels: Option<i16>,
+    pub bitrate: Option<i32>,
+    pub deleted_at: Option<NaiveDateTime>,
+    pub checksum: Option<String>,
+    pub content_hash: Option<String>,
+    pub storage_key: String,
+    pub size: Option<i64>,
+    pub description: Option<String>,
+    pub start_ms: Option<i32>,
+    pub end_ms: Option<i32>,
+    pub play_count: i64,
+    pub forked_from: Option<i64>,
+    pub forked_from_owner: Option<i64>,
+}
+
+impl From<SoundFileRow> for SoundFile {
+    fn from(r: SoundFileRow) -> Self {
+        return Self {
+            id: r.id.into(),
+            display_name: r.display_name,
+            owner: r.owner.map(|o| o.into()),
+            time_added: r.time_added,
+            is_deleted: r.is_deleted.unwrap_or(false),
+            is_public: r.is_public.unwrap_or(false),
+            processing_status: r.processing_status.into(),
+            metadata: AudioMetadata {
+                duration_ms: r.duration_ms,
+                codec: r.codec,
+                sample_rate: r.sample_rate,
+                channels: r.channels,
+                bitrate: r.bitrate,
+            },
+            storage_key: r.storage_key,
+            content_hash: r.content_hash,
+            size: r.size,
+            description: r.description,
+            start_ms: r.start_ms,
+            end_ms: r.end_ms,
+            play_count: r.play_count,
+            forked_from: r.forked_from.map(|f| f.into()),
+            forked_from_owner: r.forked_from_owner.map(|o| o.into()),
+            tags: Vec::new(),
+            is_favorite: false,
+        };
+    }
+}
+
+/// Audio properties probed from the stored file, unknown for files uploaded before probing was introduced.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, Hash, PartialEq, Eq)]
+pub struct AudioMetadata {
+    pub duration_ms: Option<i32>,
+    pub codec: Option<String>,
+    pub sample_rate: Option<i32>,
+    pub channels: Option<i16>,
+    pub bitrate: Option<i32>,
+}
+
+/// State of converting an uploaded file to the canonical format.
+/// Files uploaded before processing was introduced are `Unprocessed`.
+#[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessingStatus {
+    Unprocessed,
+    Pending,
+    Ready,
+    Failed,
+}
+
+impl ProcessingStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ProcessingStatus::Unprocessed => "unprocessed",
+            ProcessingStatus::Pending => "pending",
+            ProcessingStatus::Ready => "ready",
+            ProcessingStatus::Failed => "failed",
+        }
+    }
+}
+
+impl Default for ProcessingStatus {
+    fn default() -> Self {
+        return Self::Unprocessed;
+    }
+}
+
+impl From<String> for ProcessingStatus {
+    fn from(value: String) -> Self {
+        match value.as_str() {
+            "pending" => ProcessingStatus::Pending,
+            "ready" => ProcessingStatus::Ready,
+            "failed" => ProcessingStatus::Failed,
+            _ => ProcessingStatus::Unprocessed,
+        }
+    }
+}
+
+/// Stored state of a file, used for checking storage integrity.
+#[derive(Clone, Debug)]
+pub struct StoredFile {
+    pub id: SoundFileId,
+    pub is_deleted: bool,
+    pub processing_status: ProcessingStatus,
+    pub checksum: Option<String>,
+    pub storage_key: String,
+}
+
+impl From<SoundFileRow> for StoredFile {
+    fn from(r: SoundFileRow) -> Self {
+        return Self {
+            id: r.id.into(),
+            is_deleted: r.is_deleted.unwrap_or(false),
+            processing_status: r.processing_status.into(),
+            checksum: r.checksum,
+            storage_key: r.storage_key,
+        };
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct DeletedSoundFile {
+    #[serde(flatten)]
+    pub sound_file: SoundFile,
+    pub deleted_at: NaiveDateTime,
+    /// Time after which the file can no longer be restored.
+    pub purge_at: NaiveDateTime,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, Eq)]
@@ -28,9 +216,150 @@ pub struct SoundFilePartial {
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct FilesAndCount {
-    pub count: i64,
+    /// Missing with cursor pagination
+    pub count: Option<i64>,
     pub max: i64,
     pub files: Vec<SoundFile>,
+    /// Tags of all files matching the query, not only the returned page
+    pub tag_counts: Vec<TagCount>,
+    /// Similar display names when no file contains the search
+    pub suggestions: Vec<String>,
+    /// Cursors of the adjacent pages with cursor pagination
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+/// Files a listing or search is run on, deleted files are never listed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FileScope {
+    Public,
+    /// Files owned by the user
+    User(UserId),
+    /// Files enabled in the guild
+    Guild(GuildId),
+}
+
+impl FileScope {
+    fn owner(&self) -> Option<i64> {
+        match self {
+            FileScope::User(user_id) => return Some(user_id.0 as i64),
+            _ => return None,
+        }
+    }
+
+    fn guild(&self) -> Option<i64> {
+        match self {
+            FileScope::Guild(guild_id) => return Some(guild_id.0 as i64),
+            _ => return None,
+        }
+    }
+}
+
+/// Query params of searches in a user's or guild's files.
+#[derive(Debug, Deserialize)]
+pub struct SearchQueryParams {
+    pub search_query: String,
+    pub limit: Option<i64>,
+    pub page: Option<i64>,
+    /// Comma separated list of tags
+    pub tags: Option<String>,
+    pub tag_match: Option<TagMatch>,
+}
+
+/// Query params of a user's or guild's file listing.
+#[derive(Debug, Deserialize)]
+pub struct FileListQueryParams {
+    /// Comma separated list of tags
+    pub tags: Option<String>,
+    pub tag_match: Option<TagMatch>,
+    pub sort: Option<FileSort>,
+    pub cursor: Option<String>,
+    pub limit: Option<i64>,
+}
+
+impl FileListQueryParams {
+    /// Listings without pagination params return all files.
+    pub fn is_paginated(&self) -> bool {
+        return self.sort.is_some() || self.cursor.is_some() || self.limit.is_some();
+    }
+}
+
+/// Sort order of paginated file listings, ties are ordered by file id.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileSort {
+    Newest,
+    Oldest,
+    /// Case insensitive display name
+    Name,
+    MostPlayed,
+}
+
+impl Default for FileSort {
+    fn default() -> Self {
+        return Self::Newest;
+    }
+}
+
+impl FileSort {
+    /// Descending sorts list the files with the highest sort column first.
+    fn is_descending(&self) -> bool {
+        return matches!(self, FileSort::Newest | FileSort::MostPlayed);
+    }
+}
+
+/// Position in a paginated listing, clients get it as an opaque string.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FileCursor {
+    sort: FileSort,
+    /// Sort columns of the file the page starts after (or before when going backward)
+    time_added: NaiveDateTime,
+    display_name: String,
+    play_count: i64,
+    id: i64,
+    backward: bool,
+}
+
+impl FileCursor {
+    fn new(sort: FileSort, file: &SoundFile, backward: bool) -> Self {
+        return Self {
+            sort,
+            time_added: file.time_added,
+            display_name: file.display_name.clone().unwrap_or_default(),
+            play_count: file.play_count,
+            id: file.id.0 as i64,
+            backward,
+        };
+    }
+
+    pub fn encode(&self) -> Result<String, KekServerError> {
+        return Ok(base64::encode_config(
+            serde_json::to_vec(self)?,
+            base64::URL_SAFE_NO_PAD,
+        ));
+    }
+
+    pub fn decode(cursor: &str) -> Result<Self, KekServerError> {
+        let bytes = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| KekServerError::InvalidCursorError)?;
+        return serde_json::from_slice(&bytes).map_err(|_| KekServerError::InvalidCursorError);
+    }
+}
+
+/// Page of a cursor paginated listing, cursors are missing on the first and last page.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct FilesPage {
+    pub files: Vec<SoundFile>,
+    pub next: Option<String>,
+    pub prev: Option<String>,
+}
+
+/// Storage used by a user's files, deleted files count until they're purged.
+/// Previous versions of replaced files count towards `bytes`.
+#[derive(Clone, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct StorageUsage {
+    pub bytes: i64,
+    pub files: i64,
 }
 
 impl SoundFile {
@@ -42,6 +371,7 @@ impl SoundFile {
         is_public: bool,
     ) -> Self {
         let now = Utc::now().naive_utc();
+        let storage_key = sound_file_key(&id);
         return Self {
             id,
             display_name: Some(display_name),
@@ -49,34 +379,348 @@ impl SoundFile {
             time_added: now,
             is_deleted: false,
             is_public,
+            processing_status: ProcessingStatus::Pending,
+            metadata: AudioMetadata::default(),
+            storage_key,
+            content_hash: None,
+            size: None,
+            description: None,
+            start_ms: None,
+            end_ms: None,
+            play_count: 0,
+            forked_from: None,
+            forked_from_owner: None,
+            tags: Vec::new(),
+            is_favorite: false,
         };
     }
 
+    /// Trimmed display name, it has to fit the `display_name` column.
+    pub fn validate_display_name(display_name: &str) -> Result<String, KekServerError> {
+        let display_name = display_name.trim();
+        if display_name.is_empty() || display_name.chars().count() > MAX_DISPLAY_NAME_LENGTH {
+            return Err(KekServerError::InvalidDisplayNameError(
+                MAX_DISPLAY_NAME_LENGTH,
+            ));
+        }
+        return Ok(display_name.to_string());
+    }
+
+    /// Trim points have to be inside the audio and the segment can't be empty.
+    /// Duration is unknown for files uploaded before metadata was introduced.
+    pub fn validate_trim(
+        start_ms: Option<i32>,
+        end_ms: Option<i32>,
+        duration_ms: Option<i32>,
+    ) -> Result<(), KekServerError> {
+        let start = start_ms.unwrap_or(0);
+        let end = end_ms.or(duration_ms);
+        if start < 0 {
+            return Err(KekServerError::InvalidTrimError(
+                "start_ms can't be negative".to_string(),
+            ));
+        }
+        if let Some(end) = end {
+            if end <= start {
+                return Err(KekServerError::InvalidTrimError(
+                    "end_ms has to be after start_ms".to_string(),
+                ));
+            }
+        }
+        if let (Some(end), Some(duration)) = (end, duration_ms) {
+            if end > duration {
+                return Err(KekServerError::InvalidTrimError(format!(
+                    "end_ms can't be after the end of the audio at {} ms",
+                    duration
+                )));
+            }
+        }
+        return Ok(());
+    }
+
+    /// Trimmed description, empty descriptions are stored as `NULL`.
+    pub fn validate_description(description: &str) -> Result<Option<String>, KekServerError> {
+        let description = description.trim();
+        if description.chars().count() > MAX_DESCRIPTION_LENGTH {
+            return Err(KekServerError::InvalidDescriptionError(
+                MAX_DESCRIPTION_LENGTH,
+            ));
+        }
+        if description.is_empty() {
+            return Ok(None);
+        }
+        return Ok(Some(description.to_string()));
+    }
+
+    /// Files stored under an existing key share its processing status and checksum.
     pub async fn insert(
         &self,
         transaction: &mut Transaction<'_, Postgres>,
     ) -> Result<Self, KekServerError> {
-        let r = sqlx::query!(
+        let r = sqlx::query_as!(
+            SoundFileRow,
             "
-            INSERT INTO files (id, display_name, owner, is_public)
-            VALUES ($1, $2, $3, $4)
+            INSERT INTO files (
+                id,
+                display_name,
+                owner,
+                is_public,
+                processing_status,
+                duration_ms,
+                codec,
+                sample_rate,
+                channels,
+                bitrate,
+                storage_key,
+                content_hash,
+                size,
+                checksum
+            )
+            VALUES (
+                $1, $2, $3, $4,
+                COALESCE((SELECT processing_status FROM files WHERE storage_key = $11 LIMIT 1), $5),
+                $6, $7, $8, $9, $10, $11, $12, $13,
+                (SELECT checksum FROM files WHERE storage_key = $11 AND checksum IS NOT NULL LIMIT 1)
+            )
             RETURNING *
             ",
             self.id.0 as i64,
             self.display_name,
             self.owner.as_ref().map(|o| o.0 as i64),
-            self.is_public
+            self.is_public,
+            self.processing_status.as_str(),
+            self.metadata.duration_ms,
+            self.metadata.codec,
+            self.metadata.sample_rate,
+            self.metadata.channels,
+            self.metadata.bitrate,
+            self.storage_key,
+            self.content_hash,
+            self.size
         )
         .fetch_one(&mut *transaction)
         .await?;
-        return Ok(Self {
-            id: r.id.into(),
-            display_name: r.display_name,
-            owner: r.owner.map(|o| o.into()),
-            time_added: r.time_added,
-            is_deleted: r.is_deleted.unwrap_or(false),
-            is_public: r.is_public.unwrap_or(false),
-        });
+        return Ok(r.into());
+    }
+
+    /// Replaces the audio of the file with the audio of `self`.
+    /// Files stored under an existing key share its processing status and checksum.
+    pub async fn set_audio(
+        &self,
+        checksum: Option<&str>,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Self, KekServerError> {
+        let r = sqlx::query_as!(
+            SoundFileRow,
+            "
+            UPDATE files
+            SET storage_key = $2::VARCHAR,
+            content_hash = $3,
+            processing_status = COALESCE(
+                (SELECT processing_status FROM files WHERE storage_key = $2 AND id != $1 LIMIT 1),
+                $4
+            ),
+            duration_ms = $5,
+            codec = $6,
+            sample_rate = $7,
+            channels = $8,
+            bitrate = $9,
+            size = $10,
+            checksum = COALESCE(
+                (SELECT checksum FROM files WHERE storage_key = $2 AND id != $1 AND checksum IS NOT NULL LIMIT 1),
+                $11
+            ),
+            start_ms = $12,
+            end_ms = $13
+            WHERE id = $1
+            RETURNING *
+            ",
+            self.id.0 as i64,
+            self.storage_key,
+            self.content_hash,
+            self.processing_status.as_str(),
+            self.metadata.duration_ms,
+            self.metadata.codec,
+            self.metadata.sample_rate,
+            self.metadata.channels,
+            self.metadata.bitrate,
+            self.size,
+            checksum,
+            self.start_ms,
+            self.end_ms
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        return Ok(r.into());
+    }
+
+    pub async fn set_trim(
+        id: &SoundFileId,
+        start_ms: Option<i32>,
+        end_ms: Option<i32>,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        sqlx::query!(
+            "
+            UPDATE files
+            SET start_ms = $2, end_ms = $3
+            WHERE id = $1
+            ",
+            id.0 as i64,
+            start_ms,
+            end_ms
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+
+    /// Copies a public file of another user to the owner's library. The copy is private, shares
+    /// the stored audio with the original file and records where it was forked from.
+    /// Returns `None` if the file isn't public, is deleted or is owned by the owner.
+    pub async fn fork(
+        id: &SoundFileId,
+        file_id: &SoundFileId,
+        owner: &UserId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Self>, KekServerError> {
+        match sqlx::query_as!(
+            SoundFileRow,
+            "
+            INSERT INTO files (
+                id,
+                display_name,
+                owner,
+                is_public,
+                processing_status,
+                duration_ms,
+                codec,
+                sample_rate,
+                channels,
+                bitrate,
+                storage_key,
+                content_hash,
+                size,
+                checksum,
+                description,
+                start_ms,
+                end_ms,
+                forked_from,
+                forked_from_owner
+            )
+            SELECT
+                $1,
+                display_name,
+                $3,
+                false,
+                processing_status,
+                duration_ms,
+                codec,
+                sample_rate,
+                channels,
+                bitrate,
+                storage_key,
+                content_hash,
+                size,
+                checksum,
+                description,
+                start_ms,
+                end_ms,
+                id,
+                owner
+            FROM files
+            WHERE id = $2 AND is_public = true AND is_deleted = false AND owner <> $3
+            RETURNING *
+            ",
+            id.0 as i64,
+            file_id.0 as i64,
+            owner.0 as i64
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        {
+            Some(r) => return Ok(Some(r.into())),
+            None => return Ok(None),
+        }
+    }
+
+    /// Sets the processing status of all files stored under the key.
+    pub async fn set_processing_status(
+        storage_key: &str,
+        processing_status: &ProcessingStatus,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        sqlx::query!(
+            "
+            UPDATE files
+            SET processing_status = $2
+            WHERE storage_key = $1
+            ",
+            storage_key,
+            processing_status.as_str()
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+
+    /// Sets the metadata of all files stored under the key.
+    pub async fn set_metadata(
+        storage_key: &str,
+        metadata: &AudioMetadata,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        sqlx::query!(
+            "
+            UPDATE files
+            SET duration_ms = $2, codec = $3, sample_rate = $4, channels = $5, bitrate = $6
+            WHERE storage_key = $1
+            ",
+            storage_key,
+            metadata.duration_ms,
+            metadata.codec,
+            metadata.sample_rate,
+            metadata.channels,
+            metadata.bitrate
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+
+    /// Sets the checksum of all files stored under the key.
+    pub async fn set_checksum(
+        storage_key: &str,
+        checksum: &str,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        sqlx::query!(
+            "
+            UPDATE files
+            SET checksum = $2
+            WHERE storage_key = $1
+            ",
+            storage_key,
+            checksum
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+
+    /// All files which should have bytes in storage, including deleted files that weren't purged yet.
+    pub async fn get_stored_files(
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<StoredFile>, KekServerError> {
+        let records = sqlx::query_as!(
+            SoundFileRow,
+            "
+            SELECT * FROM files
+            "
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records.into_iter().map(StoredFile::from).collect());
     }
 
     pub async fn toggle_visibility(
@@ -84,7 +728,8 @@ impl SoundFile {
         owner: &UserId,
         transaction: &mut Transaction<'_, Postgres>,
     ) -> Result<Self, KekServerError> {
-        let r = sqlx::query!(
+        let r = sqlx::query_as!(
+            SoundFileRow,
             "
             UPDATE files
             SET is_public = NOT is_public
@@ -96,14 +741,42 @@ impl SoundFile {
         )
         .fetch_one(&mut *transaction)
         .await?;
-        return Ok(Self {
-            id: r.id.into(),
-            owner: r.owner.map(|o| o.into()),
-            display_name: r.display_name,
-            time_added: r.time_added,
-            is_public: r.is_public.unwrap_or(false),
-            is_deleted: r.is_deleted.unwrap_or(false),
-        });
+        return Ok(r.into());
+    }
+
+    /// Updates only the given fields, `description` of `Some(None)` clears the description.
+    /// Returns `None` if the user doesn't own a non deleted file with the id.
+    pub async fn update(
+        id: &SoundFileId,
+        owner: &UserId,
+        display_name: Option<&str>,
+        description: Option<Option<&str>>,
+        is_public: Option<bool>,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Self>, KekServerError> {
+        match sqlx::query_as!(
+            SoundFileRow,
+            "
+            UPDATE files
+            SET display_name = COALESCE($3, display_name),
+            description = CASE WHEN $4 THEN $5 ELSE description END,
+            is_public = COALESCE($6, is_public)
+            WHERE id = $1 AND owner = $2 AND is_deleted = false
+            RETURNING *
+            ",
+            id.0 as i64,
+            owner.0 as i64,
+            display_name,
+            description.is_some(),
+            description.flatten(),
+            is_public
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        {
+            Some(r) => return Ok(Some(r.into())),
+            None => return Ok(None),
+        }
     }
 
     pub async fn delete(
@@ -111,10 +784,11 @@ impl SoundFile {
         owner: &UserId,
         transaction: &mut Transaction<'_, Postgres>,
     ) -> Result<Self, KekServerError> {
-        let r = sqlx::query!(
+        let r = sqlx::query_as!(
+            SoundFileRow,
             "
             UPDATE files
-            SET is_deleted = true
+            SET is_deleted = true, deleted_at = COALESCE(deleted_at, CURRENT_TIMESTAMP)
             WHERE id = $1 AND owner = $2
             RETURNING *
             ",
@@ -123,14 +797,8 @@ impl SoundFile {
         )
         .fetch_one(&mut *transaction)
         .await?;
-        return Ok(Self {
-            id: r.id.into(),
-            owner: r.owner.map(|o| o.into()),
-            display_name: r.display_name,
-            time_added: r.time_added,
-            is_public: r.is_public.unwrap_or(false),
-            is_deleted: r.is_deleted.unwrap_or(false),
-        });
+        GuildFile::delete_with_files(std::slice::from_ref(id), transaction).await?;
+        return Ok(r.into());
     }
 
     pub async fn delete_multiple(
@@ -139,10 +807,11 @@ impl SoundFile {
         transaction: &mut Transaction<'_, Postgres>,
     ) -> Result<Vec<Self>, KekServerError> {
         let ids = ids.iter().map(|id| id.0 as i64).collect::<Vec<i64>>();
-        let records = sqlx::query!(
+        let records = sqlx::query_as!(
+            SoundFileRow,
             "
             UPDATE files
-            SET is_deleted = true
+            SET is_deleted = true, deleted_at = COALESCE(deleted_at, CURRENT_TIMESTAMP)
             WHERE id = ANY($1) AND owner = $2
             RETURNING *
             ",
@@ -153,169 +822,706 @@ impl SoundFile {
         .await?;
         let rows_deleted = records
             .into_iter()
-            .map(|r| Self {
-                id: r.id.into(),
-                owner: r.owner.map(|o| o.into()),
-                display_name: r.display_name,
-                time_added: r.time_added,
-                is_public: r.is_public.unwrap_or(false),
-                is_deleted: r.is_deleted.unwrap_or(false),
-            })
+            .map(Self::from)
             .collect::<Vec<SoundFile>>();
+        let deleted_ids = rows_deleted
+            .iter()
+            .map(|f| f.id.clone())
+            .collect::<Vec<SoundFileId>>();
+        GuildFile::delete_with_files(&deleted_ids, transaction).await?;
         return Ok(rows_deleted);
     }
 
-    pub async fn get_file(
-        id: &SoundFileId,
-        owner_id: &UserId,
-        transaction: &mut Transaction<'_, Postgres>,
-    ) -> Result<Option<Self>, KekServerError> {
-        match sqlx::query!(
-            "
-            SELECT * FROM files
-            WHERE id = $1 AND is_deleted = false
-            AND (is_public = true OR owner = $2)
-            ",
-            id.0 as i64,
-            owner_id.0 as i64
-        )
-        .fetch_optional(&mut *transaction)
-        .await?
-        {
-            Some(r) => {
-                return Ok(Some(Self {
-                    id: SoundFileId(r.id as u64),
-                    owner: r.owner.map(|o| UserId(o as u64)),
-                    display_name: r.display_name,
-                    time_added: r.time_added,
-                    is_public: r.is_public.unwrap_or(false),
-                    is_deleted: r.is_deleted.unwrap_or(false),
-                }));
-            }
-            None => return Ok(None),
-        }
-    }
-
-    pub async fn get_user_files(
+    /// Users files deleted within the last `retention_days`, which can still be restored.
+    pub async fn get_user_deleted_files(
         user: &UserId,
+        retention_days: i32,
         transaction: &mut Transaction<'_, Postgres>,
-    ) -> Result<Vec<Self>, KekServerError> {
-        let records = sqlx::query!(
+    ) -> Result<Vec<DeletedSoundFile>, KekServerError> {
+        let records = sqlx::query_as!(
+            SoundFileRow,
             "
             SELECT * FROM files
-            WHERE owner = $1 AND is_deleted = false
+            WHERE owner = $1 AND is_deleted = true
+            AND deleted_at >= CURRENT_TIMESTAMP - make_interval(days => $2)
+            ORDER BY deleted_at DESC
             ",
-            user.0 as i64
+            user.0 as i64,
+            retention_days
         )
         .fetch_all(&mut *transaction)
         .await?;
         let files = records
             .into_iter()
-            .map(|r| Self {
-                id: SoundFileId(r.id as u64),
-                owner: r.owner.map(|o| UserId(o as u64)),
-                display_name: r.display_name,
-                time_added: r.time_added,
-                is_public: r.is_public.unwrap_or(false),
-                is_deleted: r.is_deleted.unwrap_or(false),
+            .filter_map(|r| {
+                let deleted_at = r.deleted_at?;
+                return Some(DeletedSoundFile {
+                    deleted_at,
+                    purge_at: deleted_at + Duration::days(retention_days.into()),
+                    sound_file: r.into(),
+                });
             })
             .collect();
         return Ok(files);
     }
 
-    pub async fn get_public_files(
-        limit: i64,
-        page: i64,
+    /// Restores users files deleted within the last `retention_days` together with their guild associations.
+    pub async fn restore_multiple(
+        ids: &[SoundFileId],
+        owner: &UserId,
+        retention_days: i32,
         transaction: &mut Transaction<'_, Postgres>,
-    ) -> Result<FilesAndCount, KekServerError> {
-        let offset = if page < 1 { 0 } else { page - 1 };
-        let records = sqlx::query!(
+    ) -> Result<Vec<Self>, KekServerError> {
+        let ids = ids.iter().map(|id| id.0 as i64).collect::<Vec<i64>>();
+        let records = sqlx::query_as!(
+            SoundFileRow,
+            "
+            UPDATE files
+            SET is_deleted = false, deleted_at = NULL
+            WHERE id = ANY($1) AND owner = $2 AND is_deleted = true
+            AND deleted_at >= CURRENT_TIMESTAMP - make_interval(days => $3)
+            RETURNING *
+            ",
+            &ids,
+            owner.0 as i64,
+            retention_days
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        let restored_files = records
+            .into_iter()
+            .map(Self::from)
+            .collect::<Vec<SoundFile>>();
+        let restored_ids = restored_files
+            .iter()
+            .map(|f| f.id.clone())
+            .collect::<Vec<SoundFileId>>();
+        GuildFile::restore_with_files(&restored_ids, transaction).await?;
+        return Ok(restored_files);
+    }
+
+    /// Files soft deleted longer than the retention period.
+    pub async fn get_expired_deleted_files(
+        retention_days: i32,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<Self>, KekServerError> {
+        let records = sqlx::query_as!(
+            SoundFileRow,
             "
             SELECT * FROM files
-            WHERE is_public = true AND is_deleted = false
-            LIMIT $1 OFFSET $2
+            WHERE is_deleted = true
+            AND deleted_at < CURRENT_TIMESTAMP - make_interval(days => $1)
             ",
-            if limit > MAX_LIMIT { MAX_LIMIT } else { limit },
-            limit * offset
+            retention_days
         )
         .fetch_all(&mut *transaction)
         .await?;
+        let files = records.into_iter().map(Self::from).collect();
+        return Ok(files);
+    }
 
-        let count = sqlx::query!(
+    /// Permanently removes soft deleted files and their guild associations.
+    /// Returns the number of removed files.
+    pub async fn purge(
+        ids: &[SoundFileId],
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<u64, KekServerError> {
+        let ids = ids.iter().map(|id| id.0 as i64).collect::<Vec<i64>>();
+        sqlx::query!(
             "
-            SELECT COUNT(*) as count FROM files
-            WHERE is_public = true AND is_deleted = false
+            DELETE FROM guild_file
+            WHERE file_id = ANY($1)
+            AND file_id IN (SELECT id FROM files WHERE is_deleted = true)
+            ",
+            &ids
+        )
+        .execute(&mut *transaction)
+        .await?;
+        let result = sqlx::query!(
+            "
+            DELETE FROM files
+            WHERE id = ANY($1) AND is_deleted = true
+            ",
+            &ids
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(result.rows_affected());
+    }
+
+    /// Keys which no file or previous version of a file is stored under anymore.
+    pub async fn get_unreferenced_storage_keys(
+        storage_keys: &[String],
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<String>, KekServerError> {
+        let records = sqlx::query!(
             "
+            SELECT DISTINCT key as \"key!\" FROM UNNEST($1::text[]) as key
+            WHERE NOT EXISTS (SELECT 1 FROM files WHERE storage_key = key)
+            AND NOT EXISTS (SELECT 1 FROM file_version WHERE storage_key = key)
+            ",
+            storage_keys
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records.into_iter().map(|r| r.key).collect());
+    }
+
+    /// Any file with the same uploaded content.
+    ///
+    /// Files which failed processing are skipped, so a new upload gets another try. Deleted files are
+    /// skipped too, their bytes can be removed by the purge while a new file would reference them.
+    pub async fn get_by_content_hash(
+        content_hash: &str,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Self>, KekServerError> {
+        match sqlx::query_as!(
+            SoundFileRow,
+            "
+            SELECT * FROM files
+            WHERE content_hash = $1 AND processing_status != 'failed' AND is_deleted = false
+            LIMIT 1
+            ",
+            content_hash
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        {
+            Some(r) => return Ok(Some(r.into())),
+            None => return Ok(None),
+        }
+    }
+
+    /// One file of every storage key still waiting for processing.
+    pub async fn get_pending_files(
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<Self>, KekServerError> {
+        let records = sqlx::query_as!(
+            SoundFileRow,
+            "
+            SELECT DISTINCT ON (storage_key) * FROM files
+            WHERE processing_status = 'pending' AND is_deleted = false
+            ORDER BY storage_key, time_added
+            "
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records.into_iter().map(Self::from).collect());
+    }
+
+    pub async fn get_user_storage_usage(
+        user_id: &UserId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<StorageUsage, KekServerError> {
+        let r = sqlx::query!(
+            "
+            SELECT
+                (COALESCE(SUM(files.size), 0) + COALESCE(SUM(file_version.size), 0))::BIGINT as \"bytes!\",
+                COUNT(*) as \"files!\"
+            FROM files
+            LEFT JOIN file_version ON file_version.file_id = files.id
+            WHERE owner = $1
+            ",
+            user_id.0 as i64
         )
         .fetch_one(&mut *transaction)
+        .await?;
+        return Ok(StorageUsage {
+            bytes: r.bytes,
+            files: r.files,
+        });
+    }
+
+    pub async fn get_file(
+        id: &SoundFileId,
+        owner_id: &UserId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Self>, KekServerError> {
+        match sqlx::query_as!(
+            SoundFileRow,
+            "
+            SELECT * FROM files
+            WHERE id = $1 AND is_deleted = false
+            AND (is_public = true OR owner = $2)
+            ",
+            id.0 as i64,
+            owner_id.0 as i64
+        )
+        .fetch_optional(&mut *transaction)
         .await?
-        .count
-        .unwrap_or(0);
+        {
+            Some(r) => return Ok(Some(r.into())),
+            None => return Ok(None),
+        }
+    }
 
-        let files = records
+    /// Locks the file until the transaction ends, only non deleted files owned by the user are returned.
+    pub async fn get_user_file_for_update(
+        id: &SoundFileId,
+        owner: &UserId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Self>, KekServerError> {
+        match sqlx::query_as!(
+            SoundFileRow,
+            "
+            SELECT * FROM files
+            WHERE id = $1 AND owner = $2 AND is_deleted = false
+            FOR UPDATE
+            ",
+            id.0 as i64,
+            owner.0 as i64
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        {
+            Some(r) => return Ok(Some(r.into())),
+            None => return Ok(None),
+        }
+    }
+
+    pub async fn get_user_files(
+        user: &UserId,
+        tag_filter: &TagFilter,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<Self>, KekServerError> {
+        let records = sqlx::query_as!(
+            SoundFileRow,
+            "
+            SELECT * FROM files
+            WHERE owner = $1 AND is_deleted = false
+            AND (
+                SELECT COUNT(*) FROM file_tag
+                INNER JOIN tag ON tag.id = file_tag.tag_id
+                WHERE file_tag.file_id = files.id AND tag.name = ANY($2)
+            ) >= $3
+            ",
+            user.0 as i64,
+            &tag_filter.tags,
+            tag_filter.required_matches()
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        let files = records.into_iter().map(Self::from).collect();
+        return Ok(files);
+    }
+
+    pub async fn increment_play_count(
+        id: &SoundFileId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        sqlx::query!(
+            "
+            UPDATE files
+            SET play_count = play_count + 1
+            WHERE id = $1
+            ",
+            id.0 as i64
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+
+    /// Page of files in the scope, the cursor's sort is used instead of `sort` when a cursor is sent.
+    /// Ties are ordered by file id in the direction of the sort.
+    pub async fn get_files_page(
+        scope: &FileScope,
+        sort: FileSort,
+        cursor: Option<&FileCursor>,
+        limit: i64,
+        tag_filter: &TagFilter,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<FilesPage, KekServerError> {
+        let limit = if (1..=MAX_LIMIT).contains(&limit) {
+            limit
+        } else {
+            MAX_LIMIT
+        };
+        let sort = cursor.map(|c| c.sort).unwrap_or(sort);
+        let backward = cursor.map(|c| c.backward).unwrap_or(false);
+
+        // Going backward reads the listing in reverse order from the cursor.
+        // Every order has its own query so the keyset condition can use the sort's index.
+        let records = match (sort, sort.is_descending() != backward) {
+            (FileSort::Newest | FileSort::Oldest, true) => {
+                sqlx::query_as!(
+                    SoundFileRow,
+                    "
+                    SELECT * FROM files
+                    WHERE is_deleted = false
+                    AND (
+                        ($1::BIGINT IS NULL AND $2::BIGINT IS NULL AND is_public = true)
+                        OR owner = $1
+                        OR EXISTS (
+                            SELECT 1 FROM guild_file
+                            WHERE guild_file.file_id = files.id AND guild_file.guild_id = $2
+                            AND guild_file.is_deleted = false
+                        )
+                    )
+                    AND (
+                        SELECT COUNT(*) FROM file_tag
+                        INNER JOIN tag ON tag.id = file_tag.tag_id
+                        WHERE file_tag.file_id = files.id AND tag.name = ANY($3)
+                    ) >= $4
+                    AND ($6::BIGINT IS NULL OR (time_added, id) < ($5::TIMESTAMP, $6))
+                    ORDER BY time_added DESC, id DESC
+                    LIMIT $7
+                    ",
+                    scope.owner(),
+                    scope.guild(),
+                    &tag_filter.tags,
+                    tag_filter.required_matches(),
+                    cursor.map(|c| c.time_added),
+                    cursor.map(|c| c.id),
+                    limit + 1
+                )
+                .fetch_all(&mut *transaction)
+                .await?
+            }
+            (FileSort::Newest | FileSort::Oldest, false) => {
+                sqlx::query_as!(
+                    SoundFileRow,
+                    "
+                    SELECT * FROM files
+                    WHERE is_deleted = false
+                    AND (
+                        ($1::BIGINT IS NULL AND $2::BIGINT IS NULL AND is_public = true)
+                        OR owner = $1
+                        OR EXISTS (
+                            SELECT 1 FROM guild_file
+                            WHERE guild_file.file_id = files.id AND guild_file.guild_id = $2
+                            AND guild_file.is_deleted = false
+                        )
+                    )
+                    AND (
+                        SELECT COUNT(*) FROM file_tag
+                        INNER JOIN tag ON tag.id = file_tag.tag_id
+                        WHERE file_tag.file_id = files.id AND tag.name = ANY($3)
+                    ) >= $4
+                    AND ($6::BIGINT IS NULL OR (time_added, id) > ($5::TIMESTAMP, $6))
+                    ORDER BY time_added, id
+                    LIMIT $7
+                    ",
+                    scope.owner(),
+                    scope.guild(),
+                    &tag_filter.tags,
+                    tag_filter.required_matches(),
+                    cursor.map(|c| c.time_added),
+                    cursor.map(|c| c.id),
+                    limit + 1
+                )
+                .fetch_all(&mut *transaction)
+                .await?
+            }
+            (FileSort::Name, true) => {
+                sqlx::query_as!(
+                    SoundFileRow,
+                    "
+                    SELECT * FROM files
+                    WHERE is_deleted = false
+                    AND (
+                        ($1::BIGINT IS NULL AND $2::BIGINT IS NULL AND is_public = true)
+                        OR owner = $1
+                        OR EXISTS (
+                            SELECT 1 FROM guild_file
+                            WHERE guild_file.file_id = files.id AND guild_file.guild_id = $2
+                            AND guild_file.is_deleted = false
+                        )
+                    )
+                    AND (
+                        SELECT COUNT(*) FROM file_tag
+                        INNER JOIN tag ON tag.id = file_tag.tag_id
+                        WHERE file_tag.file_id = files.id AND tag.name = ANY($3)
+                    ) >= $4
+                    AND ($6::BIGINT IS NULL OR (LOWER(COALESCE(display_name, '')), id) < (LOWER($5::VARCHAR), $6))
+                    ORDER BY LOWER(COALESCE(display_name, '')) DESC, id DESC
+                    LIMIT $7
+                    ",
+                    scope.owner(),
+                    scope.guild(),
+                    &tag_filter.tags,
+                    tag_filter.required_matches(),
+                    cursor.map(|c| c.display_name.clone()),
+                    cursor.map(|c| c.id),
+                    limit + 1
+                )
+                .fetch_all(&mut *transaction)
+                .await?
+            }
+            (FileSort::Name, false) => {
+                sqlx::query_as!(
+                    SoundFileRow,
+                    "
+                    SELECT * FROM files
+                    WHERE is_deleted = false
+                    AND (
+                        ($1::BIGINT IS NULL AND $2::BIGINT IS NULL AND is_public = true)
+                        OR owner = $1
+                        OR EXISTS (
+                            SELECT 1 FROM guild_file
+                            WHERE guild_file.file_id = files.id AND guild_file.guild_id = $2
+                            AND guild_file.is_deleted = false
+                        )
+                    )
+                    AND (
+                        SELECT COUNT(*) FROM file_tag
+                        INNER JOIN tag ON tag.id = file_tag.tag_id
+                        WHERE file_tag.file_id = files.id AND tag.name = ANY($3)
+                    ) >= $4
+                    AND ($6::BIGINT IS NULL OR (LOWER(COALESCE(display_name, '')), id) > (LOWER($5::VARCHAR), $6))
+                    ORDER BY LOWER(COALESCE(display_name, '')), id
+                    LIMIT $7
+                    ",
+                    scope.owner(),
+                    scope.guild(),
+                    &tag_filter.tags,
+                    tag_filter.required_matches(),
+                    cursor.map(|c| c.display_name.clone()),
+                    cursor.map(|c| c.id),
+                    limit + 1
+                )
+                .fetch_all(&mut *transaction)
+                .await?
+            }
+            (FileSort::MostPlayed, true) => {
+                sqlx::query_as!(
+                    SoundFileRow,
+                    "
+                    SELECT * FROM files
+                    WHERE is_deleted = false
+                    AND (
+                        ($1::BIGINT IS NULL AND $2::BIGINT IS NULL AND is_public = true)
+                        OR owner = $1
+                        OR EXISTS (
+                            SELECT 1 FROM guild_file
+                            WHERE guild_file.file_id = files.id AND guild_file.guild_id = $2
+                            AND guild_file.is_deleted = false
+                        )
+                    )
+                    AND (
+                        SELECT COUNT(*) FROM file_tag
+                        INNER JOIN tag ON tag.id = file_tag.tag_id
+                        WHERE file_tag.file_id = files.id AND tag.name = ANY($3)
+                    ) >= $4
+                    AND ($6::BIGINT IS NULL OR (play_count, id) < ($5::BIGINT, $6))
+                    ORDER BY play_count DESC, id DESC
+                    LIMIT $7
+                    ",
+                    scope.owner(),
+                    scope.guild(),
+                    &tag_filter.tags,
+                    tag_filter.required_matches(),
+                    cursor.map(|c| c.play_count),
+                    cursor.map(|c| c.id),
+                    limit + 1
+                )
+                .fetch_all(&mut *transaction)
+                .await?
+            }
+            (FileSort::MostPlayed, false) => {
+                sqlx::query_as!(
+                    SoundFileRow,
+                    "
+                    SELECT * FROM files
+                    WHERE is_deleted = false
+                    AND (
+                        ($1::BIGINT IS NULL AND $2::BIGINT IS NULL AND is_public = true)
+                        OR owner = $1
+                        OR EXISTS (
+                            SELECT 1 FROM guild_file
+                            WHERE guild_file.file_id = files.id AND guild_file.guild_id = $2
+                            AND guild_file.is_deleted = false
+                        )
+                    )
+                    AND (
+                        SELECT COUNT(*) FROM file_tag
+                        INNER JOIN tag ON tag.id = file_tag.tag_id
+                        WHERE file_tag.file_id = files.id AND tag.name = ANY($3)
+                    ) >= $4
+                    AND ($6::BIGINT IS NULL OR (play_count, id) > ($5::BIGINT, $6))
+                    ORDER BY play_count, id
+                    LIMIT $7
+                    ",
+                    scope.owner(),
+                    scope.guild(),
+                    &tag_filter.tags,
+                    tag_filter.required_matches(),
+                    cursor.map(|c| c.play_count),
+                    cursor.map(|c| c.id),
+                    limit + 1
+                )
+                .fetch_all(&mut *transaction)
+                .await?
+            }
+        };
+
+        let has_more = records.len() as i64 > limit;
+        let mut files = records
             .into_iter()
-            .map(|r| Self {
-                id: SoundFileId(r.id as u64),
-                owner: r.owner.map(|o| UserId(o as u64)),
-                display_name: r.display_name,
-                time_added: r.time_added,
-                is_public: r.is_public.unwrap_or(true),
-                is_deleted: r.is_deleted.unwrap_or(false),
-            })
-            .collect();
-        return Ok(FilesAndCount { count, files, max: MAX_LIMIT });
+            .take(limit as usize)
+            .map(Self::from)
+            .collect::<Vec<Self>>();
+        if backward {
+            files.reverse();
+        }
+        let (has_next, has_prev) = if backward {
+            (cursor.is_some(), has_more)
+        } else {
+            (has_more, cursor.is_some())
+        };
+        let next = match files.last() {
+            Some(file) if has_next => Some(FileCursor::new(sort, file, false).encode()?),
+            _ => None,
+        };
+        let prev = match files.first() {
+            Some(file) if has_prev => Some(FileCursor::new(sort, file, true).encode()?),
+            _ => None,
+        };
+        return Ok(FilesPage { files, next, prev });
     }
 
-    pub async fn get_public_files_search(
+    /// Files whose display name contains the search or is similar to it,
+    /// files containing the search are ranked first, then by word similarity.
+    /// When no file contains the search, similar display names are returned as suggestions.
+    pub async fn search(
+        scope: &FileScope,
         limit: i64,
         page: i64,
-        search: String,
+        search: &str,
+        tag_filter: &TagFilter,
         transaction: &mut Transaction<'_, Postgres>,
     ) -> Result<FilesAndCount, KekServerError> {
+        let limit = if (1..=MAX_LIMIT).contains(&limit) {
+            limit
+        } else {
+            MAX_LIMIT
+        };
         let offset = if page < 1 { 0 } else { page - 1 };
+        let like_search = format!("%{}%", postgres_like_escape(search.to_string()));
 
-        let mut search = postgres_like_escape(search);
-        search.push('%');
-        search.insert(0, '%');
+        // `<%` uses the trigram index with the threshold set for the transaction
+        sqlx::query!(
+            "SELECT set_config('pg_trgm.word_similarity_threshold', $1, true)",
+            SEARCH_SIMILARITY_THRESHOLD.to_string()
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
 
-        let records = sqlx::query!(
+        let records = sqlx::query_as!(
+            SoundFileRow,
             "
             SELECT * FROM files
-            WHERE is_public = true AND is_deleted = false
-            AND display_name ILIKE $3
+            WHERE is_deleted = false
+            AND (
+                ($5::BIGINT IS NULL AND $6::BIGINT IS NULL AND is_public = true)
+                OR owner = $5
+                OR EXISTS (
+                    SELECT 1 FROM guild_file
+                    WHERE guild_file.file_id = files.id AND guild_file.guild_id = $6
+                    AND guild_file.is_deleted = false
+                )
+            )
+            AND (display_name ILIKE $3 OR $4 <% display_name)
+            AND (
+                SELECT COUNT(*) FROM file_tag
+                INNER JOIN tag ON tag.id = file_tag.tag_id
+                WHERE file_tag.file_id = files.id AND tag.name = ANY($7)
+            ) >= $8
+            ORDER BY
+                display_name ILIKE $3 DESC,
+                word_similarity($4, display_name) DESC,
+                similarity($4, display_name) DESC,
+                id
             LIMIT $1 OFFSET $2
             ",
-            if limit > MAX_LIMIT { MAX_LIMIT } else { limit },
+            limit,
             limit * offset,
-            &search
+            &like_search,
+            search,
+            scope.owner(),
+            scope.guild(),
+            &tag_filter.tags,
+            tag_filter.required_matches()
         )
         .fetch_all(&mut *transaction)
         .await?;
-        let count = sqlx::query!(
+        let counts = sqlx::query!(
             "
-            SELECT COUNT(*) as count FROM files
-            WHERE is_public = true AND is_deleted = false
-            AND display_name ILIKE $1
+            SELECT
+                COUNT(*) as \"count!\",
+                COUNT(*) FILTER (WHERE display_name ILIKE $1) as \"exact_count!\"
+            FROM files
+            WHERE is_deleted = false
+            AND (
+                ($3::BIGINT IS NULL AND $4::BIGINT IS NULL AND is_public = true)
+                OR owner = $3
+                OR EXISTS (
+                    SELECT 1 FROM guild_file
+                    WHERE guild_file.file_id = files.id AND guild_file.guild_id = $4
+                    AND guild_file.is_deleted = false
+                )
+            )
+            AND (display_name ILIKE $1 OR $2 <% display_name)
+            AND (
+                SELECT COUNT(*) FROM file_tag
+                INNER JOIN tag ON tag.id = file_tag.tag_id
+                WHERE file_tag.file_id = files.id AND tag.name = ANY($5)
+            ) >= $6
             ",
-            &search
+            &like_search,
+            search,
+            scope.owner(),
+            scope.guild(),
+            &tag_filter.tags,
+            tag_filter.required_matches()
         )
         .fetch_one(&mut *transaction)
-        .await?
-        .count
-        .unwrap_or(0);
+        .await?;
 
-        let files = records
+        let mut suggestions = Vec::new();
+        if counts.exact_count == 0 {
+            // Suggestions ignore the tag filter and accept less similar names
+            suggestions = sqlx::query!(
+                "
+                SELECT display_name as \"display_name!\" FROM files
+                WHERE is_deleted = false AND display_name IS NOT NULL
+                AND (
+                    ($3::BIGINT IS NULL AND $4::BIGINT IS NULL AND is_public = true)
+                    OR owner = $3
+                    OR EXISTS (
+                        SELECT 1 FROM guild_file
+                        WHERE guild_file.file_id = files.id AND guild_file.guild_id = $4
+                        AND guild_file.is_deleted = false
+                    )
+                )
+                AND word_similarity($1, display_name) >= $2
+                GROUP BY display_name
+                ORDER BY word_similarity($1, display_name) DESC, similarity($1, display_name) DESC
+                LIMIT $5
+                ",
+                search,
+                *SEARCH_SIMILARITY_THRESHOLD / 2.0,
+                scope.owner(),
+                scope.guild(),
+                MAX_SUGGESTIONS
+            )
+            .fetch_all(&mut *transaction)
+            .await?
             .into_iter()
-            .map(|r| Self {
-                id: SoundFileId(r.id as u64),
-                owner: r.owner.map(|o| UserId(o as u64)),
-                display_name: r.display_name,
-                time_added: r.time_added,
-                is_public: r.is_public.unwrap_or(true),
-                is_deleted: r.is_deleted.unwrap_or(false),
-            })
+            .map(|r| r.display_name)
             .collect();
-        return Ok(FilesAndCount { count, files, max: MAX_LIMIT });
+        }
+
+        let files = records.into_iter().map(Self::from).collect();
+        return Ok(FilesAndCount {
+            count: Some(counts.count),
+            files,
+            max: MAX_LIMIT,
+            tag_counts: Vec::new(),
+            suggestions,
+            next: None,
+            prev: None,
+        });
     }
 }
 
@@ -326,14 +1532,30 @@ mod tests {
 
     use crate::{
         database::tests_db_helper::db_connection,
-        models::ids::SoundFileId,
+        models::{guild_file::GuildFile, ids::SoundFileId, tag::TagFilter},
         utils::test_utils::{
-            insert_file_test_util, insert_random_file_test_util, insert_user_test_util,
-            PublicDeleted,
+            insert_file_test_util, insert_guild_file_test_util, insert_guild_test_util,
+            insert_random_file_test_util, insert_user_test_util, PublicDeleted,
         },
     };
 
-    use super::{SoundFile, MAX_LIMIT};
+    use super::{
+        AudioMetadata, FileCursor, FileScope, FileSort, ProcessingStatus, SoundFile, StorageUsage,
+        MAX_DISPLAY_NAME_LENGTH, MAX_LIMIT,
+    };
+
+    #[test]
+    fn test_validate_display_name() {
+        let max_length_name = "a".repeat(MAX_DISPLAY_NAME_LENGTH);
+
+        assert_eq!(
+            SoundFile::validate_display_name("  Airhorn ").unwrap(),
+            "Airhorn"
+        );
+        assert!(SoundFile::validate_display_name(&max_length_name).is_ok());
+        assert!(SoundFile::validate_display_name(&format!("{}a", max_length_name)).is_err());
+        assert!(SoundFile::validate_display_name("   ").is_err());
+    }
 
     #[actix_web::test]
     async fn test_insert_sound_file() {
@@ -352,30 +1574,386 @@ mod tests {
         let sound_file = generated_sound_file.insert(&mut transaction).await.unwrap();
         transaction.commit().await.unwrap();
 
-        assert_eq!(sound_file.id, generated_sound_file.id);
-        assert_eq!(sound_file.display_name, generated_sound_file.display_name);
-        assert_eq!(sound_file.owner, generated_sound_file.owner);
-        assert_eq!(sound_file.is_deleted, false);
-        assert_eq!(sound_file.is_public, is_public);
+        assert_eq!(sound_file.id, generated_sound_file.id);
+        assert_eq!(sound_file.display_name, generated_sound_file.display_name);
+        assert_eq!(sound_file.owner, generated_sound_file.owner);
+        assert_eq!(sound_file.is_deleted, false);
+        assert_eq!(sound_file.is_public, is_public);
+        assert_eq!(sound_file.processing_status, ProcessingStatus::Pending);
+    }
+
+    #[actix_web::test]
+    async fn test_get_pending_files() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let pending_file = insert_random_file_test_util(&mut transaction).await;
+        let ready_file = insert_random_file_test_util(&mut transaction).await;
+        SoundFile::set_processing_status(
+            &pending_file.storage_key,
+            &ProcessingStatus::Pending,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        SoundFile::set_processing_status(
+            &ready_file.storage_key,
+            &ProcessingStatus::Ready,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let pending_files = SoundFile::get_pending_files(&mut transaction)
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert!(pending_files.iter().any(|f| f.id == pending_file.id));
+        assert!(!pending_files.iter().any(|f| f.id == ready_file.id));
+    }
+
+    #[actix_web::test]
+    async fn test_set_processing_status() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let sound_file = insert_random_file_test_util(&mut transaction).await;
+        SoundFile::set_processing_status(
+            &sound_file.storage_key,
+            &ProcessingStatus::Ready,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let gotten_file = SoundFile::get_file(
+            &sound_file.id,
+            &sound_file.owner.clone().unwrap(),
+            &mut transaction,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(sound_file.processing_status, ProcessingStatus::Unprocessed);
+        assert_eq!(gotten_file.processing_status, ProcessingStatus::Ready);
+    }
+
+    #[actix_web::test]
+    async fn test_set_metadata() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let sound_file = insert_random_file_test_util(&mut transaction).await;
+        let metadata = AudioMetadata {
+            duration_ms: Some(1500),
+            codec: Some("opus".to_string()),
+            sample_rate: Some(48000),
+            channels: Some(2),
+            bitrate: Some(96000),
+        };
+        SoundFile::set_metadata(&sound_file.storage_key, &metadata, &mut transaction)
+            .await
+            .unwrap();
+        let gotten_file = SoundFile::get_file(
+            &sound_file.id,
+            &sound_file.owner.clone().unwrap(),
+            &mut transaction,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(sound_file.metadata, AudioMetadata::default());
+        assert_eq!(gotten_file.metadata, metadata);
+    }
+
+    #[actix_web::test]
+    async fn test_set_checksum() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let sound_file = insert_random_file_test_util(&mut transaction).await;
+        let checksum = "a".repeat(64);
+        SoundFile::set_checksum(&sound_file.storage_key, &checksum, &mut transaction)
+            .await
+            .unwrap();
+        let stored_files = SoundFile::get_stored_files(&mut transaction).await.unwrap();
+        transaction.commit().await.unwrap();
+
+        let stored_file = stored_files
+            .into_iter()
+            .find(|f| f.id == sound_file.id)
+            .unwrap();
+        assert_eq!(stored_file.checksum, Some(checksum));
+        assert!(!stored_file.is_deleted);
+    }
+
+    #[actix_web::test]
+    async fn test_toggle_visibility() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let sound_file = insert_random_file_test_util(&mut transaction).await;
+        let is_public_before_toggle = sound_file.is_public;
+        let sound_file = SoundFile::toggle_visibility(
+            &sound_file.id,
+            &sound_file.owner.unwrap(),
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_ne!(sound_file.is_public, is_public_before_toggle);
+    }
+
+    #[actix_web::test]
+    async fn test_get_files_page() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let owner = insert_user_test_util(&mut transaction).await;
+        let mut files = vec![];
+        for display_name in ["b", "C", "a"] {
+            let file = insert_file_test_util(&owner.id, None, &mut transaction).await;
+            let file = SoundFile::update(
+                &file.id,
+                &owner.id,
+                Some(display_name),
+                None,
+                None,
+                &mut transaction,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+            files.push(file);
+        }
+        SoundFile::increment_play_count(&files[1].id, &mut transaction)
+            .await
+            .unwrap();
+
+        let scope = FileScope::User(owner.id.clone());
+        let tag_filter = TagFilter::default();
+        let first_page = SoundFile::get_files_page(
+            &scope,
+            FileSort::Name,
+            None,
+            2,
+            &tag_filter,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let next_cursor = FileCursor::decode(first_page.next.as_ref().unwrap()).unwrap();
+        let last_page = SoundFile::get_files_page(
+            &scope,
+            FileSort::Newest,
+            Some(&next_cursor),
+            2,
+            &tag_filter,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let prev_cursor = FileCursor::decode(last_page.prev.as_ref().unwrap()).unwrap();
+        let prev_page = SoundFile::get_files_page(
+            &scope,
+            FileSort::Name,
+            Some(&prev_cursor),
+            2,
+            &tag_filter,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let most_played = SoundFile::get_files_page(
+            &scope,
+            FileSort::MostPlayed,
+            None,
+            1,
+            &tag_filter,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        transaction.commit().await.unwrap();
+
+        let first_ids = first_page
+            .files
+            .iter()
+            .map(|f| f.id.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(first_ids, vec![files[2].id.clone(), files[0].id.clone()]);
+        assert_eq!(first_page.prev, None);
+        assert_eq!(last_page.files.len(), 1);
+        assert_eq!(last_page.files[0].id, files[1].id);
+        assert_eq!(last_page.next, None);
+        assert_eq!(prev_page.files, first_page.files);
+        assert_eq!(prev_page.prev, None);
+        assert_eq!(most_played.files[0].id, files[1].id);
+        assert_eq!(most_played.files[0].play_count, 1);
+        assert!(FileCursor::decode("not a cursor").is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_search_files() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let owner = insert_user_test_util(&mut transaction).await;
+        let guild = insert_guild_test_util(&mut transaction).await;
+        let mut files = vec![];
+        for display_name in ["Sad trombone", "Airhorn", "Loud airhorn remix"] {
+            let file = insert_file_test_util(&owner.id, None, &mut transaction).await;
+            let file = SoundFile::update(
+                &file.id,
+                &owner.id,
+                Some(display_name),
+                None,
+                None,
+                &mut transaction,
+            )
+            .await
+            .unwrap()
+            .unwrap();
+            files.push(file);
+        }
+        insert_guild_file_test_util(&guild.id, files[1].clone(), &mut transaction).await;
+
+        let user_scope = FileScope::User(owner.id.clone());
+        let tag_filter = TagFilter::default();
+        let exact_results = SoundFile::search(
+            &user_scope,
+            MAX_LIMIT,
+            1,
+            "airhorn",
+            &tag_filter,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let typo_results = SoundFile::search(
+            &user_scope,
+            MAX_LIMIT,
+            1,
+            "airhron",
+            &tag_filter,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let guild_results = SoundFile::search(
+            &FileScope::Guild(guild.id.clone()),
+            MAX_LIMIT,
+            1,
+            "airhorn",
+            &tag_filter,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let negative_limit_results =
+            SoundFile::search(&user_scope, -1, 1, "airhorn", &tag_filter, &mut transaction)
+                .await
+                .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(exact_results.count, Some(2));
+        assert_eq!(negative_limit_results.files.len(), 2);
+        assert_eq!(exact_results.files[0].id, files[1].id);
+        assert!(exact_results.suggestions.is_empty());
+        assert!(!typo_results.files.iter().any(|f| f.id == files[0].id));
+        assert_eq!(typo_results.suggestions[0], "Airhorn");
+        assert_eq!(guild_results.count, Some(1));
+        assert_eq!(guild_results.files[0].id, files[1].id);
     }
 
     #[actix_web::test]
-    async fn test_toggle_visibility() {
+    async fn test_update_sound_file() {
         let mut connection = db_connection().await;
         let mut transaction = connection.begin().await.unwrap();
 
         let sound_file = insert_random_file_test_util(&mut transaction).await;
-        let is_public_before_toggle = sound_file.is_public;
-        let sound_file = SoundFile::toggle_visibility(
+        let owner = sound_file.owner.clone().unwrap();
+        let renamed_file = SoundFile::update(
             &sound_file.id,
-            &sound_file.owner.unwrap(),
+            &owner,
+            Some("Renamed file"),
+            Some(Some("Test description")),
+            Some(true),
+            &mut transaction,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        let cleared_file = SoundFile::update(
+            &sound_file.id,
+            &owner,
+            None,
+            Some(None),
+            None,
+            &mut transaction,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        let other_user = insert_user_test_util(&mut transaction).await;
+        let not_owned_file = SoundFile::update(
+            &sound_file.id,
+            &other_user.id,
+            Some("Stolen file"),
+            None,
+            None,
             &mut transaction,
         )
         .await
         .unwrap();
         transaction.commit().await.unwrap();
 
-        assert_ne!(sound_file.is_public, is_public_before_toggle);
+        assert_eq!(renamed_file.display_name, Some("Renamed file".to_string()));
+        assert_eq!(
+            renamed_file.description,
+            Some("Test description".to_string())
+        );
+        assert!(renamed_file.is_public);
+        assert_eq!(cleared_file.display_name, renamed_file.display_name);
+        assert_eq!(cleared_file.description, None);
+        assert!(cleared_file.is_public);
+        assert_eq!(not_owned_file, None);
+    }
+
+    #[test]
+    fn test_validate_trim() {
+        assert!(SoundFile::validate_trim(Some(500), Some(1500), Some(2000)).is_ok());
+        assert!(SoundFile::validate_trim(Some(500), None, None).is_ok());
+        assert!(SoundFile::validate_trim(None, None, Some(2000)).is_ok());
+        assert!(SoundFile::validate_trim(Some(-1), None, Some(2000)).is_err());
+        assert!(SoundFile::validate_trim(Some(1500), Some(1500), Some(2000)).is_err());
+        assert!(SoundFile::validate_trim(Some(2000), None, Some(2000)).is_err());
+        assert!(SoundFile::validate_trim(None, Some(2500), Some(2000)).is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_set_trim() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let sound_file = insert_random_file_test_util(&mut transaction).await;
+        let owner = sound_file.owner.clone().unwrap();
+        SoundFile::set_trim(&sound_file.id, Some(500), Some(1500), &mut transaction)
+            .await
+            .unwrap();
+        let trimmed_file = SoundFile::get_file(&sound_file.id, &owner, &mut transaction)
+            .await
+            .unwrap()
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(trimmed_file.start_ms, Some(500));
+        assert_eq!(trimmed_file.end_ms, Some(1500));
     }
 
     #[actix_web::test]
@@ -421,6 +1999,327 @@ mod tests {
         }
     }
 
+    #[actix_web::test]
+    async fn test_restore_deleted_files() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let owner = insert_user_test_util(&mut transaction).await;
+        let sound_file = insert_file_test_util(&owner.id, None, &mut transaction).await;
+        let expired_file = insert_file_test_util(&owner.id, None, &mut transaction).await;
+        let enabled_guild = insert_guild_test_util(&mut transaction).await;
+        let disabled_guild = insert_guild_test_util(&mut transaction).await;
+        insert_guild_file_test_util(&enabled_guild.id, sound_file.clone(), &mut transaction).await;
+        insert_guild_file_test_util(&disabled_guild.id, sound_file.clone(), &mut transaction).await;
+        GuildFile::delete_guild_file(&disabled_guild.id, &sound_file.id, &mut transaction)
+            .await
+            .unwrap();
+
+        SoundFile::delete_multiple(
+            &[sound_file.id.clone(), expired_file.id.clone()],
+            &owner.id,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        sqlx::query!(
+            "
+            UPDATE files
+            SET deleted_at = CURRENT_TIMESTAMP - INTERVAL '40 days'
+            WHERE id = $1
+            ",
+            expired_file.id.0 as i64
+        )
+        .execute(&mut transaction)
+        .await
+        .unwrap();
+        let guild_file_after_delete =
+            GuildFile::get_guild_file(&enabled_guild.id, &sound_file.id, &mut transaction)
+                .await
+                .unwrap();
+
+        let deleted_files = SoundFile::get_user_deleted_files(&owner.id, 30, &mut transaction)
+            .await
+            .unwrap();
+        let restored_files = SoundFile::restore_multiple(
+            &[sound_file.id.clone(), expired_file.id.clone()],
+            &owner.id,
+            30,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let enabled_guild_file =
+            GuildFile::get_guild_file(&enabled_guild.id, &sound_file.id, &mut transaction)
+                .await
+                .unwrap();
+        let disabled_guild_file =
+            GuildFile::get_guild_file(&disabled_guild.id, &sound_file.id, &mut transaction)
+                .await
+                .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert!(guild_file_after_delete.is_none());
+        assert_eq!(deleted_files.len(), 1);
+        assert_eq!(deleted_files[0].sound_file.id, sound_file.id);
+        assert_eq!(restored_files.len(), 1);
+        assert_eq!(restored_files[0].id, sound_file.id);
+        assert!(!restored_files[0].is_deleted);
+        assert!(enabled_guild_file.is_some());
+        assert!(disabled_guild_file.is_none());
+    }
+
+    #[actix_web::test]
+    async fn test_purge_expired_deleted_files() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let sound_file = insert_random_file_test_util(&mut transaction).await;
+        let guild = insert_guild_test_util(&mut transaction).await;
+        insert_guild_file_test_util(&guild.id, sound_file.clone(), &mut transaction).await;
+        SoundFile::delete(
+            &sound_file.id,
+            &sound_file.owner.clone().unwrap(),
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        sqlx::query!(
+            "
+            UPDATE files
+            SET deleted_at = CURRENT_TIMESTAMP - INTERVAL '40 days'
+            WHERE id = $1
+            ",
+            sound_file.id.0 as i64
+        )
+        .execute(&mut transaction)
+        .await
+        .unwrap();
+
+        let expired_files = SoundFile::get_expired_deleted_files(30, &mut transaction)
+            .await
+            .unwrap();
+        let not_expired_files = SoundFile::get_expired_deleted_files(60, &mut transaction)
+            .await
+            .unwrap();
+        let purged = SoundFile::purge(std::slice::from_ref(&sound_file.id), &mut transaction)
+            .await
+            .unwrap();
+        let guild_file_count = sqlx::query!(
+            "
+            SELECT COUNT(*) as count FROM guild_file
+            WHERE file_id = $1
+            ",
+            sound_file.id.0 as i64
+        )
+        .fetch_one(&mut transaction)
+        .await
+        .unwrap()
+        .count;
+        let file_count = sqlx::query!(
+            "
+            SELECT COUNT(*) as count FROM files
+            WHERE id = $1
+            ",
+            sound_file.id.0 as i64
+        )
+        .fetch_one(&mut transaction)
+        .await
+        .unwrap()
+        .count;
+        transaction.commit().await.unwrap();
+
+        assert!(expired_files.iter().any(|f| f.id == sound_file.id));
+        assert!(!not_expired_files.iter().any(|f| f.id == sound_file.id));
+        assert_eq!(purged, 1);
+        assert_eq!(guild_file_count, Some(0));
+        assert_eq!(file_count, Some(0));
+    }
+
+    #[actix_web::test]
+    async fn test_shared_storage_key() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let content_hash = Uuid::new_v4().to_string();
+        let mut sound_file = SoundFile::new(
+            SoundFileId(Uuid::new_v4().as_u128() as u64),
+            "Original".to_string(),
+            Some(insert_user_test_util(&mut transaction).await.id),
+            false,
+        );
+        sound_file.content_hash = Some(content_hash.clone());
+        sound_file.insert(&mut transaction).await.unwrap();
+        SoundFile::set_processing_status(
+            &sound_file.storage_key,
+            &ProcessingStatus::Ready,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+
+        let existing_file = SoundFile::get_by_content_hash(&content_hash, &mut transaction)
+            .await
+            .unwrap()
+            .unwrap();
+        let mut duplicate_file = SoundFile::new(
+            SoundFileId(Uuid::new_v4().as_u128() as u64),
+            "Duplicate".to_string(),
+            Some(insert_user_test_util(&mut transaction).await.id),
+            false,
+        );
+        duplicate_file.storage_key = existing_file.storage_key.clone();
+        duplicate_file.content_hash = Some(content_hash.clone());
+        let duplicate_file = duplicate_file.insert(&mut transaction).await.unwrap();
+
+        let storage_keys = vec![sound_file.storage_key.clone()];
+        SoundFile::delete(
+            &sound_file.id,
+            &sound_file.owner.clone().unwrap(),
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        SoundFile::purge(std::slice::from_ref(&sound_file.id), &mut transaction)
+            .await
+            .unwrap();
+        let referenced_keys =
+            SoundFile::get_unreferenced_storage_keys(&storage_keys, &mut transaction)
+                .await
+                .unwrap();
+        SoundFile::delete(
+            &duplicate_file.id,
+            &duplicate_file.owner.clone().unwrap(),
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let deleted_existing_file = SoundFile::get_by_content_hash(&content_hash, &mut transaction)
+            .await
+            .unwrap();
+        SoundFile::purge(std::slice::from_ref(&duplicate_file.id), &mut transaction)
+            .await
+            .unwrap();
+        let unreferenced_keys =
+            SoundFile::get_unreferenced_storage_keys(&storage_keys, &mut transaction)
+                .await
+                .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(existing_file.id, sound_file.id);
+        assert_eq!(duplicate_file.storage_key, sound_file.storage_key);
+        assert_eq!(duplicate_file.processing_status, ProcessingStatus::Ready);
+        assert!(referenced_keys.is_empty());
+        assert_eq!(deleted_existing_file, None);
+        assert_eq!(unreferenced_keys, storage_keys);
+    }
+
+    #[actix_web::test]
+    async fn test_get_user_storage_usage() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let user = insert_user_test_util(&mut transaction).await;
+        let mut files = Vec::new();
+        for size in [100, 250] {
+            let mut sound_file = SoundFile::new(
+                SoundFileId(Uuid::new_v4().as_u128() as u64),
+                "Test file name".to_string(),
+                Some(user.id.clone()),
+                false,
+            );
+            sound_file.size = Some(size);
+            files.push(sound_file.insert(&mut transaction).await.unwrap());
+        }
+        SoundFile::delete(&files[0].id, &user.id, &mut transaction)
+            .await
+            .unwrap();
+
+        let usage = SoundFile::get_user_storage_usage(&user.id, &mut transaction)
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(
+            usage,
+            StorageUsage {
+                bytes: 350,
+                files: 2
+            }
+        );
+    }
+
+    #[actix_web::test]
+    async fn test_fork_file() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let user = insert_user_test_util(&mut transaction).await;
+        let sound_file = insert_random_file_test_util(&mut transaction).await;
+        let owner = sound_file.owner.clone().unwrap();
+        let private_fork = SoundFile::fork(
+            &SoundFileId(Uuid::new_v4().as_u128() as u64),
+            &sound_file.id,
+            &user.id,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        SoundFile::update(
+            &sound_file.id,
+            &owner,
+            None,
+            Some(Some("Description")),
+            Some(true),
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let own_fork = SoundFile::fork(
+            &SoundFileId(Uuid::new_v4().as_u128() as u64),
+            &sound_file.id,
+            &owner,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let fork = SoundFile::fork(
+            &SoundFileId(Uuid::new_v4().as_u128() as u64),
+            &sound_file.id,
+            &user.id,
+            &mut transaction,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        SoundFile::delete(&sound_file.id, &owner, &mut transaction)
+            .await
+            .unwrap();
+        let user_files =
+            SoundFile::get_user_files(&user.id, &TagFilter::default(), &mut transaction)
+                .await
+                .unwrap();
+        let unreferenced_keys = SoundFile::get_unreferenced_storage_keys(
+            std::slice::from_ref(&sound_file.storage_key),
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(private_fork, None);
+        assert_eq!(own_fork, None);
+        assert_eq!(fork.owner, Some(user.id));
+        assert!(!fork.is_public);
+        assert_eq!(fork.storage_key, sound_file.storage_key);
+        assert_eq!(fork.display_name, sound_file.display_name);
+        assert_eq!(fork.description.as_deref(), Some("Description"));
+        assert_eq!(fork.forked_from, Some(sound_file.id));
+        assert_eq!(fork.forked_from_owner, Some(owner));
+        assert_eq!(user_files, vec![fork]);
+        assert!(unreferenced_keys.is_empty());
+    }
+
     #[actix_web::test]
     async fn test_get_file() {
         let mut connection = db_connection().await;
@@ -477,9 +2376,10 @@ mod tests {
                 insert_file_test_util(&other_owner.id, None, &mut transaction).await;
             }
         }
-        let gotten_files = SoundFile::get_user_files(&owner.id, &mut transaction)
-            .await
-            .unwrap();
+        let gotten_files =
+            SoundFile::get_user_files(&owner.id, &TagFilter::default(), &mut transaction)
+                .await
+                .unwrap();
 
         transaction.commit().await.unwrap();
 
@@ -526,48 +2426,81 @@ mod tests {
             }
         }
 
-        let public_files_page_0 = SoundFile::get_public_files(MAX_LIMIT, 0, &mut transaction)
-            .await
-            .unwrap();
-
-        let public_files_page_1 = SoundFile::get_public_files(MAX_LIMIT, 1, &mut transaction)
-            .await
-            .unwrap();
-
-        let public_files_page_2 = SoundFile::get_public_files(MAX_LIMIT, 2, &mut transaction)
-            .await
-            .unwrap();
-
-        let public_files_limit_100 = SoundFile::get_public_files(100, 1, &mut transaction)
-            .await
-            .unwrap();
-
-        let public_files_over_max =
-            SoundFile::get_public_files(MAX_LIMIT + MAX_LIMIT, 1, &mut transaction)
-                .await
-                .unwrap();
-
-        let public_files_limit_100_page_2 = SoundFile::get_public_files(100, 2, &mut transaction)
-            .await
-            .unwrap();
-
-        assert_eq!(public_files_page_0.files, public_files_page_1.files);
-        assert_ne!(public_files_page_0.files, public_files_page_2.files);
-        assert_eq!(public_files_limit_100.files.len(), 100usize);
-        assert_eq!(public_files_over_max.files.len(), MAX_LIMIT as usize);
+        let scope = FileScope::Public;
+        let tag_filter = TagFilter::default();
+        let first_page = SoundFile::get_files_page(
+            &scope,
+            FileSort::Newest,
+            None,
+            MAX_LIMIT,
+            &tag_filter,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let next_cursor = FileCursor::decode(first_page.next.as_ref().unwrap()).unwrap();
+        let second_page = SoundFile::get_files_page(
+            &scope,
+            FileSort::Newest,
+            Some(&next_cursor),
+            MAX_LIMIT,
+            &tag_filter,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let limit_100 = SoundFile::get_files_page(
+            &scope,
+            FileSort::Newest,
+            None,
+            100,
+            &tag_filter,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let limit_100_cursor = FileCursor::decode(limit_100.next.as_ref().unwrap()).unwrap();
+        let limit_100_page_2 = SoundFile::get_files_page(
+            &scope,
+            FileSort::Newest,
+            Some(&limit_100_cursor),
+            100,
+            &tag_filter,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let over_max = SoundFile::get_files_page(
+            &scope,
+            FileSort::Newest,
+            None,
+            MAX_LIMIT + MAX_LIMIT,
+            &tag_filter,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
 
-        assert_eq!(public_files_limit_100_page_2.files.len(), 100usize);
+        assert_eq!(first_page.prev, None);
+        assert!(second_page.prev.is_some());
+        assert!(!second_page
+            .files
+            .iter()
+            .any(|f| first_page.files.contains(f)));
+        assert_eq!(limit_100.files.len(), 100usize);
+        assert_eq!(over_max.files.len(), MAX_LIMIT as usize);
         assert_eq!(
-            public_files_limit_100_page_2.files,
-            public_files_page_1
-                .clone()
+            limit_100_page_2.files,
+            first_page
                 .files
+                .clone()
                 .into_iter()
                 .skip(100)
+                .take(100)
                 .collect::<Vec<SoundFile>>()
         );
 
-        for pf in &public_files_page_0.files {
+        for pf in &first_page.files {
             assert_eq!(pf.is_public, true);
             assert_eq!(pf.is_deleted, false);
         }
@@ -0,0 +1,455 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+
+use crate::error::errors::KekServerError;
+
+use super::{
+    ids::{GuildId, SoundFileId, UserId},
+    postgres_like_escape,
+    sound_file::{SoundFile, SEARCH_SIMILARITY_THRESHOLD},
+};
+
+pub const MAX_TAG_LENGTH: usize = 32;
+pub const MAX_TAGS_PER_FILE: usize = 10;
+
+pub struct Tag;
+
+/// Whether listed files need all of the filtered tags or at least one of them.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TagMatch {
+    All,
+    Any,
+}
+
+impl Default for TagMatch {
+    fn default() -> Self {
+        return Self::All;
+    }
+}
+
+/// Tag filter of file listings, an empty filter matches every file.
+#[derive(Clone, Debug, Default)]
+pub struct TagFilter {
+    pub tags: Vec<String>,
+    pub tag_match: TagMatch,
+}
+
+impl TagFilter {
+    /// Tags are sent as a comma separated list.
+    pub fn from_query(
+        tags: Option<&str>,
+        tag_match: Option<TagMatch>,
+    ) -> Result<Self, KekServerError> {
+        let tags = match tags {
+            Some(tags) => Tag::normalize(
+                &tags
+                    .split(',')
+                    .filter(|t| !t.trim().is_empty())
+                    .map(|t| t.to_string())
+                    .collect::<Vec<String>>(),
+            )?,
+            None => Vec::new(),
+        };
+        return Ok(Self {
+            tags,
+            tag_match: tag_match.unwrap_or_default(),
+        });
+    }
+
+    /// Number of filtered tags a file needs to have to be listed.
+    pub fn required_matches(&self) -> i64 {
+        if self.tags.is_empty() {
+            return 0;
+        }
+        match self.tag_match {
+            TagMatch::All => return self.tags.len() as i64,
+            TagMatch::Any => return 1,
+        }
+    }
+}
+
+/// Tag filter query params of file listings.
+#[derive(Debug, Deserialize)]
+pub struct TagQueryParams {
+    /// Comma separated list of tags
+    pub tags: Option<String>,
+    pub tag_match: Option<TagMatch>,
+}
+
+/// Number of listed files with the tag.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TagCount {
+    pub name: String,
+    pub count: i64,
+}
+
+impl Tag {
+    /// Tags are stored trimmed and lowercase, so `Meme` and `meme ` are the same tag.
+    pub fn normalize(tags: &[String]) -> Result<Vec<String>, KekServerError> {
+        let mut normalized = Vec::with_capacity(tags.len());
+        for tag in tags {
+            let tag = tag.trim().to_lowercase();
+            if tag.is_empty() || tag.chars().count() > MAX_TAG_LENGTH {
+                return Err(KekServerError::InvalidTagError(tag));
+            }
+            if !normalized.contains(&tag) {
+                normalized.push(tag);
+            }
+        }
+        if normalized.len() > MAX_TAGS_PER_FILE {
+            return Err(KekServerError::InvalidTagError(format!(
+                "Files can have at most {} tags",
+                MAX_TAGS_PER_FILE
+            )));
+        }
+        return Ok(normalized);
+    }
+
+    /// Expects tags normalized with `Tag::normalize`.
+    pub async fn add_file_tags(
+        file_id: &SoundFileId,
+        tags: &[String],
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        sqlx::query!(
+            "
+            INSERT INTO tag (name)
+            SELECT UNNEST($1::text[])
+            ON CONFLICT (name) DO NOTHING
+            ",
+            tags
+        )
+        .execute(&mut *transaction)
+        .await?;
+        sqlx::query!(
+            "
+            INSERT INTO file_tag (file_id, tag_id)
+            SELECT $1, id FROM tag
+            WHERE name = ANY($2)
+            ON CONFLICT DO NOTHING
+            ",
+            file_id.0 as i64,
+            tags
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+
+    /// Expects tags normalized with `Tag::normalize`.
+    pub async fn remove_file_tags(
+        file_id: &SoundFileId,
+        tags: &[String],
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        sqlx::query!(
+            "
+            DELETE FROM file_tag
+            USING tag
+            WHERE file_tag.tag_id = tag.id
+            AND file_tag.file_id = $1
+            AND tag.name = ANY($2)
+            ",
+            file_id.0 as i64,
+            tags
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+
+    /// Sorted tags of each file, files without tags are missing from the map.
+    pub async fn get_for_files(
+        file_ids: &[SoundFileId],
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<HashMap<SoundFileId, Vec<String>>, KekServerError> {
+        let file_ids = file_ids.iter().map(|id| id.0 as i64).collect::<Vec<i64>>();
+        let records = sqlx::query!(
+            "
+            SELECT file_tag.file_id, tag.name FROM file_tag
+            INNER JOIN tag ON tag.id = file_tag.tag_id
+            WHERE file_tag.file_id = ANY($1)
+            ORDER BY tag.name
+            ",
+            &file_ids
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        let mut tags = HashMap::<SoundFileId, Vec<String>>::new();
+        for r in records {
+            tags.entry(r.file_id.into()).or_default().push(r.name);
+        }
+        return Ok(tags);
+    }
+
+    /// Fills in `tags` of the files.
+    pub async fn attach_to_files(
+        files: Vec<&mut SoundFile>,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        let file_ids = files
+            .iter()
+            .map(|f| f.id.clone())
+            .collect::<Vec<SoundFileId>>();
+        let mut tags = Tag::get_for_files(&file_ids, transaction).await?;
+        for file in files {
+            file.tags = tags.remove(&file.id).unwrap_or_default();
+        }
+        return Ok(());
+    }
+
+    /// Tag counts of public files matching the search and tag filter,
+    /// files are matched like in `SoundFile::search`.
+    pub async fn get_public_counts(
+        search: Option<&str>,
+        tag_filter: &TagFilter,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<TagCount>, KekServerError> {
+        let like_search = search.map(|s| format!("%{}%", postgres_like_escape(s.to_string())));
+        let records = sqlx::query!(
+            "
+            SELECT tag.name, COUNT(*) as \"count!\" FROM files
+            INNER JOIN file_tag ON file_tag.file_id = files.id
+            INNER JOIN tag ON tag.id = file_tag.tag_id
+            WHERE files.is_public = true AND files.is_deleted = false
+            AND (
+                $1::text IS NULL
+                OR files.display_name ILIKE $1
+                OR word_similarity($2, files.display_name) >= $3
+            )
+            AND (
+                SELECT COUNT(*) FROM file_tag
+                INNER JOIN tag ON tag.id = file_tag.tag_id
+                WHERE file_tag.file_id = files.id AND tag.name = ANY($4)
+            ) >= $5
+            GROUP BY tag.name
+            ORDER BY 2 DESC, tag.name
+            ",
+            like_search,
+            search,
+            *SEARCH_SIMILARITY_THRESHOLD,
+            &tag_filter.tags,
+            tag_filter.required_matches()
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records
+            .into_iter()
+            .map(|r| TagCount {
+                name: r.name,
+                count: r.count,
+            })
+            .collect());
+    }
+
+    /// Tag counts of the user's files matching the tag filter.
+    pub async fn get_user_counts(
+        user_id: &UserId,
+        tag_filter: &TagFilter,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<TagCount>, KekServerError> {
+        let records = sqlx::query!(
+            "
+            SELECT tag.name, COUNT(*) as \"count!\" FROM files
+            INNER JOIN file_tag ON file_tag.file_id = files.id
+            INNER JOIN tag ON tag.id = file_tag.tag_id
+            WHERE files.owner = $1 AND files.is_deleted = false
+            AND (
+                SELECT COUNT(*) FROM file_tag
+                INNER JOIN tag ON tag.id = file_tag.tag_id
+                WHERE file_tag.file_id = files.id AND tag.name = ANY($2)
+            ) >= $3
+            GROUP BY tag.name
+            ORDER BY 2 DESC, tag.name
+            ",
+            user_id.0 as i64,
+            &tag_filter.tags,
+            tag_filter.required_matches()
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records
+            .into_iter()
+            .map(|r| TagCount {
+                name: r.name,
+                count: r.count,
+            })
+            .collect());
+    }
+
+    /// Tag counts of files enabled in the guild matching the tag filter.
+    pub async fn get_guild_counts(
+        guild_id: &GuildId,
+        tag_filter: &TagFilter,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<TagCount>, KekServerError> {
+        let records = sqlx::query!(
+            "
+            SELECT tag.name, COUNT(*) as \"count!\" FROM files
+            INNER JOIN guild_file ON guild_file.file_id = files.id
+            INNER JOIN file_tag ON file_tag.file_id = files.id
+            INNER JOIN tag ON tag.id = file_tag.tag_id
+            WHERE guild_file.guild_id = $1 AND guild_file.is_deleted = false
+            AND (
+                SELECT COUNT(*) FROM file_tag
+                INNER JOIN tag ON tag.id = file_tag.tag_id
+                WHERE file_tag.file_id = files.id AND tag.name = ANY($2)
+            ) >= $3
+            GROUP BY tag.name
+            ORDER BY 2 DESC, tag.name
+            ",
+            guild_id.0 as i64,
+            &tag_filter.tags,
+            tag_filter.required_matches()
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records
+            .into_iter()
+            .map(|r| TagCount {
+                name: r.name,
+                count: r.count,
+            })
+            .collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection;
+
+    use crate::{
+        database::tests_db_helper::db_connection,
+        models::sound_file::SoundFile,
+        utils::test_utils::{
+            insert_file_test_util, insert_random_file_test_util, insert_user_test_util,
+        },
+    };
+
+    use super::{Tag, TagCount, TagFilter, TagMatch};
+
+    #[test]
+    fn test_normalize_tags() {
+        let tags =
+            Tag::normalize(&[" Meme".to_string(), "meme".to_string(), "Loud".to_string()]).unwrap();
+
+        assert_eq!(tags, vec!["meme".to_string(), "loud".to_string()]);
+        assert!(Tag::normalize(&["  ".to_string()]).is_err());
+        assert!(Tag::normalize(&["a".repeat(33)]).is_err());
+    }
+
+    #[test]
+    fn test_tag_filter() {
+        let all_filter = TagFilter::from_query(Some("Meme,,loud"), None).unwrap();
+        let any_filter = TagFilter::from_query(Some("meme,loud"), Some(TagMatch::Any)).unwrap();
+        let empty_filter = TagFilter::from_query(None, Some(TagMatch::Any)).unwrap();
+
+        assert_eq!(
+            all_filter.tags,
+            vec!["meme".to_string(), "loud".to_string()]
+        );
+        assert_eq!(all_filter.required_matches(), 2);
+        assert_eq!(any_filter.required_matches(), 1);
+        assert_eq!(empty_filter.required_matches(), 0);
+    }
+
+    #[actix_web::test]
+    async fn test_add_file_tags() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let sound_file = insert_random_file_test_util(&mut transaction).await;
+        let tags = vec!["meme".to_string(), "loud".to_string()];
+        Tag::add_file_tags(&sound_file.id, &tags, &mut transaction)
+            .await
+            .unwrap();
+        Tag::add_file_tags(&sound_file.id, &tags[..1], &mut transaction)
+            .await
+            .unwrap();
+        let file_tags = sqlx::query!(
+            "
+            SELECT tag.name FROM tag
+            INNER JOIN file_tag ON file_tag.tag_id = tag.id
+            WHERE file_tag.file_id = $1
+            ORDER BY tag.name
+            ",
+            sound_file.id.0 as i64
+        )
+        .fetch_all(&mut transaction)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|r| r.name)
+        .collect::<Vec<String>>();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(file_tags, vec!["loud".to_string(), "meme".to_string()]);
+    }
+
+    #[actix_web::test]
+    async fn test_filter_and_count_tags() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let user = insert_user_test_util(&mut transaction).await;
+        let meme_file = insert_file_test_util(&user.id, None, &mut transaction).await;
+        let loud_meme_file = insert_file_test_util(&user.id, None, &mut transaction).await;
+        let untagged_file = insert_file_test_util(&user.id, None, &mut transaction).await;
+        Tag::add_file_tags(&meme_file.id, &["meme".to_string()], &mut transaction)
+            .await
+            .unwrap();
+        let tags = vec!["meme".to_string(), "loud".to_string(), "quiet".to_string()];
+        Tag::add_file_tags(&loud_meme_file.id, &tags, &mut transaction)
+            .await
+            .unwrap();
+        Tag::remove_file_tags(&loud_meme_file.id, &tags[2..], &mut transaction)
+            .await
+            .unwrap();
+
+        let all_filter = TagFilter::from_query(Some("meme,loud"), None).unwrap();
+        let any_filter = TagFilter::from_query(Some("meme,loud"), Some(TagMatch::Any)).unwrap();
+        let mut all_files = SoundFile::get_user_files(&user.id, &all_filter, &mut transaction)
+            .await
+            .unwrap();
+        Tag::attach_to_files(all_files.iter_mut().collect(), &mut transaction)
+            .await
+            .unwrap();
+        let any_files = SoundFile::get_user_files(&user.id, &any_filter, &mut transaction)
+            .await
+            .unwrap();
+        let unfiltered_files =
+            SoundFile::get_user_files(&user.id, &TagFilter::default(), &mut transaction)
+                .await
+                .unwrap();
+        let tag_counts = Tag::get_user_counts(&user.id, &TagFilter::default(), &mut transaction)
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(all_files.len(), 1);
+        assert_eq!(all_files[0].id, loud_meme_file.id);
+        assert_eq!(
+            all_files[0].tags,
+            vec!["loud".to_string(), "meme".to_string()]
+        );
+        assert_eq!(any_files.len(), 2);
+        assert!(!any_files.iter().any(|f| f.id == untagged_file.id));
+        assert_eq!(unfiltered_files.len(), 3);
+        assert_eq!(
+            tag_counts,
+            vec![
+                TagCount {
+                    name: "meme".to_string(),
+                    count: 2
+                },
+                TagCount {
+                    name: "loud".to_string(),
+                    count: 1
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,351 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+
+use crate::error::errors::KekServerError;
+
+use super::ids::{SoundFileId, TransferOfferId, UserId};
+
+/// Files offered by their owner to another user, the files change owner when the recipient accepts the offer.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TransferOffer {
+    pub id: TransferOfferId,
+    pub from_user: UserId,
+    pub to_user: UserId,
+    pub files: Vec<SoundFileId>,
+    pub time_added: NaiveDateTime,
+    pub expires_at: NaiveDateTime,
+}
+
+/// Recorded change of a file's owner.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OwnershipTransfer {
+    pub file_id: SoundFileId,
+    pub from_user: UserId,
+    pub to_user: UserId,
+    pub offer_id: TransferOfferId,
+    pub time_transferred: NaiveDateTime,
+}
+
+impl TransferOffer {
+    /// Returns `None` if some of the files aren't owned by the user or are deleted.
+    pub async fn insert(
+        id: &TransferOfferId,
+        from_user: &UserId,
+        to_user: &UserId,
+        file_ids: &[SoundFileId],
+        expiry_hours: i32,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Self>, KekServerError> {
+        let mut file_ids = file_ids.iter().map(|f| f.0 as i64).collect::<Vec<i64>>();
+        file_ids.sort_unstable();
+        file_ids.dedup();
+        let r = sqlx::query!(
+            "
+            INSERT INTO transfer_offer (id, from_user, to_user, expires_at)
+            VALUES ($1, $2, $3, CURRENT_TIMESTAMP + make_interval(hours => $4))
+            RETURNING *
+            ",
+            id.0 as i64,
+            from_user.0 as i64,
+            to_user.0 as i64,
+            expiry_hours
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        let offered_files = sqlx::query!(
+            "
+            INSERT INTO transfer_offer_file (offer_id, file_id)
+            SELECT $1, id FROM files
+            WHERE id = ANY($2) AND owner = $3 AND is_deleted = false
+            RETURNING file_id
+            ",
+            id.0 as i64,
+            &file_ids,
+            from_user.0 as i64
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        if file_ids.is_empty() || offered_files.len() != file_ids.len() {
+            TransferOffer::delete(id, transaction).await?;
+            return Ok(None);
+        }
+        return Ok(Some(Self {
+            id: r.id.into(),
+            from_user: r.from_user.into(),
+            to_user: r.to_user.into(),
+            files: file_ids.into_iter().map(SoundFileId::from).collect(),
+            time_added: r.time_added,
+            expires_at: r.expires_at,
+        }));
+    }
+
+    /// Pending offer sent or received by the user, expired offers are not returned.
+    pub async fn get(
+        id: &TransferOfferId,
+        user_id: &UserId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Self>, KekServerError> {
+        match sqlx::query!(
+            "
+            SELECT transfer_offer.*, array_agg(transfer_offer_file.file_id) as \"files!\"
+            FROM transfer_offer
+            INNER JOIN transfer_offer_file ON transfer_offer_file.offer_id = transfer_offer.id
+            WHERE transfer_offer.id = $1 AND (from_user = $2 OR to_user = $2)
+            AND expires_at > CURRENT_TIMESTAMP
+            GROUP BY transfer_offer.id
+            ",
+            id.0 as i64,
+            user_id.0 as i64
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        {
+            Some(r) => {
+                return Ok(Some(Self {
+                    id: r.id.into(),
+                    from_user: r.from_user.into(),
+                    to_user: r.to_user.into(),
+                    files: r.files.into_iter().map(SoundFileId::from).collect(),
+                    time_added: r.time_added,
+                    expires_at: r.expires_at,
+                }));
+            }
+            None => return Ok(None),
+        }
+    }
+
+    /// Pending offers sent or received by the user, newest first.
+    pub async fn get_user_offers(
+        user_id: &UserId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<Self>, KekServerError> {
+        let records = sqlx::query!(
+            "
+            SELECT transfer_offer.*, array_agg(transfer_offer_file.file_id) as \"files!\"
+            FROM transfer_offer
+            INNER JOIN transfer_offer_file ON transfer_offer_file.offer_id = transfer_offer.id
+            WHERE (from_user = $1 OR to_user = $1) AND expires_at > CURRENT_TIMESTAMP
+            GROUP BY transfer_offer.id
+            ORDER BY transfer_offer.time_added DESC, transfer_offer.id DESC
+            ",
+            user_id.0 as i64
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records
+            .into_iter()
+            .map(|r| Self {
+                id: r.id.into(),
+                from_user: r.from_user.into(),
+                to_user: r.to_user.into(),
+                files: r.files.into_iter().map(SoundFileId::from).collect(),
+                time_added: r.time_added,
+                expires_at: r.expires_at,
+            })
+            .collect());
+    }
+
+    /// Withdraws or declines the offer.
+    pub async fn delete(
+        id: &TransferOfferId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        sqlx::query!(
+            "
+            DELETE FROM transfer_offer
+            WHERE id = $1
+            ",
+            id.0 as i64
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+
+    /// Makes the recipient the owner of the offered files and records the changes, the offer is removed.
+    /// Files which were deleted or changed owner since the offer was made are skipped.
+    pub async fn accept(
+        &self,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<OwnershipTransfer>, KekServerError> {
+        let file_ids = self.files.iter().map(|f| f.0 as i64).collect::<Vec<i64>>();
+        let records = sqlx::query!(
+            "
+            WITH transferred_files AS (
+                UPDATE files
+                SET owner = $3
+                WHERE id = ANY($4) AND owner = $2 AND is_deleted = false
+                RETURNING id
+            )
+            INSERT INTO ownership_transfer (file_id, from_user, to_user, offer_id)
+            SELECT id, $2, $3, $1 FROM transferred_files
+            RETURNING *
+            ",
+            self.id.0 as i64,
+            self.from_user.0 as i64,
+            self.to_user.0 as i64,
+            &file_ids
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        TransferOffer::delete(&self.id, transaction).await?;
+        return Ok(records
+            .into_iter()
+            .map(|r| OwnershipTransfer {
+                file_id: r.file_id.into(),
+                from_user: r.from_user.into(),
+                to_user: r.to_user.into(),
+                offer_id: r.offer_id.into(),
+                time_transferred: r.time_transferred,
+            })
+            .collect());
+    }
+
+    /// Removes expired offers and returns the number of removed offers.
+    pub async fn delete_expired(
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<u64, KekServerError> {
+        let result = sqlx::query!(
+            "
+            DELETE FROM transfer_offer
+            WHERE expires_at <= CURRENT_TIMESTAMP
+            "
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(result.rows_affected());
+    }
+}
+
+impl OwnershipTransfer {
+    /// Files transferred from or to the user, most recent first.
+    pub async fn get_user_transfers(
+        user_id: &UserId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<Self>, KekServerError> {
+        let records = sqlx::query!(
+            "
+            SELECT * FROM ownership_transfer
+            WHERE from_user = $1 OR to_user = $1
+            ORDER BY time_transferred DESC, id DESC
+            ",
+            user_id.0 as i64
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records
+            .into_iter()
+            .map(|r| Self {
+                file_id: r.file_id.into(),
+                from_user: r.from_user.into(),
+                to_user: r.to_user.into(),
+                offer_id: r.offer_id.into(),
+                time_transferred: r.time_transferred,
+            })
+            .collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection;
+    use uuid::Uuid;
+
+    use crate::{
+        database::tests_db_helper::db_connection,
+        models::{ids::TransferOfferId, sound_file::SoundFile},
+        utils::test_utils::{insert_random_file_test_util, insert_user_test_util},
+    };
+
+    use super::{OwnershipTransfer, TransferOffer};
+
+    #[actix_web::test]
+    async fn test_transfer_offer() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let recipient = insert_user_test_util(&mut transaction).await;
+        let sound_file = insert_random_file_test_util(&mut transaction).await;
+        let owner = sound_file.owner.clone().unwrap();
+        let other_file = insert_random_file_test_util(&mut transaction).await;
+
+        let invalid_offer = TransferOffer::insert(
+            &TransferOfferId(Uuid::new_v4().as_u128() as u64),
+            &owner,
+            &recipient.id,
+            &[sound_file.id.clone(), other_file.id.clone()],
+            1,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let offer = TransferOffer::insert(
+            &TransferOfferId(Uuid::new_v4().as_u128() as u64),
+            &owner,
+            &recipient.id,
+            &[sound_file.id.clone(), sound_file.id.clone()],
+            1,
+            &mut transaction,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+        let received_offers = TransferOffer::get_user_offers(&recipient.id, &mut transaction)
+            .await
+            .unwrap();
+        let transfers = offer.accept(&mut transaction).await.unwrap();
+        let accepted_offer = TransferOffer::get(&offer.id, &recipient.id, &mut transaction)
+            .await
+            .unwrap();
+        let recipient_file = SoundFile::get_file(&sound_file.id, &recipient.id, &mut transaction)
+            .await
+            .unwrap();
+        let owner_transfers = OwnershipTransfer::get_user_transfers(&owner, &mut transaction)
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(invalid_offer, None);
+        assert_eq!(offer.files, vec![sound_file.id.clone()]);
+        assert_eq!(received_offers, vec![offer.clone()]);
+        assert_eq!(transfers.len(), 1);
+        assert_eq!(transfers[0].file_id, sound_file.id);
+        assert_eq!(transfers[0].from_user, owner);
+        assert_eq!(transfers[0].to_user, recipient.id);
+        assert_eq!(accepted_offer, None);
+        assert!(recipient_file.is_some());
+        assert_eq!(owner_transfers, transfers);
+    }
+
+    #[actix_web::test]
+    async fn test_expired_transfer_offer() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let recipient = insert_user_test_util(&mut transaction).await;
+        let sound_file = insert_random_file_test_util(&mut transaction).await;
+        let offer = TransferOffer::insert(
+            &TransferOfferId(Uuid::new_v4().as_u128() as u64),
+            sound_file.owner.as_ref().unwrap(),
+            &recipient.id,
+            std::slice::from_ref(&sound_file.id),
+            -1,
+            &mut transaction,
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        let expired_offer = TransferOffer::get(&offer.id, &recipient.id, &mut transaction)
+            .await
+            .unwrap();
+        let deleted_offers = TransferOffer::delete_expired(&mut transaction)
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(expired_offer, None);
+        assert!(deleted_offers >= 1);
+    }
+}
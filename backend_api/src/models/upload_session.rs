@@ -0,0 +1,229 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction};
+
+use crate::error::errors::KekServerError;
+
+use super::ids::{SoundFileId, UserId};
+
+/// Partially uploaded file, finalizing it creates a sound file with the same id.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct UploadSession {
+    pub id: SoundFileId,
+    pub owner: UserId,
+    pub display_name: String,
+    pub is_public: bool,
+    /// Total size of the file in bytes
+    pub size: i64,
+    pub uploaded_size: i64,
+    pub expires_at: NaiveDateTime,
+}
+
+impl UploadSession {
+    pub async fn insert(
+        id: &SoundFileId,
+        owner: &UserId,
+        display_name: &str,
+        is_public: bool,
+        size: i64,
+        expiry_hours: i32,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Self, KekServerError> {
+        let r = sqlx::query!(
+            "
+            INSERT INTO upload_session (id, owner, display_name, is_public, size, expires_at)
+            VALUES ($1, $2, $3, $4, $5, CURRENT_TIMESTAMP + make_interval(hours => $6))
+            RETURNING *
+            ",
+            id.0 as i64,
+            owner.0 as i64,
+            display_name,
+            is_public,
+            size,
+            expiry_hours
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        return Ok(Self {
+            id: r.id.into(),
+            owner: r.owner.into(),
+            display_name: r.display_name,
+            is_public: r.is_public,
+            size: r.size,
+            uploaded_size: r.uploaded_size,
+            expires_at: r.expires_at,
+        });
+    }
+
+    /// Locks the session until the transaction ends, expired sessions are not returned.
+    pub async fn get_for_update(
+        id: &SoundFileId,
+        owner: &UserId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Option<Self>, KekServerError> {
+        match sqlx::query!(
+            "
+            SELECT * FROM upload_session
+            WHERE id = $1 AND owner = $2 AND expires_at > CURRENT_TIMESTAMP
+            FOR UPDATE
+            ",
+            id.0 as i64,
+            owner.0 as i64
+        )
+        .fetch_optional(&mut *transaction)
+        .await?
+        {
+            Some(r) => {
+                return Ok(Some(Self {
+                    id: r.id.into(),
+                    owner: r.owner.into(),
+                    display_name: r.display_name,
+                    is_public: r.is_public,
+                    size: r.size,
+                    uploaded_size: r.uploaded_size,
+                    expires_at: r.expires_at,
+                }));
+            }
+            None => return Ok(None),
+        }
+    }
+
+    /// Records appended bytes and extends the session expiry.
+    pub async fn append(
+        &self,
+        appended_size: i64,
+        expiry_hours: i32,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Self, KekServerError> {
+        let r = sqlx::query!(
+            "
+            UPDATE upload_session
+            SET uploaded_size = uploaded_size + $2,
+            expires_at = CURRENT_TIMESTAMP + make_interval(hours => $3)
+            WHERE id = $1
+            RETURNING *
+            ",
+            self.id.0 as i64,
+            appended_size,
+            expiry_hours
+        )
+        .fetch_one(&mut *transaction)
+        .await?;
+        return Ok(Self {
+            id: r.id.into(),
+            owner: r.owner.into(),
+            display_name: r.display_name,
+            is_public: r.is_public,
+            size: r.size,
+            uploaded_size: r.uploaded_size,
+            expires_at: r.expires_at,
+        });
+    }
+
+    pub async fn delete(
+        id: &SoundFileId,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        sqlx::query!(
+            "
+            DELETE FROM upload_session
+            WHERE id = $1
+            ",
+            id.0 as i64
+        )
+        .execute(&mut *transaction)
+        .await?;
+        return Ok(());
+    }
+
+    /// Removes expired sessions and returns them.
+    pub async fn delete_expired(
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<Vec<Self>, KekServerError> {
+        let records = sqlx::query!(
+            "
+            DELETE FROM upload_session
+            WHERE expires_at <= CURRENT_TIMESTAMP
+            RETURNING *
+            "
+        )
+        .fetch_all(&mut *transaction)
+        .await?;
+        return Ok(records
+            .into_iter()
+            .map(|r| Self {
+                id: r.id.into(),
+                owner: r.owner.into(),
+                display_name: r.display_name,
+                is_public: r.is_public,
+                size: r.size,
+                uploaded_size: r.uploaded_size,
+                expires_at: r.expires_at,
+            })
+            .collect());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sqlx::Connection;
+    use uuid::Uuid;
+
+    use crate::{
+        database::tests_db_helper::db_connection, models::ids::SoundFileId,
+        utils::test_utils::insert_user_test_util,
+    };
+
+    use super::UploadSession;
+
+    #[actix_web::test]
+    async fn test_upload_session() {
+        let mut connection = db_connection().await;
+        let mut transaction = connection.begin().await.unwrap();
+
+        let user = insert_user_test_util(&mut transaction).await;
+        let id = SoundFileId(Uuid::new_v4().as_u128() as u64);
+        let session = UploadSession::insert(
+            &id,
+            &user.id,
+            "Test file name",
+            false,
+            100,
+            1,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let session = session.append(40, 1, &mut transaction).await.unwrap();
+        let locked_session = UploadSession::get_for_update(&id, &user.id, &mut transaction)
+            .await
+            .unwrap();
+
+        let expired_id = SoundFileId(Uuid::new_v4().as_u128() as u64);
+        UploadSession::insert(
+            &expired_id,
+            &user.id,
+            "Test file name",
+            false,
+            100,
+            -1,
+            &mut transaction,
+        )
+        .await
+        .unwrap();
+        let expired_session =
+            UploadSession::get_for_update(&expired_id, &user.id, &mut transaction)
+                .await
+                .unwrap();
+        let deleted_sessions = UploadSession::delete_expired(&mut transaction)
+            .await
+            .unwrap();
+        transaction.commit().await.unwrap();
+
+        assert_eq!(session.uploaded_size, 40);
+        assert_eq!(locked_session, Some(session));
+        assert_eq!(expired_session, None);
+        assert!(deleted_sessions.iter().any(|s| s.id == expired_id));
+        assert!(!deleted_sessions.iter().any(|s| s.id == id));
+    }
+}
@@ -10,8 +10,9 @@ use actix_web::{
 
 use chrono::Utc;
 use oauth2::{
-    basic::BasicTokenType, url::Url, AccessToken, AuthorizationCode, CsrfToken, PkceCodeChallenge,
-    PkceCodeVerifier, RefreshToken, StandardRevocableToken, StandardTokenResponse, TokenResponse, reqwest::async_http_client,
+    basic::BasicTokenType, reqwest::async_http_client, url::Url, AccessToken, AuthorizationCode,
+    CsrfToken, PkceCodeChallenge, PkceCodeVerifier, RefreshToken, StandardRevocableToken,
+    StandardTokenResponse, TokenResponse,
 };
 use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
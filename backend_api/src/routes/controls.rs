@@ -15,12 +15,15 @@ use crate::{
     middleware::{auth_middleware::AuthService, user_guilds_middleware::UserGuildsService},
     models::{
         guild_file::GuildFile,
+        guild_role::GuildAction,
+        guild_settings::GuildSettings,
         ids::{ChannelId, GuildId, SoundFileId},
         sound_file::SoundFile,
     },
     utils::{
         auth::AuthorizedUserExt,
         cache::{UserGuildsCache, UserGuildsCacheUtil},
+        validation::Validation,
     },
     ws::{
         ws_server::{ControlsServer, ControlsServerMessage},
@@ -131,6 +134,14 @@ pub async fn play_request(
     }
 
     let mut transaction = db_pool.begin().await?;
+    Validation::is_action_allowed(
+        &authorized_user,
+        &req_payload.guild_id,
+        GuildAction::Play,
+        &user_guilds_cache,
+        &mut transaction,
+    )
+    .await?;
 
     match GuildFile::get_guild_file(
         &req_payload.guild_id,
@@ -140,10 +151,13 @@ pub async fn play_request(
     .await?
     {
         Some(guild_file) => {
+            SoundFile::increment_play_count(&guild_file.file_id, &mut transaction).await?;
+            let settings = GuildSettings::get(&guild_file.guild_id, &mut transaction).await?;
             transaction.commit().await?;
 
             let payload = req_payload.into_inner();
-            let control = ControlsServerMessage::new_play(guild_file, payload.channel_id);
+            let control =
+                ControlsServerMessage::new_play(guild_file, payload.channel_id, &settings);
             let resp = send_command(control, server_address, ws_channels).await?;
 
             return Ok(HttpResponse::Ok().json(resp));
@@ -157,6 +171,7 @@ pub async fn stop_request(
     server_address: Data<Addr<ControlsServer>>,
     AuthorizedUserExt(authorized_user): AuthorizedUserExt,
     Json(stop_payload): Json<StopPayload>,
+    db_pool: Data<PgPool>,
     ws_channels: Data<WsSessionCommChannels>,
     user_guilds_cache: Data<UserGuildsCache>,
 ) -> Result<HttpResponse, KekServerError> {
@@ -169,6 +184,17 @@ pub async fn stop_request(
         return Err(KekServerError::NotInGuildError);
     }
 
+    let mut transaction = db_pool.begin().await?;
+    Validation::is_action_allowed(
+        &authorized_user,
+        &stop_payload.guild_id,
+        GuildAction::Play,
+        &user_guilds_cache,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
     let control = ControlsServerMessage::new_stop(stop_payload.guild_id);
     let resp = send_command(control, server_address, ws_channels).await?;
 
@@ -180,6 +206,7 @@ pub async fn skip_request(
     server_address: Data<Addr<ControlsServer>>,
     AuthorizedUserExt(authorized_user): AuthorizedUserExt,
     Json(skip_payload): Json<SkipPayload>,
+    db_pool: Data<PgPool>,
     ws_channels: Data<WsSessionCommChannels>,
     user_guilds_cache: Data<UserGuildsCache>,
 ) -> Result<HttpResponse, KekServerError> {
@@ -192,6 +219,17 @@ pub async fn skip_request(
         return Err(KekServerError::NotInGuildError);
     }
 
+    let mut transaction = db_pool.begin().await?;
+    Validation::is_action_allowed(
+        &authorized_user,
+        &skip_payload.guild_id,
+        GuildAction::Play,
+        &user_guilds_cache,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
     let control = ControlsServerMessage::new_skip(skip_payload.guild_id);
     let resp = send_command(control, server_address, ws_channels).await?;
 
@@ -1,30 +1,52 @@
-use std::sync::Mutex;
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
 
 use actix_multipart::{Field, Multipart};
 use actix_web::{
-    get, post,
-    web::{self, scope, Data, Path, Query, ServiceConfig},
+    delete, get, patch, post, put,
+    web::{self, scope, Data, Json, Path, Payload, Query, ServiceConfig},
     HttpRequest, HttpResponse,
 };
 use futures_util::TryStreamExt;
 use log::error;
 use serde::{Deserialize, Serialize};
 use snowflake::SnowflakeIdGenerator;
-use sqlx::PgPool;
-use tokio::{
-    fs::{remove_file, File},
-    io::AsyncWriteExt,
-};
+use sqlx::{PgPool, Postgres, Transaction};
 
 use crate::{
-    config::Config,
+    audio::{
+        metadata::probe_metadata, processing::spawn_processing, trim::trim_audio,
+        waveform::generate_waveform,
+    },
+    config::{Config, Quota},
+    deleted_files_purge::delete_blobs,
     error::errors::KekServerError,
-    middleware::auth_middleware::AuthService,
+    expired_uploads_purge::UPLOAD_SESSION_EXPIRY_HOURS,
+    middleware::{auth_middleware::AuthService, user_guilds_middleware::UserGuildsService},
     models::{
-        ids::{SoundFileId, UserId},
-        sound_file::{SoundFile, MAX_LIMIT},
+        favorite::Favorite,
+        file_version::FileVersion,
+        file_waveform::FileWaveform,
+        guild::Guild,
+        guild_file::GuildFile,
+        ids::{GuildId, SoundFileId, UserId},
+        sound_file::{
+            FileCursor, FileScope, FileSort, FilesAndCount, ProcessingStatus, SoundFile,
+            StorageUsage, MAX_LIMIT,
+        },
+        tag::{Tag, TagFilter, TagMatch},
+        upload_session::UploadSession,
+    },
+    storage::{
+        checksum, signed_url::is_valid_audio_signature, sound_file_key, upload_key, SoundStorage,
+    },
+    utils::{
+        auth::{AuthorizedUser, AuthorizedUserExt},
+        cache::{TrimmedAudioCache, UserGuildsCache},
+        validation::Validation,
     },
-    utils::auth::AuthorizedUserExt,
 };
 use lazy_static::lazy_static;
 
@@ -34,27 +56,61 @@ lazy_static! {
         .unwrap_or_else(|_| 10_000_000.to_string())
         .parse()
         .unwrap_or(10_000_000);
+    static ref MAX_DURATION_MS: Option<i64> = dotenv::var("MAX_DURATION_MS")
+        .ok()
+        .and_then(|d| d.parse().ok());
 }
 
 const PUBLIC_SUFFIX: &str = "_p";
+const MANIFEST_FIELD: &str = "manifest";
+const MAX_MANIFEST_SIZE: usize = 64_000;
+const UPLOAD_OFFSET_HEADER: &str = "Upload-Offset";
 
 pub fn config(cfg: &mut ServiceConfig) {
     cfg.service(
         scope("/files")
             .service(upload_file)
+            .service(create_upload_session)
+            .service(get_upload_session)
+            .service(append_upload)
+            .service(finalize_upload)
+            .service(cancel_upload)
+            .service(replace_file_audio)
+            .service(rollback_file_audio)
+            .service(fork_file)
             .service(get_public_files)
-            .service(preview),
+            .service(preview)
+            .service(signed_audio)
+            .service(waveform),
     );
 }
 
-async fn delete_file(sound_file: &SoundFile) -> Result<(), KekServerError> {
-    let full_file_path = format!("{}{}", dotenv::var("SOUNDFILE_DIR")?, sound_file.id.0);
-    return Ok(remove_file(full_file_path).await?);
+/// Whether the file's bytes were saved by this upload instead of being shared with an existing file.
+fn is_new_blob(sound_file: &SoundFile) -> bool {
+    return sound_file.storage_key == sound_file_key(&sound_file.id);
+}
+
+/// Removes bytes saved by this upload, bytes shared with other files are kept.
+async fn delete_file(
+    sound_file: &SoundFile,
+    storage: &Data<dyn SoundStorage>,
+) -> Result<(), KekServerError> {
+    if !is_new_blob(sound_file) {
+        return Ok(());
+    }
+    return storage.delete(&sound_file.storage_key).await;
+}
+
+async fn validate_audio_mime(
+    sound_file: &SoundFile,
+    storage: &Data<dyn SoundStorage>,
+) -> Result<(), KekServerError> {
+    let data = storage.read(&sound_file.storage_key).await?;
+    return validate_audio_data(data).await;
 }
 
-async fn validate_audio_mime(sound_file: &SoundFile) -> Result<(), KekServerError> {
-    let full_file_path = format!("{}{}", dotenv::var("SOUNDFILE_DIR")?, sound_file.id.0);
-    let mime = web::block(move || infer::get_from_path(full_file_path)).await??;
+async fn validate_audio_data(data: Vec<u8>) -> Result<(), KekServerError> {
+    let mime = web::block(move || infer::get(&data)).await?;
 
     let mime = match mime {
         Some(m) => m,
@@ -82,79 +138,338 @@ fn parse_display_name(field: &Field) -> String {
     }
 }
 
+/// Reads the whole field, returns `None` if it is larger than `MAX_FILE_SIZE`.
+/// Rest of an oversized field is skipped when reading the next one.
+async fn read_audio_field(field: &mut Field) -> Result<Option<Vec<u8>>, KekServerError> {
+    let mut data = Vec::new();
+    while let Some(chunk) = field.try_next().await? {
+        if data.len() + chunk.len() > *MAX_FILE_SIZE {
+            return Ok(None);
+        }
+        data.extend_from_slice(&chunk);
+    }
+    return Ok(Some(data));
+}
+
 async fn insert_valid_files(
     mut files: Vec<UploadedFile>,
     db_pool: Data<PgPool>,
+    storage: &Data<dyn SoundStorage>,
 ) -> Result<Vec<UploadedFile>, KekServerError> {
+    let mut failed_files = Vec::new();
     let mut transaction = db_pool.begin().await?;
     for entry in &mut files {
         if let Some(file) = &entry.sound_file {
-            match validate_audio_mime(file).await {
+            match validate_audio_mime(file, storage).await {
                 Ok(_) => {
                     file.insert(&mut transaction).await?;
+                    if let Some(manifest) = &entry.manifest {
+                        Tag::add_file_tags(&file.id, &manifest.tags, &mut transaction).await?;
+                        GuildFile::bulk_insert(
+                            &manifest.guilds,
+                            std::slice::from_ref(&file.id),
+                            &mut transaction,
+                        )
+                        .await?;
+                    }
                     entry.uploaded = true;
                 }
                 Err(e) => {
                     error!("{}", e);
-                    delete_file(file).await?;
+                    failed_files.push(file.clone());
+                    entry.error = Some(UploadError::from(&e));
                 }
             }
         }
     }
     transaction.commit().await?;
 
+    // Bytes saved for a failed file can be shared with a duplicate uploaded in the same request
+    let uploaded_keys = files
+        .iter()
+        .filter(|f| f.uploaded)
+        .filter_map(|f| f.sound_file.as_ref())
+        .map(|f| f.storage_key.clone())
+        .collect::<HashSet<String>>();
+    for file in failed_files {
+        if !uploaded_keys.contains(&file.storage_key) {
+            delete_file(&file, storage).await?;
+        }
+    }
+
     return Ok(files);
 }
 
-fn is_file_public(file_name: &str) -> bool {
-    return file_name.ends_with(PUBLIC_SUFFIX);
+/// Inserts uploaded files and starts processing bytes which aren't shared with existing files.
+async fn finish_upload(
+    files: Vec<UploadedFile>,
+    db_pool: Data<PgPool>,
+    storage: Data<dyn SoundStorage>,
+) -> Result<Vec<UploadedFile>, KekServerError> {
+    let files = insert_valid_files(files, db_pool.clone(), &storage).await?;
+
+    // Bytes shared with stored files were already processed, bytes saved by this request are
+    // processed once even if the file they were saved for failed
+    let mut created_keys = files
+        .iter()
+        .filter_map(|f| f.sound_file.as_ref())
+        .filter(|f| is_new_blob(f))
+        .map(|f| f.storage_key.clone())
+        .collect::<HashSet<String>>();
+    let processing_files = files
+        .iter()
+        .filter(|f| f.uploaded)
+        .filter_map(|f| f.sound_file.clone())
+        .filter(|f| created_keys.remove(&f.storage_key))
+        .collect();
+    spawn_processing(processing_files, db_pool, storage);
+
+    return Ok(files);
+}
+
+fn is_allowed_uploader(config: &Option<Config>, user_id: &UserId) -> bool {
+    match config {
+        Some(cfg) => cfg.get_allowed_uploaders().contains(&user_id.0.into()),
+        None => true,
+    }
+}
+
+async fn get_quota_and_usage(
+    config: &Option<Config>,
+    user_id: &UserId,
+    db_pool: &Data<PgPool>,
+) -> Result<(Quota, StorageUsage), KekServerError> {
+    let quota = match config {
+        Some(cfg) => cfg.get_user_quota(&user_id.0.into()),
+        None => Quota::default(),
+    };
+    let mut transaction = db_pool.begin().await?;
+    let usage = SoundFile::get_user_storage_usage(user_id, &mut transaction).await?;
+    transaction.commit().await?;
+    return Ok((quota, usage));
 }
 
-fn create_new_file(id: i64, user_id: UserId, field: &Field) -> SoundFile {
-    let mut name = parse_display_name(field);
-    let is_public = is_file_public(&name);
-    name.truncate(name.len() - PUBLIC_SUFFIX.len());
-    return SoundFile::new(SoundFileId(id as u64), name, Some(user_id), is_public);
+/// File with identical content, uploaded earlier in the same request or stored before.
+async fn find_existing_blob(
+    content_hash: &str,
+    uploaded_blobs: &HashMap<String, SoundFile>,
+    db_pool: &Data<PgPool>,
+) -> Result<Option<SoundFile>, KekServerError> {
+    if let Some(file) = uploaded_blobs.get(content_hash) {
+        return Ok(Some(file.clone()));
+    }
+    let mut transaction = db_pool.begin().await?;
+    let file = SoundFile::get_by_content_hash(content_hash, &mut transaction).await?;
+    transaction.commit().await?;
+    return Ok(file);
+}
+
+/// Stores the file under the key of an existing file, processing results are shared with it.
+fn share_blob(sound_file: &mut SoundFile, existing_file: SoundFile) {
+    sound_file.storage_key = existing_file.storage_key;
+    sound_file.processing_status = existing_file.processing_status;
+    sound_file.metadata = existing_file.metadata;
+}
+
+fn exceeds_max_duration(sound_file: &SoundFile) -> bool {
+    match (*MAX_DURATION_MS, sound_file.metadata.duration_ms) {
+        (Some(max_duration), Some(duration)) => duration as i64 > max_duration,
+        _ => false,
+    }
+}
+
+async fn delete_uploaded_files(
+    files: &[UploadedFile],
+    storage: &Data<dyn SoundStorage>,
+) -> Result<(), KekServerError> {
+    for file in files.iter().filter_map(|f| f.sound_file.as_ref()) {
+        delete_file(file, storage).await?;
+    }
+    return Ok(());
+}
+
+/// Options of a single uploaded file, the `manifest` part is a json array of these.
+#[derive(Clone, Debug, Deserialize)]
+struct FileManifest {
+    /// Name of the multipart field with the audio
+    field: String,
+    display_name: String,
+    #[serde(default)]
+    is_public: bool,
+    #[serde(default)]
+    tags: Vec<String>,
+    /// Guilds the file is enabled in after upload
+    #[serde(default)]
+    guilds: Vec<GuildId>,
+}
+
+async fn parse_manifest(
+    field: &mut Field,
+    authorized_user: &AuthorizedUser,
+    user_guilds_cache: &Data<UserGuildsCache>,
+    db_pool: &Data<PgPool>,
+) -> Result<HashMap<String, FileManifest>, KekServerError> {
+    let mut data = Vec::new();
+    while let Some(chunk) = field.try_next().await? {
+        data.extend_from_slice(&chunk);
+        if data.len() > MAX_MANIFEST_SIZE {
+            return Err(KekServerError::InvalidManifestError(
+                "Manifest too large".to_string(),
+            ));
+        }
+    }
+    let entries = serde_json::from_slice::<Vec<FileManifest>>(&data)
+        .map_err(|e| KekServerError::InvalidManifestError(e.to_string()))?;
+
+    let mut manifest = HashMap::new();
+    let mut transaction = db_pool.begin().await?;
+    for mut entry in entries {
+        entry.tags = Tag::normalize(&entry.tags)?;
+
+        if !entry.guilds.is_empty() {
+            Validation::is_user_in_provided_guilds(
+                authorized_user,
+                &entry.guilds,
+                user_guilds_cache,
+            )?;
+            for guild_id in &entry.guilds {
+                if Guild::get_guild_from_id(guild_id, &mut transaction)
+                    .await?
+                    .is_none()
+                {
+                    return Err(KekServerError::InvalidGuildIdError);
+                }
+                // Uploaded files are owned by the uploader
+                Validation::can_add_to_guild(
+                    authorized_user,
+                    guild_id,
+                    true,
+                    user_guilds_cache,
+                    &mut transaction,
+                )
+                .await?;
+            }
+        }
+        manifest.insert(entry.field.clone(), entry);
+    }
+    transaction.commit().await?;
+
+    return Ok(manifest);
+}
+
+fn create_new_file(
+    id: i64,
+    user_id: UserId,
+    field: &Field,
+    manifest: Option<&FileManifest>,
+) -> SoundFile {
+    let id = SoundFileId(id as u64);
+    if let Some(manifest) = manifest {
+        return SoundFile::new(
+            id,
+            manifest.display_name.clone(),
+            Some(user_id),
+            manifest.is_public,
+        );
+    }
+
+    let (name, is_public) = strip_public_suffix(parse_display_name(field));
+    return SoundFile::new(id, name, Some(user_id), is_public);
+}
+
+/// Uploads without a manifest mark public files with a suffix on the name.
+fn strip_public_suffix(name: String) -> (String, bool) {
+    match name.strip_suffix(PUBLIC_SUFFIX) {
+        Some(stripped) => return (stripped.trim_end().to_string(), true),
+        None => return (name, false),
+    }
+}
+
+/// Reason a single file failed to upload, codes are the same as in error responses.
+#[derive(Debug, Serialize)]
+struct UploadError {
+    error: &'static str,
+    description: String,
+}
+
+impl From<&KekServerError> for UploadError {
+    fn from(e: &KekServerError) -> Self {
+        return Self {
+            error: e.error_code(),
+            description: e.to_string(),
+        };
+    }
 }
 
 #[derive(Debug, Serialize)]
 struct UploadedFile {
     uploaded: bool,
     sound_file: Option<SoundFile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<UploadError>,
+    #[serde(skip)]
+    manifest: Option<FileManifest>,
+}
+
+impl UploadedFile {
+    fn new(sound_file: SoundFile, manifest: Option<FileManifest>) -> Self {
+        return Self {
+            uploaded: false,
+            sound_file: Some(sound_file),
+            error: None,
+            manifest,
+        };
+    }
+
+    fn failed(e: KekServerError) -> Self {
+        error!("{}", e);
+        return Self {
+            uploaded: false,
+            sound_file: None,
+            error: Some(UploadError::from(&e)),
+            manifest: None,
+        };
+    }
 }
 
-// TODO: full path code repeats, make nicer
-#[post("/upload", wrap = "AuthService")]
+#[post("/upload", wrap = "UserGuildsService", wrap = "AuthService")]
 pub async fn upload_file(
     mut payload: Multipart,
     snowflake: Data<Mutex<SnowflakeIdGenerator>>,
     AuthorizedUserExt(authorized_user): AuthorizedUserExt,
     config: Data<Option<Config>>,
     db_pool: Data<PgPool>,
+    storage: Data<dyn SoundStorage>,
+    user_guilds_cache: Data<UserGuildsCache>,
 ) -> Result<HttpResponse, KekServerError> {
-    match config.as_ref() {
-        Some(cfg) => {
-            if !cfg
-                .get_allowed_uploaders()
-                .contains(&authorized_user.discord_user.id.0.into())
-            {
-                return Ok(HttpResponse::Forbidden().finish());
-            }
-        }
-        None => {}
+    if !is_allowed_uploader(&config, &authorized_user.discord_user.id) {
+        return Ok(HttpResponse::Forbidden().finish());
     }
 
-    let mut uploaded_files_size = 0;
-    let mut max_file_size_exceeded = false;
+    let (quota, mut usage) =
+        get_quota_and_usage(&config, &authorized_user.discord_user.id, &db_pool).await?;
 
     let mut uploaded_files: Vec<UploadedFile> = Vec::new();
+    // Files saved by this request by content hash, so duplicates within one upload share bytes too
+    let mut uploaded_blobs: HashMap<String, SoundFile> = HashMap::new();
+    // Audio fields sent before the manifest fall back to options from their name
+    let mut manifest: HashMap<String, FileManifest> = HashMap::new();
 
+    // Failing files are reported in the response without rejecting the rest of the upload
     while let Some(mut field) = payload.try_next().await? {
+        if field.name() == MANIFEST_FIELD {
+            match parse_manifest(&mut field, &authorized_user, &user_guilds_cache, &db_pool).await {
+                Ok(parsed_manifest) => manifest = parsed_manifest,
+                Err(e) => {
+                    delete_uploaded_files(&uploaded_files, &storage).await?;
+                    return Err(e);
+                }
+            }
+            continue;
+        }
+
         if mime::AUDIO != field.content_type().type_() {
-            uploaded_files.push(UploadedFile {
-                uploaded: false,
-                sound_file: None,
-            });
+            uploaded_files.push(UploadedFile::failed(KekServerError::WrongMimeTypeError));
             continue;
         }
 
@@ -164,69 +479,592 @@ pub async fn upload_file(
             id = lock.generate();
         }
 
-        let sound_file = create_new_file(id, authorized_user.discord_user.id.clone(), &field);
-        let full_file_path = format!("{}{}", dotenv::var("SOUNDFILE_DIR")?, sound_file.id.0);
-        let mut file_handle = File::create(full_file_path).await?;
+        let file_manifest = manifest.get(field.name()).cloned();
+        let mut sound_file = create_new_file(
+            id,
+            authorized_user.discord_user.id.clone(),
+            &field,
+            file_manifest.as_ref(),
+        );
+        match SoundFile::validate_display_name(&sound_file.display_name.unwrap_or_default()) {
+            Ok(display_name) => sound_file.display_name = Some(display_name),
+            Err(e) => {
+                uploaded_files.push(UploadedFile::failed(e));
+                continue;
+            }
+        }
 
-        uploaded_files.push(UploadedFile {
-            uploaded: false,
-            sound_file: Some(sound_file),
-        });
+        let data = match read_audio_field(&mut field).await? {
+            Some(data) => data,
+            None => {
+                uploaded_files.push(UploadedFile::failed(KekServerError::FileTooLargeError));
+                continue;
+            }
+        };
+
+        sound_file.size = Some(data.len() as i64);
+        let content_hash = checksum(&data);
+        let existing_file = find_existing_blob(&content_hash, &uploaded_blobs, &db_pool).await?;
+        sound_file.content_hash = Some(content_hash.clone());
 
-        while let Some(chunk) = field.try_next().await? {
-            uploaded_files_size += chunk.len();
-            if uploaded_files_size > *MAX_FILE_SIZE {
-                max_file_size_exceeded = true;
-                break;
+        if let Some(existing_file) = existing_file {
+            share_blob(&mut sound_file, existing_file);
+        } else {
+            match probe_metadata(&sound_file.storage_key, &data).await {
+                Ok(metadata) => sound_file.metadata = metadata,
+                Err(e) => {
+                    uploaded_files.push(UploadedFile::failed(e));
+                    continue;
+                }
             }
-            file_handle.write_all(&chunk).await?;
         }
+
+        if exceeds_max_duration(&sound_file) {
+            uploaded_files.push(UploadedFile::failed(KekServerError::FileTooLongError(
+                MAX_DURATION_MS.unwrap_or_default(),
+            )));
+            continue;
+        }
+
+        let mut new_usage = usage.clone();
+        new_usage.bytes += data.len() as i64;
+        new_usage.files += 1;
+        if quota.is_exceeded_by(&new_usage) {
+            uploaded_files.push(UploadedFile::failed(KekServerError::QuotaExceededError));
+            continue;
+        }
+        usage = new_usage;
+
+        if is_new_blob(&sound_file) {
+            storage.save(&sound_file.storage_key, &data).await?;
+            uploaded_blobs.insert(content_hash, sound_file.clone());
+        }
+        uploaded_files.push(UploadedFile::new(sound_file, file_manifest));
     }
 
-    if max_file_size_exceeded {
-        for UploadedFile { sound_file, .. } in uploaded_files {
-            if let Some(file) = sound_file {
-                delete_file(&file).await?;
+    let uploaded_files = finish_upload(uploaded_files, db_pool, storage).await?;
+
+    return Ok(HttpResponse::Ok().json(uploaded_files));
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewUploadSession {
+    display_name: String,
+    #[serde(default)]
+    is_public: bool,
+    /// Total size of the file in bytes
+    size: i64,
+}
+
+async fn discard_upload_session(
+    session: &UploadSession,
+    mut transaction: Transaction<'_, Postgres>,
+    storage: &Data<dyn SoundStorage>,
+) -> Result<(), KekServerError> {
+    UploadSession::delete(&session.id, &mut transaction).await?;
+    transaction.commit().await?;
+    // Nothing is saved before the first append
+    if session.uploaded_size > 0 {
+        storage.delete(&upload_key(&session.id)).await?;
+    }
+    return Ok(());
+}
+
+/// Starts a resumable upload, its bytes are sent with `append_upload` and turned into a file with `finalize_upload`.
+#[post("/uploads", wrap = "AuthService")]
+pub async fn create_upload_session(
+    Json(new_session): Json<NewUploadSession>,
+    snowflake: Data<Mutex<SnowflakeIdGenerator>>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    config: Data<Option<Config>>,
+    db_pool: Data<PgPool>,
+) -> Result<HttpResponse, KekServerError> {
+    let user_id = &authorized_user.discord_user.id;
+    if !is_allowed_uploader(&config, user_id) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+    if new_session.size <= 0 {
+        return Err(KekServerError::Other(
+            "Upload size must be positive".to_string(),
+        ));
+    }
+    if new_session.size as usize > *MAX_FILE_SIZE {
+        return Err(KekServerError::FileTooLargeError);
+    }
+    let display_name = SoundFile::validate_display_name(&new_session.display_name)?;
+
+    let (quota, mut usage) = get_quota_and_usage(&config, user_id, &db_pool).await?;
+    usage.bytes += new_session.size;
+    usage.files += 1;
+    if quota.is_exceeded_by(&usage) {
+        return Err(KekServerError::QuotaExceededError);
+    }
+
+    let id;
+    {
+        let mut lock = snowflake.lock().unwrap();
+        id = lock.generate();
+    }
+
+    let mut transaction = db_pool.begin().await?;
+    let session = UploadSession::insert(
+        &SoundFileId(id as u64),
+        user_id,
+        &display_name,
+        new_session.is_public,
+        new_session.size,
+        *UPLOAD_SESSION_EXPIRY_HOURS,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Ok().json(session));
+}
+
+#[get("/uploads/{upload_id}", wrap = "AuthService")]
+pub async fn get_upload_session(
+    upload_id: Path<SoundFileId>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+) -> Result<HttpResponse, KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+    let session = UploadSession::get_for_update(
+        &upload_id,
+        &authorized_user.discord_user.id,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    match session {
+        Some(session) => return Ok(HttpResponse::Ok().json(session)),
+        None => return Err(KekServerError::UploadSessionNotFoundError),
+    }
+}
+
+/// Appends the request body to the upload, `Upload-Offset` header must match the uploaded size.
+///
+/// Uploaded bytes are rewritten on every append, which is fine for files limited by `MAX_FILE_SIZE`.
+#[patch("/uploads/{upload_id}", wrap = "AuthService")]
+pub async fn append_upload(
+    upload_id: Path<SoundFileId>,
+    mut payload: Payload,
+    req: HttpRequest,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    storage: Data<dyn SoundStorage>,
+) -> Result<HttpResponse, KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+    let session = match UploadSession::get_for_update(
+        &upload_id,
+        &authorized_user.discord_user.id,
+        &mut transaction,
+    )
+    .await?
+    {
+        Some(session) => session,
+        None => return Err(KekServerError::UploadSessionNotFoundError),
+    };
+
+    let offset = match req.headers().get(UPLOAD_OFFSET_HEADER) {
+        Some(offset) => offset.to_str()?.parse::<i64>()?,
+        None => {
+            return Err(KekServerError::UploadOffsetMismatchError(
+                session.uploaded_size,
+            ))
+        }
+    };
+    if offset != session.uploaded_size {
+        return Err(KekServerError::UploadOffsetMismatchError(
+            session.uploaded_size,
+        ));
+    }
+
+    let key = upload_key(&session.id);
+    let mut data = Vec::new();
+    if session.uploaded_size > 0 {
+        data = storage.read(&key).await?;
+        // Bytes of an append whose transaction failed are dropped
+        data.truncate(session.uploaded_size as usize);
+    }
+
+    let mut appended_size = 0;
+    while let Some(chunk) = payload.try_next().await? {
+        appended_size += chunk.len() as i64;
+        if session.uploaded_size + appended_size > session.size {
+            return Err(KekServerError::FileTooLargeError);
+        }
+        data.extend_from_slice(&chunk);
+    }
+
+    storage.save(&key, &data).await?;
+    let session = session
+        .append(
+            appended_size,
+            *UPLOAD_SESSION_EXPIRY_HOURS,
+            &mut transaction,
+        )
+        .await?;
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Ok().json(session));
+}
+
+/// Creates a sound file from a completely uploaded session, the file gets the id of the session.
+#[post("/uploads/{upload_id}/finalize", wrap = "AuthService")]
+pub async fn finalize_upload(
+    upload_id: Path<SoundFileId>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    config: Data<Option<Config>>,
+    db_pool: Data<PgPool>,
+    storage: Data<dyn SoundStorage>,
+) -> Result<HttpResponse, KekServerError> {
+    let user_id = &authorized_user.discord_user.id;
+    let mut transaction = db_pool.begin().await?;
+    let session = match UploadSession::get_for_update(&upload_id, user_id, &mut transaction).await?
+    {
+        Some(session) => session,
+        None => return Err(KekServerError::UploadSessionNotFoundError),
+    };
+    if session.uploaded_size != session.size {
+        return Err(KekServerError::UploadIncompleteError);
+    }
+
+    let mut data = storage.read(&upload_key(&session.id)).await?;
+    data.truncate(session.size as usize);
+
+    let mut sound_file = SoundFile::new(
+        session.id.clone(),
+        session.display_name.clone(),
+        Some(user_id.clone()),
+        session.is_public,
+    );
+    sound_file.size = Some(data.len() as i64);
+    let content_hash = checksum(&data);
+    let existing_file = find_existing_blob(&content_hash, &HashMap::new(), &db_pool).await?;
+    sound_file.content_hash = Some(content_hash);
+
+    if let Some(existing_file) = existing_file {
+        share_blob(&mut sound_file, existing_file);
+    } else {
+        match probe_metadata(&sound_file.storage_key, &data).await {
+            Ok(metadata) => sound_file.metadata = metadata,
+            Err(e) => {
+                discard_upload_session(&session, transaction, &storage).await?;
+                return Ok(HttpResponse::Ok().json(UploadedFile::failed(e)));
             }
         }
-        return Err(KekServerError::FileTooLargeError);
     }
 
-    let uploaded_files = insert_valid_files(uploaded_files, db_pool).await?;
+    if exceeds_max_duration(&sound_file) {
+        discard_upload_session(&session, transaction, &storage).await?;
+        return Err(KekServerError::FileTooLongError(
+            MAX_DURATION_MS.unwrap_or_default(),
+        ));
+    }
 
-    return Ok(HttpResponse::Ok().json(uploaded_files));
+    // Quota could have been used up by other uploads since the session was created
+    let (quota, mut usage) = get_quota_and_usage(&config, user_id, &db_pool).await?;
+    usage.bytes += data.len() as i64;
+    usage.files += 1;
+    if quota.is_exceeded_by(&usage) {
+        return Err(KekServerError::QuotaExceededError);
+    }
+
+    if is_new_blob(&sound_file) {
+        storage.save(&sound_file.storage_key, &data).await?;
+    }
+    let uploaded_files = finish_upload(
+        vec![UploadedFile::new(sound_file, None)],
+        db_pool.clone(),
+        storage.clone(),
+    )
+    .await?;
+    discard_upload_session(&session, transaction, &storage).await?;
+
+    return Ok(HttpResponse::Ok().json(&uploaded_files[0]));
+}
+
+#[delete("/uploads/{upload_id}", wrap = "AuthService")]
+pub async fn cancel_upload(
+    upload_id: Path<SoundFileId>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    storage: Data<dyn SoundStorage>,
+) -> Result<HttpResponse, KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+    let session = match UploadSession::get_for_update(
+        &upload_id,
+        &authorized_user.discord_user.id,
+        &mut transaction,
+    )
+    .await?
+    {
+        Some(session) => session,
+        None => return Err(KekServerError::UploadSessionNotFoundError),
+    };
+    discard_upload_session(&session, transaction, &storage).await?;
+
+    return Ok(HttpResponse::Ok().json(session));
 }
 
 #[derive(Debug, Deserialize)]
 pub struct PublicFilesQueryParams {
     limit: Option<i64>,
+    /// Page of the search results, listings without a search are paginated with the cursor
     page: Option<i64>,
     search_query: Option<String>,
+    /// Comma separated list of tags
+    tags: Option<String>,
+    tag_match: Option<TagMatch>,
+    sort: Option<FileSort>,
+    cursor: Option<String>,
+}
+
+/// Replaces the audio of an owned file, its id, guilds and tags stay the same.
+/// The replaced audio is kept as the previous version until the next replacement.
+#[put("/{file_id}/audio", wrap = "AuthService")]
+pub async fn replace_file_audio(
+    file_id: Path<SoundFileId>,
+    mut payload: Multipart,
+    snowflake: Data<Mutex<SnowflakeIdGenerator>>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    config: Data<Option<Config>>,
+    db_pool: Data<PgPool>,
+    storage: Data<dyn SoundStorage>,
+) -> Result<HttpResponse, KekServerError> {
+    let user_id = &authorized_user.discord_user.id;
+    if !is_allowed_uploader(&config, user_id) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    // Only the first part is used, it has to be the audio file
+    let mut field = match payload.try_next().await? {
+        Some(field) => field,
+        None => return Err(KekServerError::NoFilesUploadedError),
+    };
+    if mime::AUDIO != field.content_type().type_() {
+        return Err(KekServerError::WrongMimeTypeError);
+    }
+    let data = match read_audio_field(&mut field).await? {
+        Some(data) => data,
+        None => return Err(KekServerError::FileTooLargeError),
+    };
+    validate_audio_data(data.clone()).await?;
+
+    let (quota, mut usage) = get_quota_and_usage(&config, user_id, &db_pool).await?;
+    let mut transaction = db_pool.begin().await?;
+    let current_file =
+        match SoundFile::get_user_file_for_update(&file_id, user_id, &mut transaction).await? {
+            Some(file) => file,
+            None => return Err(KekServerError::InvalidFileIdError),
+        };
+    // Previous version would keep a status which is never updated
+    if current_file.processing_status == ProcessingStatus::Pending {
+        return Err(KekServerError::FileProcessingPendingError);
+    }
+
+    let mut sound_file = current_file.clone();
+    sound_file.size = Some(data.len() as i64);
+    // Trim points of the replaced audio don't fit the new audio, they are kept with the previous version
+    sound_file.start_ms = None;
+    sound_file.end_ms = None;
+    let content_hash = checksum(&data);
+    let existing_file = find_existing_blob(&content_hash, &HashMap::new(), &db_pool).await?;
+    sound_file.content_hash = Some(content_hash);
+
+    let is_new_blob = existing_file.is_none();
+    if let Some(existing_file) = existing_file {
+        share_blob(&mut sound_file, existing_file);
+    } else {
+        let id;
+        {
+            let mut lock = snowflake.lock().unwrap();
+            id = lock.generate();
+        }
+        sound_file.storage_key = sound_file_key(&SoundFileId(id as u64));
+        sound_file.processing_status = ProcessingStatus::Pending;
+        sound_file.metadata = probe_metadata(&sound_file.storage_key, &data).await?;
+    }
+
+    if exceeds_max_duration(&sound_file) {
+        return Err(KekServerError::FileTooLongError(
+            MAX_DURATION_MS.unwrap_or_default(),
+        ));
+    }
+
+    // The current audio stays stored as the previous version, the older version is dropped
+    let previous_version = FileVersion::get(&file_id, &mut transaction).await?;
+    usage.bytes += data.len() as i64;
+    usage.bytes -= previous_version
+        .as_ref()
+        .and_then(|v| v.size)
+        .unwrap_or_default();
+    if quota.is_exceeded_by(&usage) {
+        return Err(KekServerError::QuotaExceededError);
+    }
+
+    if is_new_blob {
+        storage.save(&sound_file.storage_key, &data).await?;
+    }
+
+    let dropped_version = FileVersion::delete(&file_id, &mut transaction).await?;
+    FileVersion::insert_from_file(&file_id, &mut transaction).await?;
+    let sound_file = sound_file.set_audio(None, &mut transaction).await?;
+    FileWaveform::delete(&file_id, &mut transaction).await?;
+    let dropped_blobs = dropped_version
+        .into_iter()
+        .map(|v| (v.storage_key, v.processing_status))
+        .collect::<Vec<(String, ProcessingStatus)>>();
+    let dropped_keys = dropped_blobs
+        .iter()
+        .map(|(key, _)| key.clone())
+        .collect::<Vec<String>>();
+    let unreferenced_keys =
+        SoundFile::get_unreferenced_storage_keys(&dropped_keys, &mut transaction).await?;
+    transaction.commit().await?;
+
+    delete_blobs(&unreferenced_keys, &dropped_blobs, &storage).await;
+    if is_new_blob {
+        spawn_processing(vec![sound_file.clone()], db_pool, storage);
+    }
+
+    return Ok(HttpResponse::Ok().json(sound_file));
+}
+
+/// Swaps the audio of an owned file with its previous version, so a rollback can be undone by another rollback.
+#[post("/{file_id}/audio/rollback", wrap = "AuthService")]
+pub async fn rollback_file_audio(
+    file_id: Path<SoundFileId>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+) -> Result<HttpResponse, KekServerError> {
+    let user_id = &authorized_user.discord_user.id;
+    let mut transaction = db_pool.begin().await?;
+    let current_file =
+        match SoundFile::get_user_file_for_update(&file_id, user_id, &mut transaction).await? {
+            Some(file) => file,
+            None => return Err(KekServerError::InvalidFileIdError),
+        };
+    if current_file.processing_status == ProcessingStatus::Pending {
+        return Err(KekServerError::FileProcessingPendingError);
+    }
+
+    let previous_version = match FileVersion::delete(&file_id, &mut transaction).await? {
+        Some(version) => version,
+        None => return Err(KekServerError::NoPreviousVersionError),
+    };
+    FileVersion::insert_from_file(&file_id, &mut transaction).await?;
+    let sound_file = previous_version
+        .restore(current_file)
+        .set_audio(previous_version.checksum.as_deref(), &mut transaction)
+        .await?;
+    FileWaveform::delete(&file_id, &mut transaction).await?;
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Ok().json(sound_file));
+}
+
+/// Copies a public file of another user to the user's library, the copy shares the stored audio
+/// so it stays available if the original file is made private or deleted.
+#[post("/{file_id}/fork", wrap = "AuthService")]
+pub async fn fork_file(
+    file_id: Path<SoundFileId>,
+    snowflake: Data<Mutex<SnowflakeIdGenerator>>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    config: Data<Option<Config>>,
+    db_pool: Data<PgPool>,
+) -> Result<HttpResponse, KekServerError> {
+    let user_id = &authorized_user.discord_user.id;
+    if !is_allowed_uploader(&config, user_id) {
+        return Ok(HttpResponse::Forbidden().finish());
+    }
+
+    let (quota, mut usage) = get_quota_and_usage(&config, user_id, &db_pool).await?;
+    let id;
+    {
+        let mut lock = snowflake.lock().unwrap();
+        id = lock.generate();
+    }
+
+    let mut transaction = db_pool.begin().await?;
+    let sound_file = match SoundFile::fork(
+        &SoundFileId(id as u64),
+        &file_id,
+        user_id,
+        &mut transaction,
+    )
+    .await?
+    {
+        Some(file) => file,
+        None => return Err(KekServerError::InvalidFileIdError),
+    };
+    // Forks count towards the quota like uploads even though the audio is shared
+    usage.bytes += sound_file.size.unwrap_or_default();
+    usage.files += 1;
+    if quota.is_exceeded_by(&usage) {
+        return Err(KekServerError::QuotaExceededError);
+    }
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Created().json(sound_file));
 }
 
 #[get("/public", wrap = "AuthService")]
 pub async fn get_public_files(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
     Query(query): Query<PublicFilesQueryParams>,
     db_pool: Data<PgPool>,
 ) -> Result<HttpResponse, KekServerError> {
+    let tag_filter = TagFilter::from_query(query.tags.as_deref(), query.tag_match)?;
     let mut transaction = db_pool.begin().await?;
-    let files;
-    if let Some(search_query) = query.search_query {
-        files = SoundFile::get_public_files_search(
+    let mut files;
+    if let Some(search_query) = &query.search_query {
+        files = SoundFile::search(
+            &FileScope::Public,
             query.limit.unwrap_or(MAX_LIMIT),
             query.page.unwrap_or(1),
             search_query,
+            &tag_filter,
             &mut transaction,
         )
         .await?;
     } else {
-        files = SoundFile::get_public_files(
+        let cursor = query
+            .cursor
+            .as_deref()
+            .map(FileCursor::decode)
+            .transpose()?;
+        let page = SoundFile::get_files_page(
+            &FileScope::Public,
+            query.sort.unwrap_or_default(),
+            cursor.as_ref(),
             query.limit.unwrap_or(MAX_LIMIT),
-            query.page.unwrap_or(1),
+            &tag_filter,
             &mut transaction,
         )
         .await?;
+        files = FilesAndCount {
+            count: None,
+            max: MAX_LIMIT,
+            files: page.files,
+            tag_counts: Vec::new(),
+            suggestions: Vec::new(),
+            next: page.next,
+            prev: page.prev,
+        };
     }
+    Tag::attach_to_files(files.files.iter_mut().collect(), &mut transaction).await?;
+    Favorite::attach_to_files(
+        &authorized_user.discord_user.id,
+        files.files.iter_mut().collect(),
+        &mut transaction,
+    )
+    .await?;
+    files.tag_counts =
+        Tag::get_public_counts(query.search_query.as_deref(), &tag_filter, &mut transaction)
+            .await?;
     transaction.commit().await?;
     return Ok(HttpResponse::Ok().json(files));
 }
@@ -236,6 +1074,8 @@ pub async fn preview(
     db_pool: Data<PgPool>,
     path: Path<(UserId, SoundFileId)>,
     req: HttpRequest,
+    storage: Data<dyn SoundStorage>,
+    trimmed_audio_cache: Data<TrimmedAudioCache>,
 ) -> Result<HttpResponse, KekServerError> {
     let (user_id, file_id) = path.into_inner();
     let mut transaction = db_pool.begin().await?;
@@ -243,13 +1083,109 @@ pub async fn preview(
     transaction.commit().await?;
 
     if let Some(file) = file {
-        return Ok(actix_files::NamedFile::open(format!(
-            "{}{}",
-            dotenv::var("SOUNDFILE_DIR")?,
-            file.id.0
-        ))?
-        .into_response(&req));
+        let key = file.storage_key;
+        if file.start_ms.is_some() || file.end_ms.is_some() {
+            let cache_key = (key.clone(), file.start_ms, file.end_ms);
+            if let Some(trimmed) = trimmed_audio_cache.get(&cache_key) {
+                return Ok(HttpResponse::Ok()
+                    .content_type("audio/ogg")
+                    .body(trimmed.to_vec()));
+            }
+            let data = storage.read(&key).await?;
+            let trimmed = trim_audio(&key, &data, file.start_ms, file.end_ms).await?;
+            // Pending files are overwritten with the processed audio under the same key
+            if file.processing_status != ProcessingStatus::Pending {
+                trimmed_audio_cache
+                    .insert(cache_key, Arc::new(trimmed.clone()))
+                    .await;
+            }
+            return Ok(HttpResponse::Ok().content_type("audio/ogg").body(trimmed));
+        }
+
+        return stored_audio_response(&key, &storage, &req).await;
     } else {
         return Err(KekServerError::PreviewFileNotFound);
     }
 }
+
+async fn stored_audio_response(
+    key: &str,
+    storage: &Data<dyn SoundStorage>,
+    req: &HttpRequest,
+) -> Result<HttpResponse, KekServerError> {
+    if let Some(full_file_path) = storage.local_path(key) {
+        return Ok(actix_files::NamedFile::open(full_file_path)?.into_response(req));
+    }
+
+    let data = storage.read(key).await?;
+    let content_type = infer::get(&data)
+        .map(|t| t.mime_type())
+        .unwrap_or("application/octet-stream");
+    return Ok(HttpResponse::Ok().content_type(content_type).body(data));
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SignedAudioQueryParams {
+    pub expires: i64,
+    pub signature: String,
+}
+
+/// Stored audio for the bot, the path is signed in play messages.
+#[get("/audio/{storage_key}")]
+pub async fn signed_audio(
+    storage_key: Path<String>,
+    query: Query<SignedAudioQueryParams>,
+    req: HttpRequest,
+    storage: Data<dyn SoundStorage>,
+) -> Result<HttpResponse, KekServerError> {
+    let key = storage_key.into_inner();
+    if !is_valid_audio_signature(&key, query.expires, &query.signature) {
+        return Err(KekServerError::InvalidAudioSignatureError);
+    }
+    return stored_audio_response(&key, &storage, &req).await;
+}
+
+#[get("/waveform/{file_id}", wrap = "AuthService")]
+pub async fn waveform(
+    db_pool: Data<PgPool>,
+    file_id: Path<SoundFileId>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    storage: Data<dyn SoundStorage>,
+) -> Result<HttpResponse, KekServerError> {
+    let file_id = file_id.into_inner();
+    let mut transaction = db_pool.begin().await?;
+    let file =
+        match SoundFile::get_file(&file_id, &authorized_user.discord_user.id, &mut transaction)
+            .await?
+        {
+            Some(file) => file,
+            None => return Err(KekServerError::PreviewFileNotFound),
+        };
+    let waveform = FileWaveform::get(&file_id, &mut transaction).await?;
+    transaction.commit().await?;
+
+    // Files uploaded before waveforms were introduced get theirs generated on first request
+    let waveform = match waveform {
+        Some(waveform) => waveform,
+        None => generate_waveform(&file_id, &file.storage_key, &storage, &db_pool).await?,
+    };
+
+    return Ok(HttpResponse::Ok().json(waveform));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::strip_public_suffix;
+
+    #[test]
+    fn test_strip_public_suffix() {
+        assert_eq!(
+            strip_public_suffix("airhorn_p".to_string()),
+            ("airhorn".to_string(), true)
+        );
+        assert_eq!(
+            strip_public_suffix("airhorn".to_string()),
+            ("airhorn".to_string(), false)
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use actix_web::{
-    delete, get, post,
-    web::{scope, Data, Json, Path, ServiceConfig},
+    delete, get, patch, post, put,
+    web::{scope, Data, Json, Path, Query, ServiceConfig},
     HttpResponse,
 };
 use serde::Deserialize;
@@ -10,11 +10,22 @@ use crate::{
     error::errors::KekServerError,
     middleware::{auth_middleware::AuthService, user_guilds_middleware::UserGuildsService},
     models::{
-        guild_file::GuildFile,
-        ids::{GuildId, SoundFileId},
-        sound_file::SoundFile,
+        favorite::Favorite,
+        guild::Guild,
+        guild_file::{GuildFile, GuildFilesPage},
+        guild_manager::GuildManager,
+        guild_role::{GuildAction, GuildRolePermission},
+        guild_settings::GuildSettings,
+        ids::{ChannelId, GuildId, RoleId, SoundFileId, UserId},
+        sound_file::{
+            FileCursor, FileListQueryParams, FileScope, SearchQueryParams, SoundFile, MAX_LIMIT,
+        },
+        tag::{Tag, TagFilter, TagQueryParams},
+    },
+    utils::{
+        auth::AuthorizedUserExt, cache::UserGuildsCache, deserialize_present,
+        validation::Validation,
     },
-    utils::{auth::AuthorizedUserExt, cache::UserGuildsCache, validation::Validation},
 };
 
 type GuildFileIds = Path<(GuildId, SoundFileId)>;
@@ -27,6 +38,16 @@ pub fn config(cfg: &mut ServiceConfig) {
             .service(add_sound_to_guild)
             .service(delete_sound_from_guild)
             .service(get_guild_files)
+            .service(get_guild_file_tags)
+            .service(search_guild_files)
+            .service(get_guild_settings)
+            .service(update_guild_settings)
+            .service(get_guild_roles)
+            .service(update_guild_role)
+            .service(delete_guild_role)
+            .service(get_guild_managers)
+            .service(add_guild_manager)
+            .service(remove_guild_manager)
             .service(bulk_enable),
     );
 }
@@ -49,6 +70,20 @@ pub async fn add_sound_to_guild(
         &mut transaction,
     )
     .await?;
+    let is_own_file =
+        SoundFile::get_file(&file_id, &authorized_user.discord_user.id, &mut transaction)
+            .await?
+            .and_then(|f| f.owner)
+            .as_ref()
+            == Some(&authorized_user.discord_user.id);
+    Validation::can_add_to_guild(
+        &authorized_user,
+        &guild_id,
+        is_own_file,
+        &user_guilds_cache,
+        &mut transaction,
+    )
+    .await?;
     let guild_file = GuildFile::insert_guild_file(&guild_id, &file_id, &mut transaction).await?;
     transaction.commit().await?;
     return Ok(HttpResponse::Created().json(guild_file));
@@ -63,11 +98,16 @@ pub async fn delete_sound_from_guild(
 ) -> Result<HttpResponse, KekServerError> {
     let (guild_id, file_id) = path.into_inner();
     Validation::is_user_in_guild(&authorized_user, &guild_id, &user_guilds_cache)?;
-    if !Validation::has_permissions(&authorized_user, &guild_id, &user_guilds_cache)? {
-        return Err(KekServerError::Other("No permissions".to_string()));
-    }
 
     let mut transaction = db_pool.begin().await?;
+    Validation::is_action_allowed(
+        &authorized_user,
+        &guild_id,
+        GuildAction::Remove,
+        &user_guilds_cache,
+        &mut transaction,
+    )
+    .await?;
     let guild_file = GuildFile::delete_guild_file(&guild_id, &file_id, &mut transaction).await?;
     transaction.commit().await?;
 
@@ -80,17 +120,368 @@ pub async fn get_guild_files(
     AuthorizedUserExt(authorized_user): AuthorizedUserExt,
     guild_id: Path<GuildId>,
     user_guilds_cache: Data<UserGuildsCache>,
+    query: Query<FileListQueryParams>,
+) -> Result<HttpResponse, KekServerError> {
+    let guild_id = guild_id.into_inner();
+    Validation::is_user_in_guild(&authorized_user, &guild_id, &user_guilds_cache)?;
+    let tag_filter = TagFilter::from_query(query.tags.as_deref(), query.tag_match)?;
+
+    let mut transaction = db_pool.begin().await?;
+    let mut page = if query.is_paginated() {
+        let cursor = query
+            .cursor
+            .as_deref()
+            .map(FileCursor::decode)
+            .transpose()?;
+        let page = SoundFile::get_files_page(
+            &FileScope::Guild(guild_id.clone()),
+            query.sort.unwrap_or_default(),
+            cursor.as_ref(),
+            query.limit.unwrap_or(MAX_LIMIT),
+            &tag_filter,
+            &mut transaction,
+        )
+        .await?;
+        GuildFilesPage {
+            files: GuildFile::from_files(&guild_id, page.files, &mut transaction).await?,
+            next: page.next,
+            prev: page.prev,
+        }
+    } else {
+        GuildFilesPage {
+            files: GuildFile::get_guild_files(&guild_id, &tag_filter, &mut transaction).await?,
+            next: None,
+            prev: None,
+        }
+    };
+    Tag::attach_to_files(
+        page.files
+            .iter_mut()
+            .filter_map(|f| f.sound_file.as_mut())
+            .collect(),
+        &mut transaction,
+    )
+    .await?;
+    Favorite::attach_to_files(
+        &authorized_user.discord_user.id,
+        page.files
+            .iter_mut()
+            .filter_map(|f| f.sound_file.as_mut())
+            .collect(),
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Ok().json(page));
+}
+
+/// Tag counts of the guild's files matching the tag filter.
+#[get("/{guild_id}/tags")]
+pub async fn get_guild_file_tags(
+    db_pool: Data<PgPool>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    guild_id: Path<GuildId>,
+    user_guilds_cache: Data<UserGuildsCache>,
+    query: Query<TagQueryParams>,
 ) -> Result<HttpResponse, KekServerError> {
     let guild_id = guild_id.into_inner();
     Validation::is_user_in_guild(&authorized_user, &guild_id, &user_guilds_cache)?;
+    let tag_filter = TagFilter::from_query(query.tags.as_deref(), query.tag_match)?;
 
     let mut transaction = db_pool.begin().await?;
-    let files = GuildFile::get_guild_files(&guild_id, &mut transaction).await?;
+    let tag_counts = Tag::get_guild_counts(&guild_id, &tag_filter, &mut transaction).await?;
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Ok().json(tag_counts));
+}
+
+/// Ranked search in files enabled in the guild.
+#[get("/{guild_id}/search")]
+pub async fn search_guild_files(
+    db_pool: Data<PgPool>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    guild_id: Path<GuildId>,
+    user_guilds_cache: Data<UserGuildsCache>,
+    query: Query<SearchQueryParams>,
+) -> Result<HttpResponse, KekServerError> {
+    let guild_id = guild_id.into_inner();
+    Validation::is_user_in_guild(&authorized_user, &guild_id, &user_guilds_cache)?;
+    let tag_filter = TagFilter::from_query(query.tags.as_deref(), query.tag_match)?;
+
+    let mut transaction = db_pool.begin().await?;
+    let mut files = SoundFile::search(
+        &FileScope::Guild(guild_id),
+        query.limit.unwrap_or(MAX_LIMIT),
+        query.page.unwrap_or(1),
+        &query.search_query,
+        &tag_filter,
+        &mut transaction,
+    )
+    .await?;
+    Tag::attach_to_files(files.files.iter_mut().collect(), &mut transaction).await?;
+    Favorite::attach_to_files(
+        &authorized_user.discord_user.id,
+        files.files.iter_mut().collect(),
+        &mut transaction,
+    )
+    .await?;
     transaction.commit().await?;
 
     return Ok(HttpResponse::Ok().json(files));
 }
 
+#[get("/{guild_id}/settings")]
+pub async fn get_guild_settings(
+    db_pool: Data<PgPool>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    guild_id: Path<GuildId>,
+    user_guilds_cache: Data<UserGuildsCache>,
+) -> Result<HttpResponse, KekServerError> {
+    let guild_id = guild_id.into_inner();
+    Validation::is_user_in_guild(&authorized_user, &guild_id, &user_guilds_cache)?;
+
+    let mut transaction = db_pool.begin().await?;
+    let settings = GuildSettings::get(&guild_id, &mut transaction).await?;
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Ok().json(settings));
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GuildSettingsUpdate {
+    pub volume: Option<i16>,
+    pub max_queue_length: Option<i16>,
+    pub members_can_add_public_files: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub default_voice_channel_id: Option<Option<ChannelId>>,
+}
+
+/// Only fields present in the payload are changed, `null` removes the default voice channel.
+#[patch("/{guild_id}/settings")]
+pub async fn update_guild_settings(
+    db_pool: Data<PgPool>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    guild_id: Path<GuildId>,
+    user_guilds_cache: Data<UserGuildsCache>,
+    Json(update): Json<GuildSettingsUpdate>,
+) -> Result<HttpResponse, KekServerError> {
+    let guild_id = guild_id.into_inner();
+    Validation::is_user_in_guild(&authorized_user, &guild_id, &user_guilds_cache)?;
+    let volume = update
+        .volume
+        .map(GuildSettings::validate_volume)
+        .transpose()?;
+    let max_queue_length = update
+        .max_queue_length
+        .map(GuildSettings::validate_max_queue_length)
+        .transpose()?;
+
+    let mut transaction = db_pool.begin().await?;
+    Validation::is_action_allowed(
+        &authorized_user,
+        &guild_id,
+        GuildAction::Manage,
+        &user_guilds_cache,
+        &mut transaction,
+    )
+    .await?;
+    if Guild::get_guild_from_id(&guild_id, &mut transaction)
+        .await?
+        .is_none()
+    {
+        return Err(KekServerError::InvalidGuildIdError);
+    }
+    let settings = GuildSettings::update(
+        &guild_id,
+        volume,
+        max_queue_length,
+        update.members_can_add_public_files,
+        update.default_voice_channel_id,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Ok().json(settings));
+}
+
+/// Discord roles with configured permissions in the guild.
+#[get("/{guild_id}/roles")]
+pub async fn get_guild_roles(
+    db_pool: Data<PgPool>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    guild_id: Path<GuildId>,
+    user_guilds_cache: Data<UserGuildsCache>,
+) -> Result<HttpResponse, KekServerError> {
+    let guild_id = guild_id.into_inner();
+    Validation::is_user_in_guild(&authorized_user, &guild_id, &user_guilds_cache)?;
+
+    let mut transaction = db_pool.begin().await?;
+    let permissions =
+        GuildRolePermission::get_guild_permissions(&guild_id, &mut transaction).await?;
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Ok().json(permissions));
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RolePermissionUpdate {
+    #[serde(default)]
+    pub can_play: bool,
+    #[serde(default)]
+    pub can_add: bool,
+    #[serde(default)]
+    pub can_remove: bool,
+    #[serde(default)]
+    pub can_manage: bool,
+}
+
+/// Only users with permissions in the guild can configure roles.
+#[put("/{guild_id}/roles/{role_id}")]
+pub async fn update_guild_role(
+    db_pool: Data<PgPool>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    path: Path<(GuildId, RoleId)>,
+    user_guilds_cache: Data<UserGuildsCache>,
+    Json(update): Json<RolePermissionUpdate>,
+) -> Result<HttpResponse, KekServerError> {
+    let (guild_id, role_id) = path.into_inner();
+    Validation::is_user_in_guild(&authorized_user, &guild_id, &user_guilds_cache)?;
+
+    let mut transaction = db_pool.begin().await?;
+    if !Validation::has_permissions(
+        &authorized_user,
+        &guild_id,
+        &user_guilds_cache,
+        &mut transaction,
+    )
+    .await?
+    {
+        return Err(KekServerError::Other("No permissions".to_string()));
+    }
+    if Guild::get_guild_from_id(&guild_id, &mut transaction)
+        .await?
+        .is_none()
+    {
+        return Err(KekServerError::InvalidGuildIdError);
+    }
+    let permission = GuildRolePermission {
+        guild_id,
+        role_id,
+        can_play: update.can_play,
+        can_add: update.can_add,
+        can_remove: update.can_remove,
+        can_manage: update.can_manage,
+    };
+    permission.upsert(&mut transaction).await?;
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Ok().json(permission));
+}
+
+#[delete("/{guild_id}/roles/{role_id}")]
+pub async fn delete_guild_role(
+    db_pool: Data<PgPool>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    path: Path<(GuildId, RoleId)>,
+    user_guilds_cache: Data<UserGuildsCache>,
+) -> Result<HttpResponse, KekServerError> {
+    let (guild_id, role_id) = path.into_inner();
+    Validation::is_user_in_guild(&authorized_user, &guild_id, &user_guilds_cache)?;
+
+    let mut transaction = db_pool.begin().await?;
+    if !Validation::has_permissions(
+        &authorized_user,
+        &guild_id,
+        &user_guilds_cache,
+        &mut transaction,
+    )
+    .await?
+    {
+        return Err(KekServerError::Other("No permissions".to_string()));
+    }
+    if !GuildRolePermission::delete(&guild_id, &role_id, &mut transaction).await? {
+        return Err(KekServerError::InvalidRoleIdError);
+    }
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Ok().finish());
+}
+
+/// Soundboard managers have the same permissions in the guild as users with Discord permissions.
+#[get("/{guild_id}/managers")]
+pub async fn get_guild_managers(
+    db_pool: Data<PgPool>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    guild_id: Path<GuildId>,
+    user_guilds_cache: Data<UserGuildsCache>,
+) -> Result<HttpResponse, KekServerError> {
+    let guild_id = guild_id.into_inner();
+    Validation::is_user_in_guild(&authorized_user, &guild_id, &user_guilds_cache)?;
+
+    let mut transaction = db_pool.begin().await?;
+    let managers = GuildManager::get_guild_managers(&guild_id, &mut transaction).await?;
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Ok().json(managers));
+}
+
+/// Only users with Discord permissions can change the managers, managers can't add other managers.
+#[post("/{guild_id}/managers/{user_id}")]
+pub async fn add_guild_manager(
+    db_pool: Data<PgPool>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    path: Path<(GuildId, UserId)>,
+    user_guilds_cache: Data<UserGuildsCache>,
+) -> Result<HttpResponse, KekServerError> {
+    let (guild_id, user_id) = path.into_inner();
+    Validation::is_user_in_guild(&authorized_user, &guild_id, &user_guilds_cache)?;
+    if !Validation::has_discord_permissions(&authorized_user, &guild_id, &user_guilds_cache)? {
+        return Err(KekServerError::Other("No permissions".to_string()));
+    }
+
+    let mut transaction = db_pool.begin().await?;
+    if Guild::get_guild_from_id(&guild_id, &mut transaction)
+        .await?
+        .is_none()
+    {
+        return Err(KekServerError::InvalidGuildIdError);
+    }
+    let manager = GuildManager::insert(
+        &guild_id,
+        &user_id,
+        &authorized_user.discord_user.id,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Created().json(manager));
+}
+
+#[delete("/{guild_id}/managers/{user_id}")]
+pub async fn remove_guild_manager(
+    db_pool: Data<PgPool>,
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    path: Path<(GuildId, UserId)>,
+    user_guilds_cache: Data<UserGuildsCache>,
+) -> Result<HttpResponse, KekServerError> {
+    let (guild_id, user_id) = path.into_inner();
+    Validation::is_user_in_guild(&authorized_user, &guild_id, &user_guilds_cache)?;
+    if !Validation::has_discord_permissions(&authorized_user, &guild_id, &user_guilds_cache)? {
+        return Err(KekServerError::Other("No permissions".to_string()));
+    }
+
+    let mut transaction = db_pool.begin().await?;
+    if !GuildManager::delete(&guild_id, &user_id, &mut transaction).await? {
+        return Err(KekServerError::InvalidGuildManagerError);
+    }
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Ok().finish());
+}
+
 #[derive(Deserialize)]
 pub struct Bulk {
     guilds: Vec<GuildId>,
@@ -110,9 +501,23 @@ pub async fn bulk_enable(
         &user_guilds_cache,
     )?;
     let mut transaction = db_pool.begin().await?;
+    for guild_id in &bulk_payload.guilds {
+        Validation::can_add_to_guild(
+            &authorized_user,
+            guild_id,
+            true,
+            &user_guilds_cache,
+            &mut transaction,
+        )
+        .await?;
+    }
     {
-        let files =
-            SoundFile::get_user_files(&authorized_user.discord_user.id, &mut transaction).await?;
+        let files = SoundFile::get_user_files(
+            &authorized_user.discord_user.id,
+            &TagFilter::default(),
+            &mut transaction,
+        )
+        .await?;
         Validation::user_owns_provided_files(&bulk_payload.files, &files)?;
     }
 
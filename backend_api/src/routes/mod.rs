@@ -4,6 +4,8 @@ mod auth;
 mod controls;
 mod file;
 mod guild;
+mod pack;
+mod transfer;
 mod user;
 mod ws;
 
@@ -17,6 +19,8 @@ pub fn routes_config(cfg: &mut ServiceConfig) {
             .configure(file::config)
             .configure(user::config)
             .configure(guild::config)
+            .configure(pack::config)
+            .configure(transfer::config)
             .configure(ws::config)
             .configure(controls::config)
             .configure(status::config),
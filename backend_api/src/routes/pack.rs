@@ -0,0 +1,391 @@
+use std::sync::Mutex;
+
+use actix_web::{
+    delete, get, patch, post,
+    web::{scope, Data, Json, Path, Query, ServiceConfig},
+    HttpResponse,
+};
+use serde::Deserialize;
+use snowflake::SnowflakeIdGenerator;
+use sqlx::{PgPool, Postgres, Transaction};
+
+use crate::{
+    error::errors::KekServerError,
+    middleware::{auth_middleware::AuthService, user_guilds_middleware::UserGuildsService},
+    models::{
+        favorite::Favorite,
+        guild_role::GuildAction,
+        ids::{GuildId, PackId, SoundFileId, UserId},
+        pack::{Pack, PackFiles},
+        sound_file::{SoundFile, MAX_LIMIT},
+        tag::Tag,
+    },
+    utils::{
+        auth::{AuthorizedUser, AuthorizedUserExt},
+        cache::UserGuildsCache,
+        validation::Validation,
+    },
+};
+
+type PackGuildIds = Path<(PackId, GuildId)>;
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(
+        scope("/packs")
+            .wrap(AuthService)
+            .service(create_pack)
+            .service(get_user_packs)
+            .service(get_public_packs)
+            .service(get_pack)
+            .service(update_pack)
+            .service(delete_pack)
+            .service(add_pack_files)
+            .service(remove_pack_files)
+            .service(enable_pack_in_guild)
+            .service(disable_pack_in_guild),
+    );
+}
+
+/// Pack with its files, tags and favorites are attached for the user.
+async fn get_pack_files(
+    pack: Pack,
+    user_id: &UserId,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<PackFiles, KekServerError> {
+    let mut files = pack.get_files(&mut *transaction).await?;
+    Tag::attach_to_files(files.iter_mut().collect(), &mut *transaction).await?;
+    Favorite::attach_to_files(user_id, files.iter_mut().collect(), &mut *transaction).await?;
+    return Ok(PackFiles { pack, files });
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewPack {
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub is_public: bool,
+}
+
+#[post("")]
+pub async fn create_pack(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    snowflake: Data<Mutex<SnowflakeIdGenerator>>,
+    Json(new_pack): Json<NewPack>,
+) -> Result<HttpResponse, KekServerError> {
+    let name = Pack::validate_name(&new_pack.name)?;
+    let description = new_pack
+        .description
+        .map(|d| SoundFile::validate_description(&d))
+        .transpose()?
+        .flatten();
+    let id;
+    {
+        let mut lock = snowflake.lock().unwrap();
+        id = PackId::from(lock.generate());
+    }
+
+    let mut transaction = db_pool.begin().await?;
+    let pack = Pack::insert(
+        &id,
+        &authorized_user.discord_user.id,
+        &name,
+        description.as_deref(),
+        new_pack.is_public,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Created().json(pack));
+}
+
+/// Packs owned by the user, newest first.
+#[get("")]
+pub async fn get_user_packs(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+) -> Result<HttpResponse, KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+    let packs = Pack::get_user_packs(&authorized_user.discord_user.id, &mut transaction).await?;
+    transaction.commit().await?;
+    return Ok(HttpResponse::Ok().json(packs));
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PublicPacksQueryParams {
+    limit: Option<i64>,
+    page: Option<i64>,
+}
+
+#[get("/public")]
+pub async fn get_public_packs(
+    db_pool: Data<PgPool>,
+    query: Query<PublicPacksQueryParams>,
+) -> Result<HttpResponse, KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+    let packs = Pack::get_public_packs(
+        query.limit.unwrap_or(MAX_LIMIT),
+        query.page.unwrap_or(1),
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+    return Ok(HttpResponse::Ok().json(packs));
+}
+
+#[get("/{pack_id}")]
+pub async fn get_pack(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    pack_id: Path<PackId>,
+) -> Result<HttpResponse, KekServerError> {
+    let user_id = &authorized_user.discord_user.id;
+    let mut transaction = db_pool.begin().await?;
+    let pack = match Pack::get(&pack_id.into_inner(), user_id, &mut transaction).await? {
+        Some(pack) => pack,
+        None => return Err(KekServerError::InvalidPackIdError),
+    };
+    let pack_files = get_pack_files(pack, user_id, &mut transaction).await?;
+    transaction.commit().await?;
+    return Ok(HttpResponse::Ok().json(pack_files));
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PackUpdate {
+    pub name: Option<String>,
+    /// Empty description removes it
+    pub description: Option<String>,
+    pub is_public: Option<bool>,
+}
+
+#[patch("/{pack_id}")]
+pub async fn update_pack(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    pack_id: Path<PackId>,
+    Json(pack_update): Json<PackUpdate>,
+) -> Result<HttpResponse, KekServerError> {
+    let name = pack_update
+        .name
+        .map(|n| Pack::validate_name(&n))
+        .transpose()?;
+    let description = pack_update
+        .description
+        .map(|d| SoundFile::validate_description(&d))
+        .transpose()?;
+
+    let mut transaction = db_pool.begin().await?;
+    let updated_pack = Pack::update(
+        &pack_id.into_inner(),
+        &authorized_user.discord_user.id,
+        name.as_deref(),
+        description.as_ref().map(|d| d.as_deref()),
+        pack_update.is_public,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    match updated_pack {
+        Some(pack) => return Ok(HttpResponse::Ok().json(pack)),
+        None => return Err(KekServerError::InvalidPackIdError),
+    }
+}
+
+/// Files enabled in guilds through the pack stay enabled.
+#[delete("/{pack_id}")]
+pub async fn delete_pack(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    pack_id: Path<PackId>,
+) -> Result<HttpResponse, KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+    let deleted_pack = Pack::delete(
+        &pack_id.into_inner(),
+        &authorized_user.discord_user.id,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    match deleted_pack {
+        Some(pack) => return Ok(HttpResponse::Ok().json(pack)),
+        None => return Err(KekServerError::InvalidPackIdError),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PackFileIds {
+    pub files: Vec<SoundFileId>,
+}
+
+/// Enables added files in the guilds the pack is enabled in. Guilds where the owner can no longer
+/// add the files stop receiving new files of the pack, until the pack is enabled there again.
+async fn enable_added_files_in_guilds(
+    pack: &Pack,
+    added_files: &[SoundFileId],
+    authorized_user: &AuthorizedUser,
+    user_guilds_cache: &Data<UserGuildsCache>,
+    transaction: &mut Transaction<'_, Postgres>,
+) -> Result<(), KekServerError> {
+    if added_files.is_empty() {
+        return Ok(());
+    }
+    let only_own_files = pack
+        .get_files(&mut *transaction)
+        .await?
+        .iter()
+        .filter(|f| added_files.contains(&f.id))
+        .all(|f| f.owner.as_ref() == Some(&pack.owner));
+
+    for guild_id in pack.get_guild_ids(&mut *transaction).await? {
+        let allowed =
+            match Validation::is_user_in_guild(authorized_user, &guild_id, user_guilds_cache) {
+                Ok(_) => {
+                    Validation::can_add_to_guild(
+                        authorized_user,
+                        &guild_id,
+                        only_own_files,
+                        user_guilds_cache,
+                        &mut *transaction,
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+        match allowed {
+            Ok(_) => {
+                pack.enable_files_in_guild(&guild_id, added_files, &mut *transaction)
+                    .await?
+            }
+            Err(
+                KekServerError::NotInGuildError
+                | KekServerError::MissingGuildPermissionError(_)
+                | KekServerError::UnauthorizedFileAccessError(_),
+            ) => {
+                pack.disable_in_guild(&guild_id, &mut *transaction).await?;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+    return Ok(());
+}
+
+/// Adds the user's files and public files, the files are enabled in the guilds the pack is enabled in
+/// if the user can still add them there.
+#[post("/{pack_id}/files", wrap = "UserGuildsService")]
+pub async fn add_pack_files(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    pack_id: Path<PackId>,
+    Json(pack_files): Json<PackFileIds>,
+    user_guilds_cache: Data<UserGuildsCache>,
+) -> Result<HttpResponse, KekServerError> {
+    let user_id = &authorized_user.discord_user.id;
+    let mut transaction = db_pool.begin().await?;
+    let pack = match Pack::get(&pack_id.into_inner(), user_id, &mut transaction).await? {
+        Some(pack) if &pack.owner == user_id => pack,
+        _ => return Err(KekServerError::InvalidPackIdError),
+    };
+    let added_files = pack.add_files(&pack_files.files, &mut transaction).await?;
+    enable_added_files_in_guilds(
+        &pack,
+        &added_files,
+        &authorized_user,
+        &user_guilds_cache,
+        &mut transaction,
+    )
+    .await?;
+    let pack_files = get_pack_files(pack, user_id, &mut transaction).await?;
+    transaction.commit().await?;
+    return Ok(HttpResponse::Ok().json(pack_files));
+}
+
+/// Removed files stay enabled in guilds the pack is enabled in.
+#[delete("/{pack_id}/files")]
+pub async fn remove_pack_files(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    pack_id: Path<PackId>,
+    Json(pack_files): Json<PackFileIds>,
+) -> Result<HttpResponse, KekServerError> {
+    let user_id = &authorized_user.discord_user.id;
+    let mut transaction = db_pool.begin().await?;
+    let pack = match Pack::get(&pack_id.into_inner(), user_id, &mut transaction).await? {
+        Some(pack) if &pack.owner == user_id => pack,
+        _ => return Err(KekServerError::InvalidPackIdError),
+    };
+    pack.remove_files(&pack_files.files, &mut transaction)
+        .await?;
+    let pack_files = get_pack_files(pack, user_id, &mut transaction).await?;
+    transaction.commit().await?;
+    return Ok(HttpResponse::Ok().json(pack_files));
+}
+
+/// Enables all files of an own or public pack in the guild.
+#[post("/{pack_id}/guilds/{guild_id}", wrap = "UserGuildsService")]
+pub async fn enable_pack_in_guild(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    path: PackGuildIds,
+    user_guilds_cache: Data<UserGuildsCache>,
+) -> Result<HttpResponse, KekServerError> {
+    let (pack_id, guild_id) = path.into_inner();
+    Validation::is_user_in_guild(&authorized_user, &guild_id, &user_guilds_cache)?;
+
+    let mut transaction = db_pool.begin().await?;
+    let pack = match Pack::get(&pack_id, &authorized_user.discord_user.id, &mut transaction).await?
+    {
+        Some(pack) => pack,
+        None => return Err(KekServerError::InvalidPackIdError),
+    };
+    let files = pack.get_files(&mut transaction).await?;
+    Validation::can_add_to_guild(
+        &authorized_user,
+        &guild_id,
+        files
+            .iter()
+            .all(|f| f.owner.as_ref() == Some(&authorized_user.discord_user.id)),
+        &user_guilds_cache,
+        &mut transaction,
+    )
+    .await?;
+    pack.enable_in_guild(&guild_id, &mut transaction).await?;
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Created().finish());
+}
+
+/// Files enabled through the pack stay enabled in the guild.
+#[delete("/{pack_id}/guilds/{guild_id}", wrap = "UserGuildsService")]
+pub async fn disable_pack_in_guild(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    path: PackGuildIds,
+    user_guilds_cache: Data<UserGuildsCache>,
+) -> Result<HttpResponse, KekServerError> {
+    let (pack_id, guild_id) = path.into_inner();
+    Validation::is_user_in_guild(&authorized_user, &guild_id, &user_guilds_cache)?;
+
+    let mut transaction = db_pool.begin().await?;
+    Validation::is_action_allowed(
+        &authorized_user,
+        &guild_id,
+        GuildAction::Add,
+        &user_guilds_cache,
+        &mut transaction,
+    )
+    .await?;
+    let pack = match Pack::get(&pack_id, &authorized_user.discord_user.id, &mut transaction).await?
+    {
+        Some(pack) => pack,
+        None => return Err(KekServerError::InvalidPackIdError),
+    };
+    if !pack.disable_in_guild(&guild_id, &mut transaction).await? {
+        return Err(KekServerError::InvalidPackIdError);
+    }
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Ok().finish());
+}
@@ -31,7 +31,7 @@ impl Status {
             ws_sync_sessions: 0,
             ws_guilds_cached: 0,
             ws_active_connections: 0,
-            channels_server_cache_capacity: 0
+            channels_server_cache_capacity: 0,
         };
     }
 }
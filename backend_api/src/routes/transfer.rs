@@ -0,0 +1,158 @@
+use std::sync::Mutex;
+
+use actix_web::{
+    delete, get, post,
+    web::{scope, Data, Json, Path, ServiceConfig},
+    HttpResponse,
+};
+use serde::Deserialize;
+use snowflake::SnowflakeIdGenerator;
+use sqlx::PgPool;
+
+use crate::{
+    config::{Config, Quota},
+    error::errors::KekServerError,
+    expired_transfers_purge::TRANSFER_OFFER_EXPIRY_HOURS,
+    middleware::auth_middleware::AuthService,
+    models::{
+        ids::{SoundFileId, TransferOfferId, UserId},
+        sound_file::SoundFile,
+        transfer_offer::{OwnershipTransfer, TransferOffer},
+    },
+    utils::auth::AuthorizedUserExt,
+};
+
+pub fn config(cfg: &mut ServiceConfig) {
+    cfg.service(
+        scope("/transfers")
+            .wrap(AuthService)
+            .service(create_transfer_offer)
+            .service(get_transfer_offers)
+            .service(get_transfer_history)
+            .service(accept_transfer_offer)
+            .service(delete_transfer_offer),
+    );
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewTransferOffer {
+    pub to_user: UserId,
+    pub files: Vec<SoundFileId>,
+}
+
+/// Offers files owned by the user to another user, the offer expires after `TRANSFER_OFFER_EXPIRY_HOURS`.
+#[post("")]
+pub async fn create_transfer_offer(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    snowflake: Data<Mutex<SnowflakeIdGenerator>>,
+    Json(new_offer): Json<NewTransferOffer>,
+) -> Result<HttpResponse, KekServerError> {
+    let user_id = &authorized_user.discord_user.id;
+    if &new_offer.to_user == user_id {
+        return Err(KekServerError::InvalidTransferRecipientError);
+    }
+    let id;
+    {
+        let mut lock = snowflake.lock().unwrap();
+        id = TransferOfferId::from(lock.generate());
+    }
+
+    let mut transaction = db_pool.begin().await?;
+    let offer = TransferOffer::insert(
+        &id,
+        user_id,
+        &new_offer.to_user,
+        &new_offer.files,
+        *TRANSFER_OFFER_EXPIRY_HOURS,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    match offer {
+        Some(offer) => return Ok(HttpResponse::Created().json(offer)),
+        None => return Err(KekServerError::InvalidFileIdError),
+    }
+}
+
+/// Pending offers sent or received by the user.
+#[get("")]
+pub async fn get_transfer_offers(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+) -> Result<HttpResponse, KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+    let offers =
+        TransferOffer::get_user_offers(&authorized_user.discord_user.id, &mut transaction).await?;
+    transaction.commit().await?;
+    return Ok(HttpResponse::Ok().json(offers));
+}
+
+/// Files transferred from or to the user.
+#[get("/history")]
+pub async fn get_transfer_history(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+) -> Result<HttpResponse, KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+    let transfers =
+        OwnershipTransfer::get_user_transfers(&authorized_user.discord_user.id, &mut transaction)
+            .await?;
+    transaction.commit().await?;
+    return Ok(HttpResponse::Ok().json(transfers));
+}
+
+/// Makes the user the owner of the offered files, only the recipient can accept the offer.
+#[post("/{offer_id}/accept")]
+pub async fn accept_transfer_offer(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    config: Data<Option<Config>>,
+    offer_id: Path<TransferOfferId>,
+) -> Result<HttpResponse, KekServerError> {
+    let user_id = &authorized_user.discord_user.id;
+    let quota = match config.as_ref() {
+        Some(cfg) => cfg.get_user_quota(&user_id.0.into()),
+        None => Quota::default(),
+    };
+
+    let mut transaction = db_pool.begin().await?;
+    let offer = match TransferOffer::get(&offer_id, user_id, &mut transaction).await? {
+        Some(offer) if &offer.to_user == user_id => offer,
+        _ => return Err(KekServerError::InvalidTransferOfferIdError),
+    };
+    let transfers = offer.accept(&mut transaction).await?;
+    // Transferred files count towards the recipient's quota
+    let usage = SoundFile::get_user_storage_usage(user_id, &mut transaction).await?;
+    if quota.is_exceeded_by(&usage) {
+        return Err(KekServerError::QuotaExceededError);
+    }
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Ok().json(transfers));
+}
+
+/// Withdraws the offer if the user sent it or declines it if the user received it.
+#[delete("/{offer_id}")]
+pub async fn delete_transfer_offer(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    offer_id: Path<TransferOfferId>,
+) -> Result<HttpResponse, KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+    let offer = match TransferOffer::get(
+        &offer_id,
+        &authorized_user.discord_user.id,
+        &mut transaction,
+    )
+    .await?
+    {
+        Some(offer) => offer,
+        None => return Err(KekServerError::InvalidTransferOfferIdError),
+    };
+    TransferOffer::delete(&offer.id, &mut transaction).await?;
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Ok().json(offer));
+}
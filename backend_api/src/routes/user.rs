@@ -1,6 +1,6 @@
 use actix_web::{
-    delete, get, patch,
-    web::{scope, Data, Json, Path, ServiceConfig},
+    delete, get, patch, post,
+    web::{scope, Data, Json, Path, Query, ServiceConfig},
     HttpResponse,
 };
 use chrono::NaiveDateTime;
@@ -8,17 +8,24 @@ use serde::{Deserialize, Serialize};
 use sqlx::PgPool;
 
 use crate::{
+    config::{Config, Quota},
+    deleted_files_purge::DELETED_FILES_RETENTION_DAYS,
     error::errors::KekServerError,
     middleware::{auth_middleware::AuthService, user_guilds_middleware::UserGuildsService},
     models::{
+        favorite::Favorite,
         guild::Guild,
         guild_file::GuildFile,
         ids::{GuildId, SoundFileId},
-        sound_file::SoundFile,
+        sound_file::{
+            FileCursor, FileListQueryParams, FileScope, SearchQueryParams, SoundFile, MAX_LIMIT,
+        },
+        tag::{Tag, TagFilter, TagQueryParams, MAX_TAGS_PER_FILE},
     },
     utils::{
         auth::AuthorizedUserExt,
-        cache::{UserGuildsCache, UserGuildsCacheUtil, DiscordGuild},
+        cache::{DiscordGuild, UserGuildsCache, UserGuildsCacheUtil},
+        deserialize_present,
     },
 };
 
@@ -27,12 +34,23 @@ pub fn config(cfg: &mut ServiceConfig) {
         scope("/user")
             .wrap(AuthService)
             .service(get_user_files)
+            .service(get_user_file_tags)
+            .service(search_user_files)
+            .service(add_user_file_tags)
+            .service(remove_user_file_tags)
+            .service(get_favorite_files)
+            .service(add_favorite_file)
+            .service(remove_favorite_file)
             .service(delete_user_file)
             .service(delete_multiple_user_files)
+            .service(get_deleted_user_files)
+            .service(restore_user_files)
             .service(get_user_guilds)
             .service(get_guilds_with_file)
+            .service(get_user_usage)
             .service(get_enabled_user_files)
-            .service(toggle_file_visibility),
+            .service(toggle_file_visibility)
+            .service(update_user_file),
     );
 }
 
@@ -40,14 +58,239 @@ pub fn config(cfg: &mut ServiceConfig) {
 pub async fn get_user_files(
     AuthorizedUserExt(authorized_user): AuthorizedUserExt,
     db_pool: Data<PgPool>,
+    query: Query<FileListQueryParams>,
 ) -> Result<HttpResponse, KekServerError> {
+    let tag_filter = TagFilter::from_query(query.tags.as_deref(), query.tag_match)?;
     let mut transaction = db_pool.begin().await?;
-    let files =
-        SoundFile::get_user_files(&authorized_user.discord_user.id, &mut transaction).await?;
+    if query.is_paginated() {
+        let cursor = query
+            .cursor
+            .as_deref()
+            .map(FileCursor::decode)
+            .transpose()?;
+        let mut page = SoundFile::get_files_page(
+            &FileScope::User(authorized_user.discord_user.id.clone()),
+            query.sort.unwrap_or_default(),
+            cursor.as_ref(),
+            query.limit.unwrap_or(MAX_LIMIT),
+            &tag_filter,
+            &mut transaction,
+        )
+        .await?;
+        Tag::attach_to_files(page.files.iter_mut().collect(), &mut transaction).await?;
+        Favorite::attach_to_files(
+            &authorized_user.discord_user.id,
+            page.files.iter_mut().collect(),
+            &mut transaction,
+        )
+        .await?;
+        transaction.commit().await?;
+        return Ok(HttpResponse::Ok().json(page));
+    }
+    let mut files = SoundFile::get_user_files(
+        &authorized_user.discord_user.id,
+        &tag_filter,
+        &mut transaction,
+    )
+    .await?;
+    Tag::attach_to_files(files.iter_mut().collect(), &mut transaction).await?;
+    Favorite::attach_to_files(
+        &authorized_user.discord_user.id,
+        files.iter_mut().collect(),
+        &mut transaction,
+    )
+    .await?;
     transaction.commit().await?;
     return Ok(HttpResponse::Ok().json(files));
 }
 
+/// Tag counts of the user's files matching the tag filter.
+#[get("/files/tags")]
+pub async fn get_user_file_tags(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    query: Query<TagQueryParams>,
+) -> Result<HttpResponse, KekServerError> {
+    let tag_filter = TagFilter::from_query(query.tags.as_deref(), query.tag_match)?;
+    let mut transaction = db_pool.begin().await?;
+    let tag_counts = Tag::get_user_counts(
+        &authorized_user.discord_user.id,
+        &tag_filter,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+    return Ok(HttpResponse::Ok().json(tag_counts));
+}
+
+/// Ranked search in the user's files.
+#[get("/files/search")]
+pub async fn search_user_files(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    query: Query<SearchQueryParams>,
+) -> Result<HttpResponse, KekServerError> {
+    let tag_filter = TagFilter::from_query(query.tags.as_deref(), query.tag_match)?;
+    let mut transaction = db_pool.begin().await?;
+    let mut files = SoundFile::search(
+        &FileScope::User(authorized_user.discord_user.id.clone()),
+        query.limit.unwrap_or(MAX_LIMIT),
+        query.page.unwrap_or(1),
+        &query.search_query,
+        &tag_filter,
+        &mut transaction,
+    )
+    .await?;
+    Tag::attach_to_files(files.files.iter_mut().collect(), &mut transaction).await?;
+    Favorite::attach_to_files(
+        &authorized_user.discord_user.id,
+        files.files.iter_mut().collect(),
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+    return Ok(HttpResponse::Ok().json(files));
+}
+
+/// Favorited files, most recently favorited first.
+#[get("/favorites", wrap = "UserGuildsService")]
+pub async fn get_favorite_files(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    user_guilds_cache: Data<UserGuildsCache>,
+) -> Result<HttpResponse, KekServerError> {
+    let user_guilds = UserGuildsCacheUtil::get_user_guilds(&authorized_user, &user_guilds_cache)?
+        .iter()
+        .map(|guild| guild.id.clone())
+        .collect::<Vec<GuildId>>();
+
+    let mut transaction = db_pool.begin().await?;
+    let mut files = Favorite::get_user_favorite_files(
+        &authorized_user.discord_user.id,
+        &user_guilds,
+        &mut transaction,
+    )
+    .await?;
+    Tag::attach_to_files(files.iter_mut().collect(), &mut transaction).await?;
+    transaction.commit().await?;
+    return Ok(HttpResponse::Ok().json(files));
+}
+
+#[post("/favorites/{file_id}", wrap = "UserGuildsService")]
+pub async fn add_favorite_file(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    user_guilds_cache: Data<UserGuildsCache>,
+    file_id: Path<SoundFileId>,
+) -> Result<HttpResponse, KekServerError> {
+    let user_guilds = UserGuildsCacheUtil::get_user_guilds(&authorized_user, &user_guilds_cache)?
+        .iter()
+        .map(|guild| guild.id.clone())
+        .collect::<Vec<GuildId>>();
+
+    let mut transaction = db_pool.begin().await?;
+    let favorite = Favorite::insert(
+        &authorized_user.discord_user.id,
+        &file_id.into_inner(),
+        &user_guilds,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    match favorite {
+        Some(favorite) => return Ok(HttpResponse::Created().json(favorite)),
+        None => return Err(KekServerError::InvalidFileIdError),
+    }
+}
+
+#[delete("/favorites/{file_id}")]
+pub async fn remove_favorite_file(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    file_id: Path<SoundFileId>,
+) -> Result<HttpResponse, KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+    let favorite = Favorite::delete(
+        &authorized_user.discord_user.id,
+        &file_id.into_inner(),
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    match favorite {
+        Some(favorite) => return Ok(HttpResponse::Ok().json(favorite)),
+        None => return Err(KekServerError::InvalidFileIdError),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FileTags {
+    pub tags: Vec<String>,
+}
+
+#[post("/files/{file_id}/tags")]
+pub async fn add_user_file_tags(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    file_id: Path<SoundFileId>,
+    Json(file_tags): Json<FileTags>,
+) -> Result<HttpResponse, KekServerError> {
+    let file_id = file_id.into_inner();
+    let tags = Tag::normalize(&file_tags.tags)?;
+
+    let mut transaction = db_pool.begin().await?;
+    let mut file = match SoundFile::get_user_file_for_update(
+        &file_id,
+        &authorized_user.discord_user.id,
+        &mut transaction,
+    )
+    .await?
+    {
+        Some(file) => file,
+        None => return Err(KekServerError::InvalidFileIdError),
+    };
+    Tag::add_file_tags(&file_id, &tags, &mut transaction).await?;
+    Tag::attach_to_files(vec![&mut file], &mut transaction).await?;
+    // Checked after adding so tags the file already has aren't counted twice
+    if file.tags.len() > MAX_TAGS_PER_FILE {
+        return Err(KekServerError::InvalidTagError(format!(
+            "Files can have at most {} tags",
+            MAX_TAGS_PER_FILE
+        )));
+    }
+    transaction.commit().await?;
+    return Ok(HttpResponse::Ok().json(file));
+}
+
+#[delete("/files/{file_id}/tags")]
+pub async fn remove_user_file_tags(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    file_id: Path<SoundFileId>,
+    Json(file_tags): Json<FileTags>,
+) -> Result<HttpResponse, KekServerError> {
+    let file_id = file_id.into_inner();
+    let tags = Tag::normalize(&file_tags.tags)?;
+
+    let mut transaction = db_pool.begin().await?;
+    let mut file = match SoundFile::get_user_file_for_update(
+        &file_id,
+        &authorized_user.discord_user.id,
+        &mut transaction,
+    )
+    .await?
+    {
+        Some(file) => file,
+        None => return Err(KekServerError::InvalidFileIdError),
+    };
+    Tag::remove_file_tags(&file_id, &tags, &mut transaction).await?;
+    Tag::attach_to_files(vec![&mut file], &mut transaction).await?;
+    transaction.commit().await?;
+    return Ok(HttpResponse::Ok().json(file));
+}
+
 #[delete("/files/{file_id}")]
 pub async fn delete_user_file(
     AuthorizedUserExt(authorized_user): AuthorizedUserExt,
@@ -86,6 +329,67 @@ pub async fn toggle_file_visibility(
     return Ok(HttpResponse::Ok().json(toggled_file));
 }
 
+#[derive(Debug, Deserialize)]
+pub struct FileUpdate {
+    pub display_name: Option<String>,
+    /// Empty description removes it
+    pub description: Option<String>,
+    pub is_public: Option<bool>,
+    /// `null` removes the trim point, missing field leaves it unchanged
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub start_ms: Option<Option<i32>>,
+    #[serde(default, deserialize_with = "deserialize_present")]
+    pub end_ms: Option<Option<i32>>,
+}
+
+#[patch("/files/{file_id}")]
+pub async fn update_user_file(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    file_id: Path<SoundFileId>,
+    Json(file_update): Json<FileUpdate>,
+) -> Result<HttpResponse, KekServerError> {
+    let file_id = file_id.into_inner();
+    let user_id = &authorized_user.discord_user.id;
+    let display_name = file_update
+        .display_name
+        .map(|d| SoundFile::validate_display_name(&d))
+        .transpose()?;
+    let description = file_update
+        .description
+        .map(|d| SoundFile::validate_description(&d))
+        .transpose()?;
+
+    let mut transaction = db_pool.begin().await?;
+    if file_update.start_ms.is_some() || file_update.end_ms.is_some() {
+        // Trim points are validated together with the ones which aren't changed
+        let file =
+            match SoundFile::get_user_file_for_update(&file_id, user_id, &mut transaction).await? {
+                Some(file) => file,
+                None => return Err(KekServerError::InvalidFileIdError),
+            };
+        let start_ms = file_update.start_ms.unwrap_or(file.start_ms);
+        let end_ms = file_update.end_ms.unwrap_or(file.end_ms);
+        SoundFile::validate_trim(start_ms, end_ms, file.metadata.duration_ms)?;
+        SoundFile::set_trim(&file_id, start_ms, end_ms, &mut transaction).await?;
+    }
+    let updated_file = SoundFile::update(
+        &file_id,
+        user_id,
+        display_name.as_deref(),
+        description.as_ref().map(|d| d.as_deref()),
+        file_update.is_public,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+
+    match updated_file {
+        Some(file) => return Ok(HttpResponse::Ok().json(file)),
+        None => return Err(KekServerError::InvalidFileIdError),
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct FilesToDelete {
     pub files: Vec<SoundFileId>,
@@ -111,6 +415,85 @@ pub async fn delete_multiple_user_files(
         .json(serde_json::json!({ "count": deleted_files.len(), "files": deleted_files })));
 }
 
+#[get("/files/deleted")]
+pub async fn get_deleted_user_files(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+) -> Result<HttpResponse, KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+    let files = SoundFile::get_user_deleted_files(
+        &authorized_user.discord_user.id,
+        *DELETED_FILES_RETENTION_DAYS,
+        &mut transaction,
+    )
+    .await?;
+    transaction.commit().await?;
+    return Ok(HttpResponse::Ok().json(files));
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct FilesToRestore {
+    pub files: Vec<SoundFileId>,
+}
+
+#[post("/files/restore")]
+pub async fn restore_user_files(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    file_ids: Json<FilesToRestore>,
+) -> Result<HttpResponse, KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+
+    let restored_files = SoundFile::restore_multiple(
+        &file_ids.files,
+        &authorized_user.discord_user.id,
+        *DELETED_FILES_RETENTION_DAYS,
+        &mut transaction,
+    )
+    .await?;
+
+    transaction.commit().await?;
+    return Ok(HttpResponse::Ok()
+        .json(serde_json::json!({ "count": restored_files.len(), "files": restored_files })));
+}
+
+#[derive(Debug, Serialize)]
+struct UserUsage {
+    used_bytes: i64,
+    used_files: i64,
+    max_bytes: Option<i64>,
+    max_files: Option<i64>,
+    remaining_bytes: Option<i64>,
+    remaining_files: Option<i64>,
+}
+
+#[get("/usage")]
+pub async fn get_user_usage(
+    AuthorizedUserExt(authorized_user): AuthorizedUserExt,
+    db_pool: Data<PgPool>,
+    config: Data<Option<Config>>,
+) -> Result<HttpResponse, KekServerError> {
+    let quota = match config.as_ref() {
+        Some(cfg) => cfg.get_user_quota(&authorized_user.discord_user.id.0.into()),
+        None => Quota::default(),
+    };
+
+    let mut transaction = db_pool.begin().await?;
+    let usage =
+        SoundFile::get_user_storage_usage(&authorized_user.discord_user.id, &mut transaction)
+            .await?;
+    transaction.commit().await?;
+
+    return Ok(HttpResponse::Ok().json(UserUsage {
+        used_bytes: usage.bytes,
+        used_files: usage.files,
+        max_bytes: quota.max_bytes,
+        max_files: quota.max_files,
+        remaining_bytes: quota.max_bytes.map(|max| (max - usage.bytes).max(0)),
+        remaining_files: quota.max_files.map(|max| (max - usage.files).max(0)),
+    }));
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 struct UserGuilds {
     #[serde(flatten)]
@@ -127,7 +510,8 @@ pub async fn get_user_guilds(
     let user_guilds = UserGuildsCacheUtil::get_user_guilds(&authorized_user, &user_guilds_cache)?;
 
     let mut transaction = db_pool.begin().await?;
-    let guilds = Guild::get_intercepting_user_and_bot_guilds(&*user_guilds, &mut transaction).await?;
+    let guilds =
+        Guild::get_intercepting_user_and_bot_guilds(&*user_guilds, &mut transaction).await?;
     transaction.commit().await?;
 
     let guilds = guilds
@@ -162,7 +546,8 @@ pub async fn get_guilds_with_file(
     let user_guilds = UserGuildsCacheUtil::get_user_guilds(&authorized_user, &user_guilds_cache)?;
 
     let mut transaction = db_pool.begin().await?;
-    let guilds = Guild::get_intercepting_user_and_bot_guilds(&*user_guilds, &mut transaction).await?;
+    let guilds =
+        Guild::get_intercepting_user_and_bot_guilds(&*user_guilds, &mut transaction).await?;
     let guilds_with_file =
         GuildFile::get_matching_guilds_for_file(&guilds, &file_id, &mut transaction).await?;
     transaction.commit().await?;
@@ -199,8 +584,12 @@ pub async fn get_enabled_user_files(
     }
 
     let mut transaction = db_pool.begin().await?;
-    let files =
-        SoundFile::get_user_files(&authorized_user.discord_user.id, &mut transaction).await?;
+    let files = SoundFile::get_user_files(
+        &authorized_user.discord_user.id,
+        &TagFilter::default(),
+        &mut transaction,
+    )
+    .await?;
     let enabled_files = GuildFile::get_users_enabled_files_for_guild(
         &authorized_user.discord_user.id,
         &guild_id,
@@ -222,3 +611,55 @@ pub async fn get_enabled_user_files(
 
     return Ok(HttpResponse::Ok().json(enabled_files));
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use actix_web::{
+        test::{call_service, init_service, TestRequest},
+        web::Data,
+        App,
+    };
+    use tokio::sync::Mutex;
+
+    use crate::{
+        config::Config,
+        database::tests_db_helper::db_pool_util,
+        models::user::User,
+        utils::{
+            auth::{AccessToken, AuthorizedUser},
+            cache::{create_auth_middlware_queue_cache, AuthorizedUsersCache},
+        },
+    };
+
+    #[actix_web::test]
+    async fn test_get_user_usage_route() {
+        let authorized_user = Arc::new(AuthorizedUser {
+            access_token: Arc::new(AccessToken("test_token".to_owned())),
+            discord_user: User::get_test_user(),
+        });
+        let user_cache = AuthorizedUsersCache::new(1);
+        user_cache
+            .insert(
+                authorized_user.access_token.clone(),
+                authorized_user.clone(),
+            )
+            .await;
+        let app = init_service(
+            App::new()
+                .app_data(Data::new(user_cache))
+                .app_data(Data::new(Mutex::new(create_auth_middlware_queue_cache())))
+                .app_data(Data::new(db_pool_util().await))
+                .app_data(Data::new(None::<Config>))
+                .configure(super::config),
+        )
+        .await;
+        let req = TestRequest::get()
+            .uri("/user/usage")
+            .insert_header(("Authorization", "test_token"))
+            .to_request();
+        let resp = call_service(&app, req).await;
+        assert!(resp.status().is_success());
+    }
+}
@@ -0,0 +1,261 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use actix_web::web::Data;
+use lazy_static::lazy_static;
+use log::{info, warn};
+use sqlx::PgPool;
+
+use crate::{
+    error::errors::KekServerError,
+    models::{
+        file_version::FileVersion,
+        ids::SoundFileId,
+        sound_file::{ProcessingStatus, SoundFile, StoredFile},
+    },
+    utils::SNOWFLAKE_EPOCH_MS,
+};
+
+use super::{checksum, original_storage_key, quarantine_key, SoundStorage};
+
+lazy_static! {
+    pub static ref QUARANTINE_ORPHANED_FILES: bool = dotenv::var("QUARANTINE_ORPHANED_FILES")
+        .map(|q| q == "true")
+        .unwrap_or(false);
+}
+
+/// Objects younger than this can belong to an upload whose database transaction isn't committed yet.
+const ORPHAN_GRACE_PERIOD_MS: u64 = 60 * 60 * 1000;
+/// Number of checksums stored in one transaction.
+const CHECKSUM_BATCH_SIZE: usize = 100;
+
+#[derive(Debug, Default)]
+pub struct IntegrityScanReport {
+    /// Storage keys without a matching file
+    pub orphaned: Vec<String>,
+    /// Files without bytes in storage
+    pub missing: Vec<SoundFileId>,
+    /// Files whose bytes don't match the stored checksum
+    pub corrupted: Vec<SoundFileId>,
+    /// Storage keys which couldn't be read
+    pub unreadable: Vec<String>,
+    pub checksums_stored: usize,
+    pub quarantined: usize,
+}
+
+impl IntegrityScanReport {
+    pub fn log(&self) {
+        for key in &self.orphaned {
+            warn!("Orphaned storage object: [{}]", key);
+        }
+        for id in &self.missing {
+            warn!("Missing storage object for file with id: [{}]", id.0);
+        }
+        for id in &self.corrupted {
+            warn!("Checksum mismatch for file with id: [{}]", id.0);
+        }
+        for key in &self.unreadable {
+            warn!("Unreadable storage object: [{}]", key);
+        }
+        info!(
+            "Storage scan finished, orphaned: {}, quarantined: {}, missing: {}, corrupted: {}, unreadable: {}, new checksums: {}",
+            self.orphaned.len(),
+            self.quarantined,
+            self.missing.len(),
+            self.corrupted.len(),
+            self.unreadable.len(),
+            self.checksums_stored
+        );
+    }
+}
+
+/// Compares stored objects with files in the database.
+///
+/// Checksums are stored for files that don't have one yet, files which are still being processed are skipped.
+/// Objects which can't be read are reported as unreadable without stopping the scan.
+pub async fn scan_storage(
+    db_pool: &Data<PgPool>,
+    storage: &Data<dyn SoundStorage>,
+    quarantine: bool,
+) -> Result<IntegrityScanReport, KekServerError> {
+    let mut transaction = db_pool.begin().await?;
+    let files = SoundFile::get_stored_files(&mut transaction).await?;
+    let version_keys = FileVersion::get_storage_keys(&mut transaction).await?;
+    transaction.commit().await?;
+
+    let keys = storage
+        .list()
+        .await?
+        .into_iter()
+        .collect::<HashSet<String>>();
+    let mut report = IntegrityScanReport {
+        orphaned: find_orphaned_keys(&keys, &files, &version_keys, current_time_ms()),
+        ..Default::default()
+    };
+
+    // Storage is read without holding a transaction, checksums are stored afterwards in batches
+    let mut checked_keys = HashMap::<&str, Option<String>>::new();
+    let mut new_checksums = Vec::new();
+    for file in files.iter().filter(|f| !f.is_deleted) {
+        let key = file.storage_key.as_str();
+        if !keys.contains(key) {
+            report.missing.push(file.id.clone());
+            continue;
+        }
+        if file.processing_status == ProcessingStatus::Pending {
+            continue;
+        }
+
+        // Deduplicated files share their bytes, read them only once
+        let data_checksum = match checked_keys.get(key) {
+            Some(data_checksum) => data_checksum.clone(),
+            None => {
+                let data_checksum = match storage.read(key).await {
+                    Ok(data) => Some(checksum(&data)),
+                    Err(e) => {
+                        warn!("Failed to read storage object: [{}], error: {}", key, e);
+                        report.unreadable.push(key.to_string());
+                        None
+                    }
+                };
+                checked_keys.insert(key, data_checksum.clone());
+                if let (None, Some(data_checksum)) = (&file.checksum, &data_checksum) {
+                    new_checksums.push((key, data_checksum.clone()));
+                }
+                data_checksum
+            }
+        };
+        if let (Some(stored_checksum), Some(data_checksum)) = (&file.checksum, &data_checksum) {
+            if stored_checksum != data_checksum {
+                report.corrupted.push(file.id.clone());
+            }
+        }
+    }
+
+    for batch in new_checksums.chunks(CHECKSUM_BATCH_SIZE) {
+        let mut transaction = db_pool.begin().await?;
+        for (key, data_checksum) in batch {
+            SoundFile::set_checksum(key, data_checksum, &mut transaction).await?;
+        }
+        transaction.commit().await?;
+        report.checksums_stored += batch.len();
+    }
+
+    if quarantine {
+        for key in &report.orphaned {
+            match quarantine_object(key, storage).await {
+                Ok(_) => report.quarantined += 1,
+                Err(e) => warn!(
+                    "Failed to quarantine storage object: [{}], error: {}",
+                    key, e
+                ),
+            }
+        }
+    }
+
+    return Ok(report);
+}
+
+async fn quarantine_object(
+    key: &str,
+    storage: &Data<dyn SoundStorage>,
+) -> Result<(), KekServerError> {
+    let data = storage.read(key).await?;
+    storage.save(&quarantine_key(key), &data).await?;
+    return storage.delete(key).await;
+}
+
+fn current_time_ms() -> u64 {
+    return SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0);
+}
+
+/// Creation time of a key named after a snowflake id.
+fn key_created_at_ms(key: &str) -> Option<u64> {
+    let id = key.split('.').next()?.parse::<u64>().ok()?;
+    return Some((id >> 22) + SNOWFLAKE_EPOCH_MS);
+}
+
+/// Keys not used by any file or previous version of a file.
+fn find_orphaned_keys(
+    keys: &HashSet<String>,
+    files: &[StoredFile],
+    version_keys: &[String],
+    now_ms: u64,
+) -> Vec<String> {
+    let known_keys = files
+        .iter()
+        .map(|f| &f.storage_key)
+        .chain(version_keys)
+        .flat_map(|key| vec![key.clone(), original_storage_key(key)])
+        .collect::<HashSet<String>>();
+
+    let mut orphaned = keys
+        .iter()
+        .filter(|key| !known_keys.contains(*key))
+        .filter(|key| match key_created_at_ms(key) {
+            Some(created_at) => now_ms.saturating_sub(created_at) > ORPHAN_GRACE_PERIOD_MS,
+            None => true,
+        })
+        .cloned()
+        .collect::<Vec<String>>();
+    orphaned.sort();
+    return orphaned;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::{
+        models::{
+            ids::SoundFileId,
+            sound_file::{ProcessingStatus, StoredFile},
+        },
+        utils::SNOWFLAKE_EPOCH_MS,
+    };
+
+    use super::{find_orphaned_keys, ORPHAN_GRACE_PERIOD_MS};
+
+    #[test]
+    fn test_find_orphaned_keys() {
+        let now_ms = SNOWFLAKE_EPOCH_MS + 10 * ORPHAN_GRACE_PERIOD_MS;
+        let old_id = 1u64 << 22;
+        let recent_id = (now_ms - SNOWFLAKE_EPOCH_MS) << 22;
+        let files = vec![StoredFile {
+            id: SoundFileId(old_id),
+            is_deleted: false,
+            processing_status: ProcessingStatus::Ready,
+            checksum: None,
+            storage_key: old_id.to_string(),
+        }];
+        let keys = vec![
+            old_id.to_string(),
+            format!("{}.original", old_id),
+            format!("{}_version", old_id),
+            format!("{}_version.original", old_id),
+            (old_id + 1).to_string(),
+            format!("{}.original", old_id + 1),
+            recent_id.to_string(),
+            "unknown".to_string(),
+        ]
+        .into_iter()
+        .collect::<HashSet<String>>();
+
+        let version_keys = vec![format!("{}_version", old_id)];
+        let orphaned = find_orphaned_keys(&keys, &files, &version_keys, now_ms);
+
+        assert_eq!(
+            orphaned,
+            vec![
+                (old_id + 1).to_string(),
+                format!("{}.original", old_id + 1),
+                "unknown".to_string(),
+            ]
+        );
+    }
+}
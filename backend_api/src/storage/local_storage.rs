@@ -0,0 +1,135 @@
+use std::path::Path;
+
+use async_trait::async_trait;
+use tokio::fs::{create_dir_all, read, read_dir, remove_file, rename, write};
+
+use crate::error::errors::KekServerError;
+
+use super::SoundStorage;
+
+pub struct LocalStorage {
+    directory: String,
+}
+
+impl LocalStorage {
+    pub fn new(directory: String) -> Self {
+        return Self { directory };
+    }
+
+    pub fn from_env() -> Result<Self, KekServerError> {
+        return Ok(Self::new(dotenv::var("SOUNDFILE_DIR")?));
+    }
+
+    fn full_path(&self, key: &str) -> String {
+        return format!("{}{}", self.directory, key);
+    }
+}
+
+#[async_trait]
+impl SoundStorage for LocalStorage {
+    async fn save(&self, key: &str, data: &[u8]) -> Result<(), KekServerError> {
+        let path = self.full_path(key);
+        // Keys can contain a prefix directory (e.g. quarantine)
+        if let Some(parent) = Path::new(&path).parent() {
+            create_dir_all(parent).await?;
+        }
+        Ok(write(path, data).await?)
+    }
+
+    async fn read(&self, key: &str) -> Result<Vec<u8>, KekServerError> {
+        Ok(read(self.full_path(key)).await?)
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), KekServerError> {
+        Ok(remove_file(self.full_path(key)).await?)
+    }
+
+    /// Renaming within the directory is atomic, readers see either the old or the new file.
+    async fn rename(&self, from: &str, to: &str) -> Result<(), KekServerError> {
+        Ok(rename(self.full_path(from), self.full_path(to)).await?)
+    }
+
+    async fn list(&self) -> Result<Vec<String>, KekServerError> {
+        let mut keys = Vec::new();
+        let mut entries = read_dir(&self.directory).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            if entry.file_type().await?.is_file() {
+                keys.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+        Ok(keys)
+    }
+
+    fn local_path(&self, key: &str) -> Option<String> {
+        return Some(self.full_path(key));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::storage::{processing_key, quarantine_key, SoundStorage};
+
+    use super::LocalStorage;
+
+    #[actix_web::test]
+    async fn test_local_storage() {
+        let directory = format!("{}/", std::env::temp_dir().display());
+        let storage = LocalStorage::new(directory);
+        let key = Uuid::new_v4().to_string();
+        let data = b"test data".to_vec();
+
+        storage.save(&key, &data).await.unwrap();
+        let read_data = storage.read(&key).await.unwrap();
+        let listed_keys = storage.list().await.unwrap();
+        storage.delete(&key).await.unwrap();
+
+        assert_eq!(read_data, data);
+        assert!(listed_keys.contains(&key));
+        assert!(storage.read(&key).await.is_err());
+    }
+
+    #[actix_web::test]
+    async fn test_local_storage_list_skips_quarantine() {
+        let directory = format!(
+            "{}/ksv2_{}/",
+            std::env::temp_dir().display(),
+            Uuid::new_v4()
+        );
+        let storage = LocalStorage::new(directory.clone());
+        let key = Uuid::new_v4().to_string();
+
+        storage.save(&key, b"test data").await.unwrap();
+        storage
+            .save(&quarantine_key(&key), b"test data")
+            .await
+            .unwrap();
+        let listed_keys = storage.list().await.unwrap();
+        tokio::fs::remove_dir_all(&directory).await.unwrap();
+
+        assert_eq!(listed_keys, vec![key]);
+    }
+
+    #[actix_web::test]
+    async fn test_local_storage_rename() {
+        let directory = format!(
+            "{}/ksv2_{}/",
+            std::env::temp_dir().display(),
+            Uuid::new_v4()
+        );
+        let storage = LocalStorage::new(directory.clone());
+        let key = Uuid::new_v4().to_string();
+        let temp_key = processing_key(&key);
+
+        storage.save(&key, b"old data").await.unwrap();
+        storage.save(&temp_key, b"new data").await.unwrap();
+        storage.rename(&temp_key, &key).await.unwrap();
+        let read_data = storage.read(&key).await.unwrap();
+        let temp_exists = storage.read(&temp_key).await.is_ok();
+        tokio::fs::remove_dir_all(&directory).await.unwrap();
+
+        assert_eq!(read_data, b"new data");
+        assert!(!temp_exists);
+    }
+}
@@ -0,0 +1,77 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use sha2::{Digest, Sha256};
+
+use crate::{config::StorageBackend, error::errors::KekServerError, models::ids::SoundFileId};
+
+pub mod integrity_scan;
+mod local_storage;
+mod s3_storage;
+pub mod signed_url;
+pub use local_storage::LocalStorage;
+pub use s3_storage::S3Storage;
+
+/// Storage for the bytes of uploaded sound files.
+///
+/// Objects are addressed by key, for sound files the key is their id.
+#[async_trait]
+pub trait SoundStorage: Send + Sync {
+    async fn save(&self, key: &str, data: &[u8]) -> Result<(), KekServerError>;
+
+    async fn read(&self, key: &str) -> Result<Vec<u8>, KekServerError>;
+
+    async fn delete(&self, key: &str) -> Result<(), KekServerError>;
+
+    /// Keys of all top level objects, quarantined objects and partial uploads are not included.
+    async fn list(&self) -> Result<Vec<String>, KekServerError>;
+
+    /// Moves an object to another key, an existing object under the key is replaced.
+    async fn rename(&self, from: &str, to: &str) -> Result<(), KekServerError> {
+        let data = self.read(from).await?;
+        self.save(to, &data).await?;
+        return self.delete(from).await;
+    }
+
+    /// Path on disk for storages that keep objects on the local filesystem.
+    fn local_path(&self, _key: &str) -> Option<String> {
+        return None;
+    }
+}
+
+pub fn sound_file_key(id: &SoundFileId) -> String {
+    return id.0.to_string();
+}
+
+/// Key of the file as it was uploaded, before processing.
+pub fn original_storage_key(storage_key: &str) -> String {
+    return format!("{}.original", storage_key);
+}
+
+/// Hex encoded SHA-256 checksum of stored bytes.
+pub fn checksum(data: &[u8]) -> String {
+    return format!("{:x}", Sha256::digest(data));
+}
+
+/// Key an object is moved to when quarantined.
+pub fn quarantine_key(key: &str) -> String {
+    return format!("quarantine/{}", key);
+}
+
+/// Key a converted file is written to before it replaces the stored file.
+pub fn processing_key(storage_key: &str) -> String {
+    return format!("processing/{}", storage_key);
+}
+
+/// Key the bytes of an unfinished resumable upload are stored under.
+pub fn upload_key(id: &SoundFileId) -> String {
+    return format!("uploads/{}", id.0);
+}
+
+/// Creates the storage backend selected in the config file.
+pub fn create_storage(backend: StorageBackend) -> Result<Arc<dyn SoundStorage>, KekServerError> {
+    match backend {
+        StorageBackend::Local => return Ok(Arc::new(LocalStorage::from_env()?)),
+        StorageBackend::S3 => return Ok(Arc::new(S3Storage::from_env()?)),
+    }
+}
@@ -0,0 +1,118 @@
+use async_trait::async_trait;
+use s3::{creds::Credentials, error::S3Error, Bucket, Region};
+
+use crate::error::errors::KekServerError;
+
+use super::SoundStorage;
+
+pub struct S3Storage {
+    bucket: Bucket,
+}
+
+impl S3Storage {
+    /// Setting an endpoint targets an S3-compatible server (e.g. MinIO) using path style requests.
+    pub fn new(
+        bucket_name: &str,
+        region: &str,
+        endpoint: Option<String>,
+        access_key: Option<&str>,
+        secret_key: Option<&str>,
+    ) -> Result<Self, KekServerError> {
+        let credentials =
+            Credentials::new(access_key, secret_key, None, None, None).map_err(S3Error::from)?;
+        let bucket = match endpoint {
+            Some(endpoint) => Bucket::new(
+                bucket_name,
+                Region::Custom {
+                    region: region.to_string(),
+                    endpoint,
+                },
+                credentials,
+            )?
+            .with_path_style(),
+            None => Bucket::new(
+                bucket_name,
+                region.parse().map_err(S3Error::from)?,
+                credentials,
+            )?,
+        };
+        return Ok(Self { bucket });
+    }
+
+    pub fn from_env() -> Result<Self, KekServerError> {
+        let bucket_name = dotenv::var("S3_BUCKET")?;
+        let region = dotenv::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string());
+        let access_key = dotenv::var("S3_ACCESS_KEY").ok();
+        let secret_key = dotenv::var("S3_SECRET_KEY").ok();
+        return Self::new(
+            &bucket_name,
+            &region,
+            dotenv::var("S3_ENDPOINT").ok(),
+            access_key.as_deref(),
+            secret_key.as_deref(),
+        );
+    }
+}
+
+#[async_trait]
+impl SoundStorage for S3Storage {
+    async fn save(&self, key: &str, data: &[u8]) -> Result<(), KekServerError> {
+        self.bucket.put_object(key, data).await?;
+        Ok(())
+    }
+
+    async fn read(&self, key: &str) -> Result<Vec<u8>, KekServerError> {
+        Ok(self.bucket.get_object(key).await?.bytes().to_vec())
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), KekServerError> {
+        self.bucket.delete_object(key).await?;
+        Ok(())
+    }
+
+    async fn list(&self) -> Result<Vec<String>, KekServerError> {
+        // Delimiter limits listing to top level keys, leaving out quarantined objects
+        let results = self
+            .bucket
+            .list(String::new(), Some("/".to_string()))
+            .await?;
+        Ok(results
+            .into_iter()
+            .flat_map(|r| r.contents)
+            .map(|o| o.key)
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use crate::storage::SoundStorage;
+
+    use super::S3Storage;
+
+    // Requires a running S3-compatible server (e.g. MinIO) with an existing bucket,
+    // run with `cargo test -- --ignored`
+    #[actix_web::test]
+    #[ignore]
+    async fn test_s3_storage() {
+        let storage = S3Storage::new(
+            &dotenv::var("TESTING_S3_BUCKET").expect("TESTING_S3_BUCKET is not set!"),
+            "us-east-1",
+            Some(dotenv::var("TESTING_S3_ENDPOINT").expect("TESTING_S3_ENDPOINT is not set!")),
+            dotenv::var("TESTING_S3_ACCESS_KEY").ok().as_deref(),
+            dotenv::var("TESTING_S3_SECRET_KEY").ok().as_deref(),
+        )
+        .unwrap();
+        let key = Uuid::new_v4().to_string();
+        let data = b"test data".to_vec();
+
+        storage.save(&key, &data).await.unwrap();
+        let read_data = storage.read(&key).await.unwrap();
+        storage.delete(&key).await.unwrap();
+
+        assert_eq!(read_data, data);
+        assert!(storage.read(&key).await.is_err());
+    }
+}
@@ -0,0 +1,72 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use lazy_static::lazy_static;
+use sha2::Sha256;
+
+lazy_static! {
+    // The bot already holds the websocket token, so no other secret has to be shared.
+    // WS_TOKEN is required on startup.
+    static ref SIGNING_KEY: String = dotenv::var("WS_TOKEN").unwrap_or_default();
+}
+
+/// Seconds a signed audio path stays valid, covers the time a sound waits in the queue.
+pub const AUDIO_PATH_EXPIRY_SECS: i64 = 60 * 60 * 24;
+
+fn signature(secret: &str, key: &str, expires: i64) -> Hmac<Sha256> {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any size");
+    mac.update(format!("{}:{}", key, expires).as_bytes());
+    return mac;
+}
+
+fn sign(secret: &str, key: &str, expires: i64) -> String {
+    return base64::encode_config(
+        signature(secret, key, expires).finalize().into_bytes(),
+        base64::URL_SAFE_NO_PAD,
+    );
+}
+
+fn verify(secret: &str, key: &str, expires: i64, signed: &str, now: i64) -> bool {
+    if expires < now {
+        return false;
+    }
+    match base64::decode_config(signed, base64::URL_SAFE_NO_PAD) {
+        Ok(signed) => {
+            return signature(secret, key, expires)
+                .verify_slice(&signed)
+                .is_ok()
+        }
+        Err(_) => return false,
+    }
+}
+
+/// Path of the stored audio the bot hands to Lavalink, which can't send the websocket token.
+pub fn signed_audio_path(key: &str) -> String {
+    let expires = Utc::now().timestamp() + AUDIO_PATH_EXPIRY_SECS;
+    return format!(
+        "/v1/files/audio/{}?expires={}&signature={}",
+        key,
+        expires,
+        sign(&SIGNING_KEY, key, expires)
+    );
+}
+
+pub fn is_valid_audio_signature(key: &str, expires: i64, signed: &str) -> bool {
+    return verify(&SIGNING_KEY, key, expires, signed, Utc::now().timestamp());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sign, verify};
+
+    #[test]
+    fn test_audio_signature() {
+        let signed = sign("secret", "1", 100);
+        assert!(verify("secret", "1", 100, &signed, 50));
+        assert!(!verify("secret", "1", 100, &signed, 101));
+        assert!(!verify("secret", "2", 100, &signed, 50));
+        assert!(!verify("secret", "1", 200, &signed, 50));
+        assert!(!verify("other", "1", 100, &signed, 50));
+        assert!(!verify("secret", "1", 100, "not base64!", 50));
+    }
+}
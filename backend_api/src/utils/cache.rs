@@ -10,16 +10,24 @@ use crate::{
     models::ids::{GuildId, UserId},
 };
 
-use super::{auth::{AccessToken, AuthorizedUser}, permissions::Permissions};
+use super::{
+    auth::{AccessToken, AuthorizedUser},
+    permissions::Permissions,
+};
 
 pub type UserGuildsCache = Cache<UserId, Arc<Vec<DiscordGuild>>>;
 pub type AuthorizedUsersCache = Cache<Arc<AccessToken>, Arc<AuthorizedUser>>;
 pub struct UserGuildsMiddlwareQueueCache(pub Cache<Arc<AccessToken>, Arc<Notify>>);
 pub struct AuthMiddlewareQueueCache(pub Cache<Arc<AccessToken>, Arc<Notify>>);
+/// Trimmed previews keyed by storage key and trim points.
+pub type TrimmedAudioCache = Cache<(String, Option<i32>, Option<i32>), Arc<Vec<u8>>>;
 
 pub const USER_GUILDS_CACHE_TTL: u64 = 60 * 5;
 pub const AUTHORIZED_USER_CACHE_TTL: u64 = 60 * 5;
 pub const QUEUE_CACHE_TTL: u64 = 60 * 5;
+pub const TRIMMED_AUDIO_CACHE_TTL: u64 = 60 * 60;
+/// Total size of cached previews in bytes
+pub const TRIMMED_AUDIO_CACHE_CAPACITY: u64 = 100_000_000;
 
 #[derive(Clone, Debug, Serialize, Deserialize, Hash, PartialEq, Eq)]
 pub struct DiscordGuild {
@@ -66,6 +74,14 @@ pub fn create_auth_middlware_queue_cache() -> AuthMiddlewareQueueCache {
     );
 }
 
+pub fn create_trimmed_audio_cache() -> TrimmedAudioCache {
+    return Cache::builder()
+        .max_capacity(TRIMMED_AUDIO_CACHE_CAPACITY)
+        .weigher(|_, audio: &Arc<Vec<u8>>| audio.len().min(u32::MAX as usize) as u32)
+        .time_to_live(Duration::from_secs(TRIMMED_AUDIO_CACHE_TTL))
+        .build();
+}
+
 pub struct UserGuildsCacheUtil;
 
 impl UserGuildsCacheUtil {
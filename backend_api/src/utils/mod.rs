@@ -7,20 +7,19 @@ use serde::{Deserialize, Deserializer, Serializer};
 
 use crate::{error::errors::KekServerError, models::ids::Id};
 
-use reqwest::{
-    header::{AUTHORIZATION},
-    Client, Error, Response, StatusCode,
-};
+use reqwest::{header::AUTHORIZATION, Client, Error, Response, StatusCode};
 
 use self::auth::AuthorizedUser;
 
 pub mod auth;
 pub mod cache;
-pub mod validation;
 pub mod permissions;
+pub mod validation;
 
 pub const USERGUILDS: &str = "/users/@me/guilds";
 pub const MAX_RETRIES: u8 = 3;
+/// Snowflake id epoch start time 01.01.2022. 00:00
+pub const SNOWFLAKE_EPOCH_MS: u64 = 1640991600000;
 
 pub fn deserialize_string_to_number<'de, T, D>(deserializer: D) -> Result<T, D::Error>
 where
@@ -41,6 +40,15 @@ where
     };
 }
 
+/// Distinguishes a field set to `null` from a missing field, use with `#[serde(default)]`.
+pub fn deserialize_present<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    return Option::<T>::deserialize(deserializer).map(Some);
+}
+
 pub fn serialize_id_to_string<S>(num: &dyn Id, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -49,10 +57,12 @@ where
 }
 
 async fn get_request(autorized_user: &AuthorizedUser, url: &str) -> Result<Response, Error> {
-
     return Client::new()
         .get(format!("https://discord.com/api/v9{}", url))
-        .header(AUTHORIZATION, format!("Bearer {}", &autorized_user.access_token.0))
+        .header(
+            AUTHORIZATION,
+            format!("Bearer {}", &autorized_user.access_token.0),
+        )
         .send()
         .await;
 }
@@ -99,12 +109,15 @@ pub mod test_utils {
     use sqlx::{Postgres, Transaction};
     use uuid::Uuid;
 
-    use crate::models::{
-        guild::Guild,
-        guild_file::GuildFile,
-        ids::{GuildId, SoundFileId, UserId},
-        sound_file::SoundFile,
-        user::User,
+    use crate::{
+        models::{
+            guild::Guild,
+            guild_file::GuildFile,
+            ids::{GuildId, SoundFileId, UserId},
+            sound_file::{AudioMetadata, ProcessingStatus, SoundFile},
+            user::User,
+        },
+        storage::sound_file_key,
     };
 
     pub struct PublicDeleted {
@@ -170,24 +183,39 @@ pub mod test_utils {
         let id = SoundFileId(Uuid::new_v4().as_u128() as u64);
         let owner = insert_user_test_util(&mut *transaction).await;
         let now = Utc::now().naive_utc();
+        let storage_key = sound_file_key(&id);
         let sound_file = SoundFile {
             id,
             display_name: Some("Test file name".to_string()),
             time_added: now,
             is_deleted: false,
             is_public: false,
+            processing_status: ProcessingStatus::Unprocessed,
+            metadata: AudioMetadata::default(),
+            storage_key,
+            content_hash: None,
+            size: None,
+            description: None,
+            start_ms: None,
+            end_ms: None,
+            play_count: 0,
+            forked_from: None,
+            forked_from_owner: None,
+            tags: Vec::new(),
+            is_favorite: false,
             owner: Some(owner.id),
         };
         sqlx::query!(
             "
-            INSERT INTO files (id, display_name, owner, is_public, time_added)
-            VALUES ($1, $2, $3, $4, $5)
+            INSERT INTO files (id, display_name, owner, is_public, time_added, storage_key)
+            VALUES ($1, $2, $3, $4, $5, $6)
             ",
             sound_file.id.0 as i64,
             sound_file.display_name,
             sound_file.owner.as_ref().map(|o| o.0 as i64),
             sound_file.is_public,
-            sound_file.time_added
+            sound_file.time_added,
+            sound_file.storage_key
         )
         .execute(transaction)
         .await
@@ -207,25 +235,48 @@ pub mod test_utils {
             is_deleted: false,
             is_public: false,
         });
+        let storage_key = sound_file_key(&id);
         let sound_file = SoundFile {
             id,
             display_name: Some("Test file name".to_string()),
             time_added: now,
             is_deleted: public_deleted.is_deleted,
             is_public: public_deleted.is_public,
+            processing_status: ProcessingStatus::Unprocessed,
+            metadata: AudioMetadata::default(),
+            storage_key,
+            content_hash: None,
+            size: None,
+            description: None,
+            start_ms: None,
+            end_ms: None,
+            play_count: 0,
+            forked_from: None,
+            forked_from_owner: None,
+            tags: Vec::new(),
+            is_favorite: false,
             owner: Some(owner_id.clone()),
         };
         sqlx::query!(
             "
-            INSERT INTO files (id, display_name, owner, is_public, time_added, is_deleted)
-            VALUES ($1, $2, $3, $4, $5, $6)
+            INSERT INTO files (
+                id,
+                display_name,
+                owner,
+                is_public,
+                time_added,
+                is_deleted,
+                storage_key
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
             ",
             sound_file.id.0 as i64,
             sound_file.display_name,
             sound_file.owner.as_ref().map(|o| o.0 as i64),
             sound_file.is_public,
             sound_file.time_added,
-            sound_file.is_deleted
+            sound_file.is_deleted,
+            sound_file.storage_key
         )
         .execute(transaction)
         .await
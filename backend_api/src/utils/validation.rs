@@ -5,6 +5,9 @@ use crate::{
     error::errors::KekServerError,
     models::{
         guild::Guild,
+        guild_manager::GuildManager,
+        guild_role::{GuildAction, GuildMemberRoles, GuildRolePermission},
+        guild_settings::GuildSettings,
         ids::{GuildId, SoundFileId, UserId},
         sound_file::SoundFile,
     },
@@ -84,7 +87,8 @@ impl Validation {
         return Ok(());
     }
 
-    pub fn has_permissions(
+    /// Only trusts the Discord permission bits of the cached guild.
+    pub fn has_discord_permissions(
         authorized_user: &AuthorizedUser,
         guild_id: &GuildId,
         user_guilds_cache: &Data<UserGuildsCache>,
@@ -104,4 +108,110 @@ impl Validation {
 
         return Ok(permissions.is_admin() || permissions.other());
     }
+
+    /// Soundboard managers of the guild have the same permissions as users with Discord permissions.
+    pub async fn has_permissions(
+        authorized_user: &AuthorizedUser,
+        guild_id: &GuildId,
+        user_guilds_cache: &Data<UserGuildsCache>,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<bool, KekServerError> {
+        if Validation::has_discord_permissions(authorized_user, guild_id, user_guilds_cache)? {
+            return Ok(true);
+        }
+        return GuildManager::is_manager(
+            guild_id,
+            &authorized_user.discord_user.id,
+            &mut *transaction,
+        )
+        .await;
+    }
+
+    /// Users with permissions can always add files to the guild, other members can only add
+    /// files they don't own if the guild settings allow it.
+    pub async fn can_add_public_files(
+        authorized_user: &AuthorizedUser,
+        guild_id: &GuildId,
+        user_guilds_cache: &Data<UserGuildsCache>,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        let settings = GuildSettings::get(guild_id, &mut *transaction).await?;
+        if settings.members_can_add_public_files
+            || Validation::has_permissions(
+                authorized_user,
+                guild_id,
+                user_guilds_cache,
+                &mut *transaction,
+            )
+            .await?
+        {
+            return Ok(());
+        }
+        return Err(KekServerError::UnauthorizedFileAccessError(
+            "Only own files can be added to this guild".to_string(),
+        ));
+    }
+
+    /// Checks the guild's role permissions for the action, users with permissions are always allowed.
+    pub async fn is_action_allowed(
+        authorized_user: &AuthorizedUser,
+        guild_id: &GuildId,
+        action: GuildAction,
+        user_guilds_cache: &Data<UserGuildsCache>,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        if Validation::has_permissions(
+            authorized_user,
+            guild_id,
+            user_guilds_cache,
+            &mut *transaction,
+        )
+        .await?
+        {
+            return Ok(());
+        }
+        let permissions =
+            GuildRolePermission::get_guild_permissions(guild_id, &mut *transaction).await?;
+        let member_roles = GuildMemberRoles::get(
+            guild_id,
+            &authorized_user.discord_user.id,
+            &mut *transaction,
+        )
+        .await?;
+        if !GuildRolePermission::is_allowed(&permissions, &member_roles, action) {
+            return Err(KekServerError::MissingGuildPermissionError(
+                action.to_string(),
+            ));
+        }
+        return Ok(());
+    }
+
+    /// Checks if the user may enable files in the guild, `only_own_files` skips the
+    /// guild setting restricting files owned by others.
+    pub async fn can_add_to_guild(
+        authorized_user: &AuthorizedUser,
+        guild_id: &GuildId,
+        only_own_files: bool,
+        user_guilds_cache: &Data<UserGuildsCache>,
+        transaction: &mut Transaction<'_, Postgres>,
+    ) -> Result<(), KekServerError> {
+        Validation::is_action_allowed(
+            authorized_user,
+            guild_id,
+            GuildAction::Add,
+            user_guilds_cache,
+            &mut *transaction,
+        )
+        .await?;
+        if !only_own_files {
+            Validation::can_add_public_files(
+                authorized_user,
+                guild_id,
+                user_guilds_cache,
+                &mut *transaction,
+            )
+            .await?;
+        }
+        return Ok(());
+    }
 }
@@ -1,7 +1,4 @@
-use std::{
-    collections::HashMap,
-    sync::Arc,
-};
+use std::{collections::HashMap, sync::Arc};
 
 use actix::{
     Actor, Addr, Context, Handler, Message, MessageResult, ResponseFuture, Supervised, Supervisor,
@@ -6,10 +6,14 @@ use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use uuid::Uuid;
 
-use crate::models::{
-    guild_file::GuildFile,
-    ids::{ChannelId, GuildId, SoundFileId},
-    sound_file::SoundFilePartial,
+use crate::{
+    models::{
+        guild_file::GuildFile,
+        guild_settings::GuildSettings,
+        ids::{ChannelId, GuildId, SoundFileId},
+        sound_file::SoundFilePartial,
+    },
+    storage::signed_url::signed_audio_path,
 };
 
 use super::ws_session::ControlsSession;
@@ -38,20 +42,36 @@ pub struct PlayControl {
     file_id: SoundFileId,
     voice_channel_id: Option<ChannelId>,
     display_name: String,
+    /// Signed path the bot loads the stored audio from, relative to the backend url
+    audio_path: String,
+    /// Only the segment between the trim points is played
+    start_ms: Option<i32>,
+    end_ms: Option<i32>,
+    /// Guild settings the bot applies while playing
+    volume: i16,
+    max_queue_length: i16,
+    default_voice_channel_id: Option<ChannelId>,
 }
 
 impl PlayControl {
-    pub fn new(guild_file: GuildFile, voice_channel_id: Option<ChannelId>) -> Self {
+    pub fn new(
+        guild_file: GuildFile,
+        voice_channel_id: Option<ChannelId>,
+        settings: &GuildSettings,
+    ) -> Self {
         // WARN: expects sound_file field in GuildFile to be Some
+        let sound_file = guild_file.sound_file.unwrap();
         return Self {
             guild_id: guild_file.guild_id,
             file_id: guild_file.file_id,
-            display_name: guild_file
-                .sound_file
-                .unwrap()
-                .display_name
-                .unwrap_or_default(),
+            display_name: sound_file.display_name.unwrap_or_default(),
             voice_channel_id,
+            audio_path: signed_audio_path(&sound_file.storage_key),
+            start_ms: sound_file.start_ms,
+            end_ms: sound_file.end_ms,
+            volume: settings.volume,
+            max_queue_length: settings.max_queue_length,
+            default_voice_channel_id: settings.default_voice_channel_id.clone(),
         };
     }
 }
@@ -182,13 +202,18 @@ impl ControlsServerMessage {
         };
     }
 
-    pub fn new_play(guild_file: GuildFile, voice_channel_id: Option<ChannelId>) -> Self {
+    pub fn new_play(
+        guild_file: GuildFile,
+        voice_channel_id: Option<ChannelId>,
+        settings: &GuildSettings,
+    ) -> Self {
         return Self {
             op: OpCode::Play,
             message_id: Uuid::new_v4().as_u128(),
             control: Some(Controls::Play(PlayControl::new(
                 guild_file,
                 voice_channel_id,
+                settings,
             ))),
             client_error: None,
             queue: None,
@@ -317,8 +342,9 @@ mod tests {
     use crate::{
         models::{
             guild_file::GuildFile,
+            guild_settings::GuildSettings,
             ids::{GuildId, SoundFileId},
-            sound_file::SoundFile,
+            sound_file::{AudioMetadata, ProcessingStatus, SoundFile},
         },
         ws::ws_server::{Controls, OpCode},
     };
@@ -342,9 +368,22 @@ mod tests {
                 time_added: NaiveDateTime::from_timestamp(0, 0),
                 is_public: false,
                 owner: None,
+                processing_status: ProcessingStatus::Ready,
+                metadata: AudioMetadata::default(),
+                storage_key: FILE.0.to_string(),
+                content_hash: None,
+                size: None,
+                description: None,
+                start_ms: None,
+                end_ms: None,
+                play_count: 0,
+                forked_from: None,
+                forked_from_owner: None,
+                tags: Vec::new(),
+                is_favorite: false,
             }),
         };
-        let play = ControlsServerMessage::new_play(guild_file, None);
+        let play = ControlsServerMessage::new_play(guild_file, None, &GuildSettings::new(GUILD));
         let control = play.control.unwrap();
         assert!(match play.op {
             OpCode::Play => true,
@@ -16,9 +16,11 @@ use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::{
+    error::errors::KekServerError,
     models::{
         guild::Guild,
-        ids::{GuildId, UserId},
+        guild_role::{GuildMemberRoles, MemberRoles},
+        ids::{GuildId, RoleId, UserId},
     },
     utils::cache::UserGuildsCache,
     ws::channels_server::{DisconnectSyncSession, InvalidateClient},
@@ -39,6 +41,8 @@ enum SyncOpCode {
     UpdateGuildChannels,
     AddGuild,
     RemoveGuild,
+    UpdateMemberRoles,
+    UpdateGuildMemberRoles,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -47,6 +51,10 @@ struct SyncMessage {
     user_id: Option<UserId>,
     guild_id: Option<GuildId>,
     guild_voice_channels: Option<GuildVoiceChannels>,
+    /// Sent with `UpdateMemberRoles`
+    role_ids: Option<Vec<RoleId>>,
+    /// Sent with `UpdateGuildMemberRoles`
+    member_roles: Option<Vec<MemberRoles>>,
 }
 
 #[derive(Message, Serialize)]
@@ -61,6 +69,29 @@ pub struct RemoveGuild {
     pub guild_id: GuildId,
 }
 
+async fn update_member_roles(
+    pool: &PgPool,
+    guild_id: &GuildId,
+    user_id: &UserId,
+    role_ids: &[RoleId],
+) -> Result<(), KekServerError> {
+    let mut transaction = pool.begin().await?;
+    GuildMemberRoles::update(guild_id, user_id, role_ids, &mut transaction).await?;
+    transaction.commit().await?;
+    return Ok(());
+}
+
+async fn update_guild_member_roles(
+    pool: &PgPool,
+    guild_id: &GuildId,
+    members: &[MemberRoles],
+) -> Result<(), KekServerError> {
+    let mut transaction = pool.begin().await?;
+    GuildMemberRoles::update_members(guild_id, members, &mut transaction).await?;
+    transaction.commit().await?;
+    return Ok(());
+}
+
 pub struct SyncSession {
     id: u128,
     heartbeat: Instant,
@@ -139,6 +170,8 @@ impl Handler<AddGuild> for SyncSession {
             guild_id: Some(msg.guild_id),
             user_id: None,
             guild_voice_channels: None,
+            role_ids: None,
+            member_roles: None,
         }) {
             Ok(m) => {
                 ctx.text(m);
@@ -160,6 +193,8 @@ impl Handler<RemoveGuild> for SyncSession {
             guild_id: Some(msg.guild_id),
             user_id: None,
             guild_voice_channels: None,
+            role_ids: None,
+            member_roles: None,
         }) {
             Ok(m) => {
                 ctx.text(m);
@@ -254,6 +289,41 @@ impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for SyncSession {
                                 }
                             }
                         }
+                        SyncOpCode::UpdateMemberRoles => {
+                            if let (Some(guild_id), Some(user_id), Some(role_ids)) =
+                                (message.guild_id, message.user_id, message.role_ids)
+                            {
+                                debug!("Updating roles of user with id: {}", &user_id.0);
+                                if let Err(e) =
+                                    update_member_roles(&pool, &guild_id, &user_id, &role_ids).await
+                                {
+                                    error!(
+                                        "Failed to update roles of user with id: [{}], error: {}",
+                                        &user_id.0, e
+                                    );
+                                }
+                            }
+                        }
+                        SyncOpCode::UpdateGuildMemberRoles => {
+                            if let (Some(guild_id), Some(member_roles)) =
+                                (message.guild_id, message.member_roles)
+                            {
+                                debug!(
+                                    "Updating roles of {} members of guild with id: {}",
+                                    member_roles.len(),
+                                    &guild_id.0
+                                );
+                                if let Err(e) =
+                                    update_guild_member_roles(&pool, &guild_id, &member_roles)
+                                        .await
+                                {
+                                    error!(
+                                        "Failed to update member roles of guild with id: [{}], error: {}",
+                                        &guild_id.0, e
+                                    );
+                                }
+                            }
+                        }
                         SyncOpCode::AddGuild | SyncOpCode::RemoveGuild => {}
                     }
                 }
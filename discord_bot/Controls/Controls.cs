@@ -33,7 +33,7 @@ namespace KekovBot.Control
             return guild;
         }
 
-        private static async Task<bool> PlaySound(DiscordChannel channel, Sound sound)
+        private static async Task<bool> PlaySound(DiscordChannel channel, Sound sound, ControlMessage msg)
         {
             if (!_lavalink.ConnectedNodes.Any())
             {
@@ -66,6 +66,10 @@ namespace KekovBot.Control
             }
 
             var playQueue = PlayQueueDict[guild];
+            // Guild settings are sent with every play command
+            playQueue.QueueLimit = msg.MaxQueueLength ?? PlayQueue.DefaultQueueLimit;
+            await connection.SetVolumeAsync(msg.Volume ?? 100);
+
             if (AwaitingDisconnectDict.Contains(guild))
             {
                 var cancelToken = CancelationTokenDict[guild];
@@ -122,12 +126,25 @@ namespace KekovBot.Control
             }
             else
             {
-                foreach (var channel in guild.Channels.Values)
+                if (msg.DefaultVoiceChannelId != null)
+                {
+                    DiscordChannel? defaultChannel;
+                    guild.Channels.TryGetValue((ulong)msg.DefaultVoiceChannelId, out defaultChannel);
+                    if (defaultChannel != null && defaultChannel.Type == ChannelType.Voice && defaultChannel.Users.Count >= 1)
+                    {
+                        voiceChannel = defaultChannel;
+                    }
+                }
+                // Fall back to the first voice channel with users in it
+                if (voiceChannel == null)
                 {
-                    if (channel.Type == ChannelType.Voice && channel.Users.Count >= 1)
+                    foreach (var channel in guild.Channels.Values)
                     {
-                        voiceChannel = channel;
-                        break;
+                        if (channel.Type == ChannelType.Voice && channel.Users.Count >= 1)
+                        {
+                            voiceChannel = channel;
+                            break;
+                        }
                     }
                 }
                 if (voiceChannel == null)
@@ -141,13 +158,13 @@ namespace KekovBot.Control
                 throw new ChannelNotFoundException();
             }
 
-            if (msg.FileId == null || msg.DisplayName == null)
+            if (msg.FileId == null || msg.DisplayName == null || msg.AudioPath == null)
             {
                 throw new InvalidFileIdException();
             }
 
-            var sound = new Sound((ulong)msg.FileId, msg.DisplayName);
-            return await PlaySound(voiceChannel, sound);
+            var sound = new Sound((ulong)msg.FileId, msg.DisplayName, msg.AudioPath, msg.StartMs, msg.EndMs);
+            return await PlaySound(voiceChannel, sound, msg);
         }
 
         public static async Task Stop(ControlMessage msg)
@@ -23,9 +23,9 @@ namespace KekovBot.Control
             }
         }
 
-        public static async Task<LavalinkTrack> GetTrack(this LavalinkGuildConnection conn, FileInfo file)
+        public static async Task<LavalinkTrack> GetTrack(this LavalinkGuildConnection conn, Uri uri)
         {
-            var loadResult = await conn.GetTracksAsync(file);
+            var loadResult = await conn.GetTracksAsync(uri);
             if (loadResult.LoadResultType == LavalinkLoadResultType.LoadFailed || loadResult.LoadResultType == LavalinkLoadResultType.NoMatches)
             {
                 throw new FileLoadingFailedException();
@@ -33,6 +33,17 @@ namespace KekovBot.Control
             return loadResult.Tracks.First();
         }
 
+        public static async Task PlaySound(this LavalinkGuildConnection conn, Sound sound)
+        {
+            var track = await conn.GetTrack(sound.AudioUri);
+            if (sound.IsTrimmed())
+            {
+                await conn.PlayPartialAsync(track, sound.Start ?? TimeSpan.Zero, sound.End ?? track.Length);
+                return;
+            }
+            await conn.PlayAsync(track);
+        }
+
         public static void RegisterConnectionHandlers(this LavalinkGuildConnection conn, PlayQueue playQueue)
         {
             var guild = conn.Guild;
@@ -5,7 +5,8 @@ namespace KekovBot.Control
 {
     public class PlayQueue
     {
-        private static int _queue_limit = 10;
+        public const int DefaultQueueLimit = 10;
+        public int QueueLimit { get; set; } = DefaultQueueLimit;
         public Sound? CurrentlyPlaying { get; set; }
         public LavalinkGuildConnection GuildConnection { get; set; }
 
@@ -23,8 +24,7 @@ namespace KekovBot.Control
             try {
                 CurrentlyPlaying = startSound;
                 _queue.Clear();
-                var track = await GuildConnection.GetTrack(CurrentlyPlaying.FileInfo);
-                await GuildConnection.PlayAsync(track);
+                await GuildConnection.PlaySound(CurrentlyPlaying);
             } catch (FileLoadingFailedException e) {
                 await GuildConnection.Disconnect();
                 throw e;
@@ -37,8 +37,7 @@ namespace KekovBot.Control
             if (_queue.Count > 0)
             {
                 CurrentlyPlaying = _queue.Dequeue();
-                var track = await GuildConnection.GetTrack(CurrentlyPlaying.FileInfo);
-                await GuildConnection.PlayAsync(track);
+                await GuildConnection.PlaySound(CurrentlyPlaying);
                 return true;
             }
             CurrentlyPlaying = null;
@@ -60,7 +59,7 @@ namespace KekovBot.Control
         }
 
         public void Enqueue(Sound sound) {
-            if (_queue.Count < _queue_limit) {
+            if (_queue.Count < QueueLimit) {
                 _queue.Enqueue(sound);
             } else {
                 throw new QueueFullException();
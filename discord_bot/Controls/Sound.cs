@@ -6,7 +6,7 @@ namespace KekovBot.Control
 {
     public class Sound
     {
-        private static string _soundFileDir = DotEnv.Read()["SOUNDFILE_DIR"];
+        private static string _backendUrl = DotEnv.Read()["BACKEND_URL"].TrimEnd('/');
 
         [JsonProperty("id")]
         [JsonConverter(typeof(ToStringConverter))]
@@ -16,13 +16,28 @@ namespace KekovBot.Control
         public string DisplayName;
 
         [JsonIgnore]
-        public FileInfo FileInfo;
+        public Uri AudioUri;
 
-        public Sound(ulong fileId, string displayName)
+        // Only the segment between the trim points is played, the file itself isn't trimmed
+        [JsonIgnore]
+        public TimeSpan? Start;
+
+        [JsonIgnore]
+        public TimeSpan? End;
+
+        public Sound(ulong fileId, string displayName, string audioPath, int? startMs = null, int? endMs = null)
         {
             FileId = fileId;
             DisplayName = displayName;
-            FileInfo = new FileInfo($"{_soundFileDir}{FileId}");
+            // Lavalink loads the audio from the backend, the path is signed so no token is needed
+            AudioUri = new Uri($"{_backendUrl}{audioPath}");
+            Start = startMs != null ? TimeSpan.FromMilliseconds((int)startMs) : null;
+            End = endMs != null ? TimeSpan.FromMilliseconds((int)endMs) : null;
+        }
+
+        public bool IsTrimmed()
+        {
+            return Start != null || End != null;
         }
     }
 }
@@ -54,6 +54,8 @@ namespace KekovBot.Bot
         {
             DiscordClient.GuildMemberAdded += GuildMemberAddedEvent;
             DiscordClient.GuildMemberRemoved += GuildMemberRemovedEvent;
+            DiscordClient.GuildMemberUpdated += GuildMemberUpdatedEvent;
+            DiscordClient.GuildAvailable += GuildAvailableEvent;
             DiscordClient.GuildCreated += BotJoinedGuildEvent;
             DiscordClient.GuildDeleted += BotLeftGuildEvent;
 
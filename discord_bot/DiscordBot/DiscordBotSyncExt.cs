@@ -1,4 +1,5 @@
 using DSharpPlus;
+using DSharpPlus.Entities;
 using DSharpPlus.EventArgs;
 using KekovBot.WebSocket;
 using Newtonsoft.Json;
@@ -13,6 +14,19 @@ namespace KekovBot.Bot
             var response = new SyncMessage(SyncOpCode.UpdateUserCache, args.Member.Id, null);
             var responseJson = JsonConvert.SerializeObject(response);
             SyncWebsocket.Client.Send(responseJson);
+            SendMemberRoles(args.Member);
+            return Task.CompletedTask;
+        }
+
+        private Task GuildMemberUpdatedEvent(DiscordClient c, GuildMemberUpdateEventArgs args)
+        {
+            SendMemberRoles(args.Member);
+            return Task.CompletedTask;
+        }
+
+        private Task GuildAvailableEvent(DiscordClient c, GuildCreateEventArgs args)
+        {
+            SendGuildMemberRoles(args.Guild);
             return Task.CompletedTask;
         }
 
@@ -31,6 +45,7 @@ namespace KekovBot.Bot
             var response = new SyncMessage(SyncOpCode.InvalidateGuildsCache, null, args.Guild.Id);
             var responseJson = JsonConvert.SerializeObject(response);
             SyncWebsocket.Client.Send(responseJson);
+            SendGuildMemberRoles(args.Guild);
             return Task.CompletedTask;
         }
 
@@ -165,6 +180,22 @@ namespace KekovBot.Bot
             return Task.CompletedTask;
         }
 
+        // Roles are used by the backend for role based permissions
+        private void SendMemberRoles(DiscordMember member)
+        {
+            var response = new SyncMessage(member.Guild.Id, member.Id, member.Roles.Select(role => role.Id));
+            var responseJson = JsonConvert.SerializeObject(response);
+            SyncWebsocket.Client.Send(responseJson);
+        }
+
+        // Roles of all members are sent in one message instead of a message per member
+        private void SendGuildMemberRoles(DiscordGuild guild)
+        {
+            var response = new SyncMessage(guild.Id, guild.Members.Values.Select(member => new MemberRoles(member)));
+            var responseJson = JsonConvert.SerializeObject(response);
+            SyncWebsocket.Client.Send(responseJson);
+        }
+
         private void SendSyncMessage(GuildVoiceChannels guildVoiceChannels, ulong guildId)
         {
             var response = new SyncMessage(guildVoiceChannels, guildId);
@@ -37,6 +37,25 @@ namespace KekovBot.WebSocket
         [JsonProperty("display_name")]
         public string? DisplayName { get; set; }
 
+        [JsonProperty("audio_path")]
+        public string? AudioPath { get; set; }
+
+        [JsonProperty("start_ms")]
+        public int? StartMs { get; set; }
+
+        [JsonProperty("end_ms")]
+        public int? EndMs { get; set; }
+
+        [JsonProperty("volume")]
+        public int? Volume { get; set; }
+
+        [JsonProperty("max_queue_length")]
+        public int? MaxQueueLength { get; set; }
+
+        [JsonProperty("default_voice_channel_id")]
+        [JsonConverter(typeof(ToStringConverter))]
+        public ulong? DefaultVoiceChannelId { get; set; }
+
         public ControlMessage() { }
 
         public ControlMessage(OpCode code, List<Sound>? queue, ControlMessage other)
@@ -48,6 +67,12 @@ namespace KekovBot.WebSocket
             MessageId = other.MessageId;
             Queue = queue;
             DisplayName = other.DisplayName;
+            AudioPath = other.AudioPath;
+            StartMs = other.StartMs;
+            EndMs = other.EndMs;
+            Volume = other.Volume;
+            MaxQueueLength = other.MaxQueueLength;
+            DefaultVoiceChannelId = other.DefaultVoiceChannelId;
         }
 
         public ControlMessage(ClientError error, ControlMessage other) : this(OpCode.Error, null, other)
@@ -0,0 +1,21 @@
+using DSharpPlus.Entities;
+using Newtonsoft.Json;
+
+namespace KekovBot.WebSocket
+{
+    public class MemberRoles
+    {
+        [JsonProperty("user_id")]
+        [JsonConverter(typeof(ToStringConverter))]
+        public ulong UserId { get; set; }
+
+        [JsonProperty("role_ids")]
+        public List<string> RoleIds { get; set; }
+
+        public MemberRoles(DiscordMember member)
+        {
+            UserId = member.Id;
+            RoleIds = member.Roles.Select(role => role.Id.ToString()).ToList();
+        }
+    }
+}
@@ -21,6 +21,12 @@ namespace KekovBot.WebSocket
         [JsonProperty("guild_voice_channels")]
         public GuildVoiceChannels? GuildVoiceChannels { get; set; }
 
+        [JsonProperty("role_ids")]
+        public List<string>? RoleIds { get; set; }
+
+        [JsonProperty("member_roles")]
+        public List<MemberRoles>? MemberRoles { get; set; }
+
         public SyncMessage() { }
 
         public SyncMessage(SyncOpCode opCode, Nullable<ulong> userId, Nullable<ulong> guildId)
@@ -37,6 +43,21 @@ namespace KekovBot.WebSocket
             GuildVoiceChannels = guildVoiceChannels;
         }
 
+        public SyncMessage(ulong guildId, ulong userId, IEnumerable<ulong> roleIds)
+        {
+            OpCode = SyncOpCode.UpdateMemberRoles;
+            GuildId = guildId;
+            UserId = userId;
+            RoleIds = roleIds.Select(id => id.ToString()).ToList();
+        }
+
+        public SyncMessage(ulong guildId, IEnumerable<MemberRoles> memberRoles)
+        {
+            OpCode = SyncOpCode.UpdateGuildMemberRoles;
+            GuildId = guildId;
+            MemberRoles = memberRoles.ToList();
+        }
+
         public SyncMessage(SyncOpCode code, SyncMessage other)
         {
             OpCode = code;
@@ -7,5 +7,7 @@ namespace KekovBot.WebSocket
         UpdateGuildChannels,
         AddGuild,
         RemoveGuild,
+        UpdateMemberRoles,
+        UpdateGuildMemberRoles,
     }
 }
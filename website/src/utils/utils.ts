@@ -154,6 +154,12 @@ export type GuildFile = {
     sound_file: SoundFile;
 };
 
+export type GuildFilesPage = {
+    files: GuildFile[];
+    next: string | null;
+    prev: string | null;
+};
+
 export type QueueReponse = {
     id: string;
     display_name: string;
@@ -165,9 +171,11 @@ export type BulkEnablePayload = {
 };
 
 export type PublicFiles = {
-    count: number;
+    count: number | null;
     max: number;
     files: SoundFile[];
+    next: string | null;
+    prev: string | null;
 };
 
 export type UploadedFile = {
@@ -306,7 +314,7 @@ export const ApiRequest = {
         guildId: string,
         abortController: AbortController | undefined,
         accessToken: string
-    ): Promise<AxiosResponse<GuildFile[]>> => {
+    ): Promise<AxiosResponse<GuildFilesPage>> => {
         return axiosInstance.get(`${GuildRoute.getGuildSounds}${guildId}`, {
             ...authorizationHeaders(accessToken),
             signal: abortController?.signal,
@@ -375,6 +383,7 @@ export const ApiRequest = {
     },
     getPublicFiles: (
         page: string | null,
+        cursor: string | null,
         limit: string | null,
         search_query: string | null,
         accessToken: string,
@@ -382,8 +391,10 @@ export const ApiRequest = {
     ): Promise<AxiosResponse<PublicFiles>> => {
         return axiosInstance.get(
             `${FilesRoute.getPublic}?page=${page ?? "0"}${
-                limit ? "&limit=" + limit : ""
-            }${search_query ? "&search_query=" + search_query : ""}`,
+                cursor ? "&cursor=" + cursor : ""
+            }${limit ? "&limit=" + limit : ""}${
+                search_query ? "&search_query=" + search_query : ""
+            }`,
             {
                 ...authorizationHeaders(accessToken),
                 signal: abortController?.signal,
@@ -154,12 +154,12 @@ export default function Guild() {
                         abortController,
                         cookies.access_token
                     );
-                    data.sort((a, b) => {
+                    data.files.sort((a, b) => {
                         return (
                             Date.parse(a.time_added) - Date.parse(b.time_added)
                         );
                     });
-                    setGuildFiles(data);
+                    setGuildFiles(data.files);
                     setIsUpdating(false);
                 } catch (e: any | AxiosError) {
                     console.log(e);
@@ -1,5 +1,6 @@
 import {
     Box,
+    Button,
     Center,
     createStyles,
     Grid,
@@ -123,12 +124,14 @@ export default function PublicFiles() {
     );
 
     const paramsPage = searchParams.get("page");
+    const paramsCursor = searchParams.get("cursor");
     const paramsLimit = searchParams.get("limit");
     const paramsSearchQuery = searchParams.get("search_query");
 
     const handleSearch = useCallback(
         (search: string) => {
             searchParams.set("page", "1");
+            searchParams.delete("cursor");
             searchParams.set("search_query", search);
             setSearchParams(searchParams);
         },
@@ -142,6 +145,7 @@ export default function PublicFiles() {
                     abortController = new AbortController();
                     const { data } = await ApiRequest.getPublicFiles(
                         paramsPage,
+                        paramsCursor,
                         paramsLimit,
                         paramsSearchQuery,
                         cookies.access_token,
@@ -159,11 +163,18 @@ export default function PublicFiles() {
         setIsFetching(true);
         fetchFiles();
         setSelectedFile(undefined);
-    }, [cookies.access_token, paramsPage, paramsLimit, paramsSearchQuery]);
+    }, [
+        cookies.access_token,
+        paramsPage,
+        paramsCursor,
+        paramsLimit,
+        paramsSearchQuery,
+    ]);
 
     useEffect(() => {
         setTotal((old) => {
-            if (publicFiles === undefined) {
+            // Listings without a search are paginated with cursors
+            if (publicFiles === undefined || publicFiles.count === null) {
                 return old;
             }
             const paramsNumCalc = Number(paramsLimit ?? "NaN");
@@ -226,14 +237,45 @@ export default function PublicFiles() {
                         </Group>
                     </ScrollArea>
                     <Center>
-                        <Pagination
-                            page={getPageNumber(paramsPage)}
-                            onChange={(page) => {
-                                searchParams.set("page", page.toString());
-                                setSearchParams(searchParams);
-                            }}
-                            total={total}
-                        />
+                        {paramsSearchQuery !== null ? (
+                            <Pagination
+                                page={getPageNumber(paramsPage)}
+                                onChange={(page) => {
+                                    searchParams.set("page", page.toString());
+                                    setSearchParams(searchParams);
+                                }}
+                                total={total}
+                            />
+                        ) : (
+                            <Group>
+                                <Button
+                                    variant="default"
+                                    disabled={!publicFiles?.prev}
+                                    onClick={() => {
+                                        searchParams.set(
+                                            "cursor",
+                                            publicFiles?.prev ?? ""
+                                        );
+                                        setSearchParams(searchParams);
+                                    }}
+                                >
+                                    Previous
+                                </Button>
+                                <Button
+                                    variant="default"
+                                    disabled={!publicFiles?.next}
+                                    onClick={() => {
+                                        searchParams.set(
+                                            "cursor",
+                                            publicFiles?.next ?? ""
+                                        );
+                                        setSearchParams(searchParams);
+                                    }}
+                                >
+                                    Next
+                                </Button>
+                            </Group>
+                        )}
                     </Center>
                 </Paper>
             </Grid.Col>